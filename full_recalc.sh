@@ -9,5 +9,5 @@ mkdir -p "${PARSED_ACTS}"
     cd ../hun_law
     xargs -a "${RECALC_WHAT}" -d"\n" cargo run --release -- -o "${PARSED_ACTS}" -i
 )
-sed 's!.*!db/parsed_acts/\0.yml!' "${RECALC_WHAT}" | xargs -d"\n" cargo run --profile dev-fast -- add
+sed 's!.*!db/parsed_acts/\0.yml!' "${RECALC_WHAT}" | xargs -d"\n" cargo run --profile dev-fast -- add --no-recalculate
 RUST_LOG=warn cargo run --profile dev-fast -- recalculate 2010-01-01 2024-12-02
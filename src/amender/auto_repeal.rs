@@ -93,13 +93,12 @@ impl<'a> AutoRepealAccumulator<'a> {
         self.positions
             .into_iter()
             .map(|p| {
-                Ok(AppliableModification {
-                    modification: SimplifiedRepeal {
+                Ok(AppliableModification::new(
+                    ChangeCause::AutoRepeal,
+                    SimplifiedRepeal {
                         position: p.relative_to(act_ref)?,
-                    }
-                    .into(),
-                    cause: ChangeCause::AutoRepeal,
-                })
+                    },
+                ))
             })
             .collect::<Result<Vec<_>>>()
     }
@@ -24,23 +24,49 @@ use hun_law::{
     reference::{to_element::ReferenceToElement, Reference},
     structure::{
         Act, AlphabeticPoint, AlphabeticPointChildren, Article, BlockAmendmentChildren,
-        ChildrenCommon, LastChange, NumericPoint, NumericPointChildren, Paragraph,
-        ParagraphChildren, SAEBody, SubArticleElement,
+        ChildrenCommon, NumericPoint, NumericPointChildren, Paragraph, ParagraphChildren, SAEBody,
+        SubArticleElement,
+    },
+    util::{
+        debug::{DebugContextString, WithElemContext},
+        walker::SAEVisitorMut,
     },
-    util::debug::{DebugContextString, WithElemContext},
 };
 use serde::{Deserialize, Serialize};
 
-use super::{AffectedAct, ModifyAct, NeedsFullReparse};
+use super::{change_history::ChangeRecorder, AffectedAct, ModifyAct, NeedsFullReparse};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BlockAmendmentWithContent {
     pub position: Reference,
     pub content: BlockAmendmentChildren,
+    #[serde(default, skip_serializing_if = "AmendedBodyPart::is_all")]
+    pub body_part: AmendedBodyPart,
+}
+
+/// The part of the element at `position` that is replaced.
+///
+/// In case of IntroOnly and WrapUpOnly ("nyitó/záró szövegrész"), the content
+/// is a single element, and its text body is the new intro or wrap-up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AmendedBodyPart {
+    #[default]
+    All,
+    IntroOnly,
+    WrapUpOnly,
+}
+
+impl AmendedBodyPart {
+    pub fn is_all(&self) -> bool {
+        *self == Self::All
+    }
 }
 
 impl ModifyAct for BlockAmendmentWithContent {
-    fn apply(&self, act: &mut Act, change_entry: &LastChange) -> Result<NeedsFullReparse> {
+    fn apply(&self, act: &mut Act, change_entry: &ChangeRecorder) -> Result<NeedsFullReparse> {
+        if self.body_part != AmendedBodyPart::All {
+            return self.apply_partial_body(act, change_entry);
+        }
         let base_ref = act.reference();
         let act_dbg_string = act.debug_ctx();
         let article =
@@ -67,12 +93,37 @@ macro_rules! try_parse {
                     ..
                 } = &mut $base_element.body
                 {
-                    return modify_multiple(original_content, range, content, true, $change_entry);
+                    return modify_multiple(
+                        original_content,
+                        range,
+                        content,
+                        true,
+                        &$self.position.parent(),
+                        $change_entry,
+                    );
                 } else {
-                    bail!(
-                        "Wrong original content for {} reference",
-                        stringify!($part_type)
-                    )
+                    // Level change: the element had no children, so its text
+                    // becomes the intro of the new children. Children of a
+                    // different type cannot be addressed by the amended range,
+                    // so replacing them would silently drop text.
+                    if let SAEBody::Children { .. } = &$base_element.body {
+                        bail!(
+                            "Block amendment of {} would replace children of a different type",
+                            stringify!($part_type)
+                        )
+                    }
+                    let mut content =
+                        with_last_change(content, &$self.position.parent(), $change_entry)?;
+                    if let Some(ending) = $base_element.get_ending_punctuation() {
+                        if let Some(last) = content.last_mut() {
+                            last.fix_ending_punctuation(ending);
+                        }
+                    }
+                    replace_children(
+                        &mut $base_element.body,
+                        $ChildrenType1::$ChildrenType2(content),
+                    );
+                    return Ok(());
                 }
             } else {
                 bail!(
@@ -85,11 +136,41 @@ macro_rules! try_parse {
 }
 
 impl BlockAmendmentWithContent {
+    fn apply_partial_body(
+        &self,
+        act: &mut Act,
+        change_entry: &ChangeRecorder,
+    ) -> Result<NeedsFullReparse> {
+        let text = single_text_body(&self.content)?;
+        let mut visitor = PartialBodyReplacer {
+            position: &self.position,
+            body_part: self.body_part,
+            text,
+            change_entry,
+            applied: false,
+        };
+        act.walk_saes_mut(&mut visitor)?;
+        if !visitor.applied {
+            bail!(
+                "Could not find element {} for {:?} block amendment",
+                self.position,
+                self.body_part
+            );
+        }
+        let article_id = self
+            .position
+            .article()
+            .ok_or_else(|| anyhow!("No article in block amendment position"))?
+            .first_in_range();
+        let abbrevs_changed = act.add_semantic_info_to_article(article_id)?;
+        Ok(abbrevs_changed.into())
+    }
+
     fn apply_to_article(
         &self,
         article: &mut Article,
         base_ref: &Reference,
-        change_entry: &LastChange,
+        change_entry: &ChangeRecorder,
     ) -> Result<()> {
         let parent_ref = self.position.parent();
         if let Some(range) = self.position.get_last_part().paragraph() {
@@ -99,7 +180,14 @@ impl BlockAmendmentWithContent {
                     range.first_in_range().into(),
                     range.last_in_range().into(),
                 );
-                return modify_multiple(&mut article.children, range, content, false, change_entry);
+                return modify_multiple(
+                    &mut article.children,
+                    range,
+                    content,
+                    false,
+                    &parent_ref,
+                    change_entry,
+                );
             } else {
                 bail!("Wrong amendment content for paragraph reference");
             }
@@ -114,7 +202,7 @@ impl BlockAmendmentWithContent {
         &self,
         paragraph: &mut Paragraph,
         base_ref: &Reference,
-        change_entry: &LastChange,
+        change_entry: &ChangeRecorder,
     ) -> Result<()> {
         let parent_ref = self.position.parent();
         try_parse!(
@@ -158,7 +246,7 @@ impl BlockAmendmentWithContent {
     fn apply_to_alphabetic_point(
         &self,
         alphabetic_point: &mut AlphabeticPoint,
-        change_entry: &LastChange,
+        change_entry: &ChangeRecorder,
     ) -> Result<()> {
         try_parse!(
             self,
@@ -182,7 +270,7 @@ impl BlockAmendmentWithContent {
     fn apply_to_numeric_point(
         &self,
         numeric_point: &mut NumericPoint,
-        change_entry: &LastChange,
+        change_entry: &ChangeRecorder,
     ) -> Result<()> {
         try_parse!(
             self,
@@ -217,17 +305,111 @@ fn find_containing_element<'a, Item: ReferenceToElement>(
     ))
 }
 
+/// Copy the new elements, marking them as changed. `parent_ref` is the
+/// reference of the element they are inserted into.
+fn with_last_change<IT, CT>(
+    elements: &[SubArticleElement<IT, CT>],
+    parent_ref: &Reference,
+    change_entry: &ChangeRecorder,
+) -> Result<Vec<SubArticleElement<IT, CT>>>
+where
+    IT: IdentifierCommon,
+    CT: ChildrenCommon + std::fmt::Debug + Clone,
+    SubArticleElement<IT, CT>: ReferenceToElement,
+{
+    elements
+        .iter()
+        .map(|c| {
+            Ok(SubArticleElement {
+                last_change: change_entry.mark(c.reference().relative_to(parent_ref)?),
+                ..c.clone()
+            })
+        })
+        .collect()
+}
+
+/// Add children to an element that had none. Its text becomes the intro.
+fn replace_children<CT>(body: &mut SAEBody<CT>, new_children: CT) {
+    if let SAEBody::Text(text) = body {
+        let intro = std::mem::take(text)
+            .trim_end_matches(['.', ';', ','])
+            .to_string();
+        *body = SAEBody::Children {
+            intro,
+            children: new_children,
+            wrap_up: None,
+        };
+    }
+}
+
+/// The text of the single element in the content of an intro or wrap-up amendment
+fn single_text_body(content: &BlockAmendmentChildren) -> Result<&str> {
+    let body = match content {
+        BlockAmendmentChildren::Paragraph(x) if x.len() == 1 => &x[0].body,
+        BlockAmendmentChildren::AlphabeticPoint(x) if x.len() == 1 => &x[0].body,
+        BlockAmendmentChildren::NumericPoint(x) if x.len() == 1 => &x[0].body,
+        BlockAmendmentChildren::AlphabeticSubpoint(x) if x.len() == 1 => &x[0].body,
+        BlockAmendmentChildren::NumericSubpoint(x) if x.len() == 1 => &x[0].body,
+        _ => bail!("Intro or wrap-up amendment content should be a single element"),
+    };
+    if let SAEBody::Text(text) = body {
+        Ok(text)
+    } else {
+        bail!("Intro or wrap-up amendment content should be a single text element")
+    }
+}
+
+struct PartialBodyReplacer<'a> {
+    position: &'a Reference,
+    body_part: AmendedBodyPart,
+    text: &'a str,
+    change_entry: &'a ChangeRecorder,
+    applied: bool,
+}
+
+impl<'a> SAEVisitorMut for PartialBodyReplacer<'a> {
+    fn on_enter<IT: IdentifierCommon, CT: ChildrenCommon>(
+        &mut self,
+        position: &Reference,
+        element: &mut SubArticleElement<IT, CT>,
+    ) -> Result<()> {
+        if position != self.position {
+            return Ok(());
+        }
+        // The wrap-up ends the element, so the new one should end the same
+        // way, just like the last element of a full replacement.
+        let ending = element.get_ending_punctuation();
+        if let SAEBody::Children { intro, wrap_up, .. } = &mut element.body {
+            match self.body_part {
+                AmendedBodyPart::IntroOnly => *intro = self.text.to_string(),
+                AmendedBodyPart::WrapUpOnly => *wrap_up = Some(self.text.to_string()),
+                AmendedBodyPart::All => bail!("Full body amendments are handled separately"),
+            }
+            if let (AmendedBodyPart::WrapUpOnly, Some(ending)) = (self.body_part, ending) {
+                element.fix_ending_punctuation(ending);
+            }
+            element.last_change = self.change_entry.mark(position.clone());
+            self.applied = true;
+            Ok(())
+        } else {
+            bail!("Intro or wrap-up amendment of an element without children")
+        }
+    }
+}
+
 /// Returns true if there was anything deleted
 fn modify_multiple<IT, CT>(
     elements: &mut Vec<SubArticleElement<IT, CT>>,
     id_to_replace: IdentifierRange<IT>,
     replacement: &[SubArticleElement<IT, CT>],
     fix_punctuation: bool,
-    change_entry: &LastChange,
+    parent_ref: &Reference,
+    change_entry: &ChangeRecorder,
 ) -> Result<()>
 where
     IT: IdentifierCommon,
     CT: ChildrenCommon + std::fmt::Debug + Clone,
+    SubArticleElement<IT, CT>: ReferenceToElement,
 {
     if fix_punctuation {
         let ending = if replacement.is_empty() {
@@ -251,10 +433,7 @@ where
         .first()
         .ok_or_else(|| anyhow!("Empty block amendment"))?
         .identifier;
-    let replacement_with_last_change = replacement.iter().map(|c| SubArticleElement {
-        last_change: Some(change_entry.clone()),
-        ..c.clone()
-    });
+    let replacement_with_last_change = with_last_change(replacement, parent_ref, change_entry)?;
     if let Some(insertion_index) = elements
         .iter()
         .position(|element| element.identifier > first_replacement_identifier)
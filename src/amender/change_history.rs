@@ -0,0 +1,691 @@
+// Copyright (c) 2022-2023, Alex Badics
+//
+// This file is part of AJDB
+//
+// AJDB is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// AJDB is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+};
+
+use chrono::NaiveDate;
+use hun_law::{
+    reference::Reference,
+    structure::{ChangeCause, LastChange},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    text_amendment::text_replace::MatchLevel, AppliableModification, AppliableModificationType,
+};
+use crate::{
+    annulment::DecisionIdentifier, enforcement_date_set::EnforcementDateSet,
+    fixups::FixupProvenance,
+};
+
+/// All changes of all articles and SAEs of an act, in the order they were applied.
+///
+/// The elements themselves only store their last change, which is also lost
+/// for children when a repeal collates them into their parent. The history
+/// is kept next to the act instead, and is only ever appended to.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Vec<ElementChanges>", into = "Vec<ElementChanges>")]
+pub struct ChangeHistory {
+    changes: BTreeMap<Reference, Vec<ChangeHistoryEntry>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeHistoryEntry {
+    pub date: NaiveDate,
+    pub cause: ChangeCause,
+    pub kind: ModificationKind,
+    /// The element was changed before it came into force, i.e. the version
+    /// before this change (or in case of a repeal, the element itself)
+    /// never entered into force.
+    #[serde(default)]
+    pub never_in_force: bool,
+    /// Set if the change was made by a modification added or changed by a
+    /// fixup, i.e. it depends on a manual correction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fixup: Option<FixupProvenance>,
+    /// Set if the change was published after it came into force, i.e. it
+    /// retroactively changed the versions between `date` and this date.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retroactive_since: Option<NaiveDate>,
+    /// Set if the change is an annulment by the Constitutional Court.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annulment: Option<DecisionIdentifier>,
+    /// The matching used for the replaced text of a text amendment, if it
+    /// was not exact (see `AppliableModification::max_match_level`).
+    #[serde(default, skip_serializing_if = "MatchLevel::is_exact")]
+    pub match_level: MatchLevel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModificationKind {
+    BlockAmendment,
+    Repeal,
+    TextAmendment,
+    StructuralBlockAmendment,
+    PendingAmendment,
+    TextOverride,
+}
+
+/// Serialized form of `ChangeHistory`, as JSON only supports string keys.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ElementChanges {
+    reference: Reference,
+    changes: Vec<ChangeHistoryEntry>,
+}
+
+impl ChangeHistory {
+    /// The changes of a specific element, oldest first.
+    pub fn get(&self, reference: &Reference) -> &[ChangeHistoryEntry] {
+        self.changes.get(reference).map_or(&[], |c| c.as_slice())
+    }
+
+    /// Changes made to the element before it came into force, i.e. the
+    /// versions (or the whole element, in case of a repeal) that never
+    /// entered into force.
+    pub fn never_in_force_changes(&self, reference: &Reference) -> Vec<&ChangeHistoryEntry> {
+        self.get(reference)
+            .iter()
+            .filter(|entry| entry.never_in_force)
+            .collect()
+    }
+
+    /// Changes that retroactively modified the version of the act in force
+    /// on `date`, i.e. that were published only after `date`.
+    pub fn retroactive_changes_at(&self, date: NaiveDate) -> Vec<&ChangeHistoryEntry> {
+        let mut result: Vec<&ChangeHistoryEntry> = Vec::new();
+        for entry in self.changes.values().flatten() {
+            if entry.date <= date
+                && entry.retroactive_since.map_or(false, |since| since > date)
+                && !result
+                    .iter()
+                    .any(|e| e.cause == entry.cause && e.date == entry.date)
+            {
+                result.push(entry);
+            }
+        }
+        result
+    }
+
+    /// The Constitutional Court decision that made `change`, if it was an
+    /// annulment.
+    pub fn annulment_of(&self, change: &LastChange) -> Option<DecisionIdentifier> {
+        self.changes
+            .values()
+            .flatten()
+            .find(|entry| entry.date == change.date && entry.cause == change.cause)
+            .and_then(|entry| entry.annulment)
+    }
+
+    /// Record the changes made by a single modification, i.e. the elements
+    /// marked in `change_entry` by the `ModifyAct` implementation.
+    ///
+    /// `enforcement_dates` should be calculated from the act before applying
+    /// the modifications. Elements that were not in force before the date of
+    /// the change are marked as never entered into force.
+    pub fn record(
+        &mut self,
+        modification: &AppliableModification,
+        change_entry: ChangeRecorder,
+        enforcement_dates: Option<&EnforcementDateSet>,
+    ) {
+        let date = change_entry.last_change.date;
+        let match_level = change_entry.match_level.get();
+        for reference in change_entry.changed.into_inner() {
+            let never_in_force =
+                enforcement_dates.map_or(false, |eds| !eds.is_in_force(&reference, date.pred()));
+            let entry = ChangeHistoryEntry {
+                date,
+                cause: modification.cause.clone(),
+                kind: ModificationKind::from(&modification.modification),
+                never_in_force,
+                fixup: modification.fixup.clone(),
+                retroactive_since: modification.retroactive_since,
+                annulment: modification.annulment,
+                match_level,
+            };
+            let entries = self.changes.entry(reference).or_default();
+            if !entries.contains(&entry) {
+                entries.push(entry);
+            }
+        }
+    }
+}
+
+/// The change made by a single modification, passed to `ModifyAct::apply`.
+///
+/// The implementations get the `last_change` of the elements they change
+/// through `mark`, which also collects the changed elements for the change
+/// history.
+#[derive(Debug)]
+pub struct ChangeRecorder {
+    last_change: LastChange,
+    max_match_level: MatchLevel,
+    changed: RefCell<Vec<Reference>>,
+    match_level: Cell<MatchLevel>,
+}
+
+impl ChangeRecorder {
+    pub fn new(last_change: LastChange) -> Self {
+        Self {
+            last_change,
+            max_match_level: MatchLevel::Exact,
+            changed: RefCell::new(Vec::new()),
+            match_level: Cell::new(MatchLevel::Exact),
+        }
+    }
+
+    /// Allow tolerant matching of the replaced text in text amendments
+    pub fn with_max_match_level(mut self, max_match_level: MatchLevel) -> Self {
+        self.max_match_level = max_match_level;
+        self
+    }
+
+    pub fn last_change(&self) -> &LastChange {
+        &self.last_change
+    }
+
+    pub fn max_match_level(&self) -> MatchLevel {
+        self.max_match_level
+    }
+
+    /// Record that the text could only be matched with a tolerant level
+    pub fn set_match_level(&self, match_level: MatchLevel) {
+        self.match_level.set(match_level);
+    }
+
+    /// Record that the element at `reference` (including the act) was
+    /// changed, and return its new `last_change`.
+    pub fn mark(&self, reference: Reference) -> Option<LastChange> {
+        let mut changed = self.changed.borrow_mut();
+        if !changed.contains(&reference) {
+            changed.push(reference);
+        }
+        Some(self.last_change.clone())
+    }
+}
+
+impl From<&AppliableModificationType> for ModificationKind {
+    fn from(modification: &AppliableModificationType) -> Self {
+        match modification {
+            AppliableModificationType::BlockAmendment(_) => Self::BlockAmendment,
+            AppliableModificationType::Repeal(_) => Self::Repeal,
+            AppliableModificationType::TextAmendment(_) => Self::TextAmendment,
+            AppliableModificationType::StructuralBlockAmendment(_) => {
+                Self::StructuralBlockAmendment
+            }
+            AppliableModificationType::PendingAmendment(_) => Self::PendingAmendment,
+            AppliableModificationType::TextOverride(_) => Self::TextOverride,
+        }
+    }
+}
+
+impl From<Vec<ElementChanges>> for ChangeHistory {
+    fn from(elements: Vec<ElementChanges>) -> Self {
+        Self {
+            changes: elements
+                .into_iter()
+                .map(|e| (e.reference, e.changes))
+                .collect(),
+        }
+    }
+}
+
+impl From<ChangeHistory> for Vec<ElementChanges> {
+    fn from(history: ChangeHistory) -> Self {
+        history
+            .changes
+            .into_iter()
+            .map(|(reference, changes)| ElementChanges { reference, changes })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hun_law::{semantic_info::EnforcementDate, structure::Act, util::singleton_yaml};
+
+    use super::*;
+    use crate::amender::{AppliableModificationSet, OnError};
+
+    /// Act 2012/1, with a single article containing the paragraphs
+    fn test_act(paragraphs: &[&str]) -> Act {
+        let mut yaml = String::from(concat!(
+            "identifier: {year: 2012, number: 1}\n",
+            "subject: Test act\n",
+            "preamble: ''\n",
+            "publication_date: 2012-01-01\n",
+            "children:\n",
+            "- Article:\n",
+            "    identifier: '1'\n",
+            "    children:\n",
+        ));
+        for (i, body) in paragraphs.iter().enumerate() {
+            yaml.push_str(&format!(
+                "    - identifier: '{}'\n      body: {:?}\n",
+                i + 1,
+                body
+            ));
+        }
+        singleton_yaml::from_str(&yaml).unwrap()
+    }
+
+    fn paragraph_ref(paragraph: &str) -> Reference {
+        singleton_yaml::from_str(&format!(
+            "{{act: {{year: 2012, number: 1}}, article: '1', paragraph: '{paragraph}'}}"
+        ))
+        .unwrap()
+    }
+
+    fn apply(
+        act: &mut Act,
+        date: NaiveDate,
+        modifications: &[AppliableModification],
+        history: &mut ChangeHistory,
+        enforcement_dates: Option<&EnforcementDateSet>,
+    ) {
+        AppliableModificationSet::apply_to_act_with_history(
+            act,
+            date,
+            modifications.to_vec(),
+            OnError::ReturnErr,
+            history,
+            enforcement_dates,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_record_keeps_history() {
+        let mut act = test_act(&["aaa", "bbb"]);
+        let modifications: Vec<AppliableModification> = singleton_yaml::from_str(
+            r#"
+            - cause:
+                Other: first
+              modification:
+                TextAmendment:
+                  reference:
+                    SAE:
+                      reference:
+                        act:
+                          year: 2012
+                          number: 1
+                        article: '1'
+                        paragraph: '1'
+                  from: aaa
+                  to: ccc
+            - cause:
+                Other: second
+              modification:
+                Repeal:
+                  position:
+                    act:
+                      year: 2012
+                      number: 1
+                    article: '1'
+                    paragraph: '1'
+            "#,
+        )
+        .unwrap();
+        let paragraph_ref = paragraph_ref("1");
+
+        let mut history = ChangeHistory::default();
+        let date_1 = NaiveDate::from_ymd(2013, 1, 1);
+        let date_2 = NaiveDate::from_ymd(2014, 1, 1);
+        for (date, modification) in [date_1, date_2].into_iter().zip(&modifications) {
+            apply(
+                &mut act,
+                date,
+                std::slice::from_ref(modification),
+                &mut history,
+                None,
+            );
+        }
+        // Recording the same changes again does not duplicate them
+        let change_entry = ChangeRecorder::new(LastChange {
+            date: date_2,
+            cause: modifications[1].cause.clone(),
+        });
+        change_entry.mark(paragraph_ref.clone());
+        history.record(&modifications[1], change_entry, None);
+
+        assert_eq!(
+            history.get(&paragraph_ref),
+            &[
+                ChangeHistoryEntry {
+                    date: date_1,
+                    cause: ChangeCause::Other("first".into()),
+                    kind: ModificationKind::TextAmendment,
+                    never_in_force: false,
+                    fixup: None,
+                    retroactive_since: None,
+                    annulment: None,
+                    match_level: MatchLevel::Exact,
+                },
+                ChangeHistoryEntry {
+                    date: date_2,
+                    cause: ChangeCause::Other("second".into()),
+                    kind: ModificationKind::Repeal,
+                    never_in_force: false,
+                    fixup: None,
+                    retroactive_since: None,
+                    annulment: None,
+                    match_level: MatchLevel::Exact,
+                },
+            ]
+        );
+
+        let serialized = serde_json::to_string(&history).unwrap();
+        let deserialized: ChangeHistory = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(history, deserialized);
+    }
+
+    #[test]
+    fn test_record_never_in_force() {
+        let mut act = test_act(&["aaa", "bbb"]);
+        let enforcement_dates: Vec<EnforcementDate> = singleton_yaml::from_str(
+            r#"
+            - date:
+                Date: 2012-02-01
+              is_default: true
+            - date:
+                Date: 2014-01-01
+              positions:
+                - article: "1"
+                  paragraph: "2"
+            "#,
+        )
+        .unwrap();
+        let ed_set = EnforcementDateSet::from_enforcement_dates(&enforcement_dates, &act).unwrap();
+        let modification: AppliableModification = singleton_yaml::from_str(
+            r#"
+            cause:
+              Other: repeal
+            modification:
+              Repeal:
+                position:
+                  act:
+                    year: 2012
+                    number: 1
+                  article: '1'
+                  paragraph: '2'
+            "#,
+        )
+        .unwrap();
+        let paragraph_1 = paragraph_ref("1");
+        let paragraph_2 = paragraph_ref("2");
+
+        let date = NaiveDate::from_ymd(2013, 1, 1);
+        let mut history = ChangeHistory::default();
+        apply(
+            &mut act,
+            date,
+            std::slice::from_ref(&modification),
+            &mut history,
+            Some(&ed_set),
+        );
+
+        assert!(history.never_in_force_changes(&paragraph_1).is_empty());
+        assert_eq!(
+            history
+                .never_in_force_changes(&paragraph_2)
+                .iter()
+                .map(|entry| entry.kind)
+                .collect::<Vec<_>>(),
+            vec![ModificationKind::Repeal]
+        );
+    }
+
+    #[test]
+    fn test_record_fixup() {
+        let mut act = test_act(&["aaa"]);
+        let modification: AppliableModification = singleton_yaml::from_str(
+            r#"
+            cause:
+              Other: override
+            modification:
+              TextOverride:
+                position:
+                  act:
+                    year: 2012
+                    number: 1
+                  article: '1'
+                  paragraph: '1'
+                text: bbb
+            fixup:
+              file: data/fixups/act/2012/test.yml
+              index: 3
+              note: Hibás a közlöny szövege
+            "#,
+        )
+        .unwrap();
+        let paragraph_ref = paragraph_ref("1");
+
+        let date = NaiveDate::from_ymd(2013, 1, 1);
+        let mut history = ChangeHistory::default();
+        apply(
+            &mut act,
+            date,
+            std::slice::from_ref(&modification),
+            &mut history,
+            None,
+        );
+
+        let entries = history.get(&paragraph_ref);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].kind, ModificationKind::TextOverride);
+        assert_eq!(entries[0].fixup, modification.fixup);
+    }
+
+    #[test]
+    fn test_record_retroactive() {
+        let mut act = test_act(&["aaa"]);
+        let modification: AppliableModification = singleton_yaml::from_str(
+            r#"
+            cause:
+              Other: retroactive
+            modification:
+              Repeal:
+                position:
+                  act:
+                    year: 2012
+                    number: 1
+                  article: '1'
+                  paragraph: '1'
+            retroactive_since: 2013-03-01
+            "#,
+        )
+        .unwrap();
+
+        let date = NaiveDate::from_ymd(2013, 1, 1);
+        let mut history = ChangeHistory::default();
+        apply(
+            &mut act,
+            date,
+            std::slice::from_ref(&modification),
+            &mut history,
+            None,
+        );
+
+        assert!(history
+            .retroactive_changes_at(NaiveDate::from_ymd(2012, 12, 31))
+            .is_empty());
+        let changes = history.retroactive_changes_at(NaiveDate::from_ymd(2013, 2, 1));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes[0].retroactive_since,
+            Some(NaiveDate::from_ymd(2013, 3, 1))
+        );
+        assert!(history
+            .retroactive_changes_at(NaiveDate::from_ymd(2013, 3, 1))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_record_multiple_causes_on_the_same_date() {
+        let mut act = test_act(&["aaa bbb ccc", "ddd"]);
+        let modifications: Vec<AppliableModification> = singleton_yaml::from_str(
+            r#"
+            - cause:
+                Other: first
+              modification:
+                TextAmendment:
+                  reference:
+                    SAE:
+                      reference:
+                        act:
+                          year: 2012
+                          number: 1
+                        article: '1'
+                        paragraph: '1'
+                  from: aaa
+                  to: xxx
+            - cause:
+                Other: second
+              modification:
+                TextAmendment:
+                  reference:
+                    SAE:
+                      reference:
+                        act:
+                          year: 2012
+                          number: 1
+                        article: '1'
+                        paragraph: '1'
+                  from: bbb
+                  to: yyy
+            - cause:
+                Other: first
+              modification:
+                TextAmendment:
+                  reference:
+                    SAE:
+                      reference:
+                        act:
+                          year: 2012
+                          number: 1
+                        article: '1'
+                        paragraph: '1'
+                  from: ccc
+                  to: zzz
+            - cause:
+                Other: second
+              modification:
+                TextOverride:
+                  position:
+                    act:
+                      year: 2012
+                      number: 1
+                    article: '1'
+                    paragraph: '2'
+                  text: eee
+            "#,
+        )
+        .unwrap();
+        let paragraph_1 = paragraph_ref("1");
+        let paragraph_2 = paragraph_ref("2");
+
+        let date = NaiveDate::from_ymd(2013, 1, 1);
+        let mut history = ChangeHistory::default();
+        apply(&mut act, date, &modifications, &mut history, None);
+
+        // Every cause is kept, even though only the last one is in the
+        // last_change of the element.
+        let mut causes: Vec<_> = history
+            .get(&paragraph_1)
+            .iter()
+            .map(|entry| (entry.cause.clone(), entry.kind))
+            .collect();
+        causes.sort_by_key(|(cause, _)| format!("{cause:?}"));
+        assert_eq!(
+            causes,
+            vec![
+                (
+                    ChangeCause::Other("first".into()),
+                    ModificationKind::TextAmendment
+                ),
+                (
+                    ChangeCause::Other("second".into()),
+                    ModificationKind::TextAmendment
+                ),
+            ]
+        );
+        // Only the element that was actually changed is attributed to a cause
+        assert_eq!(
+            history
+                .get(&paragraph_2)
+                .iter()
+                .map(|entry| (&entry.cause, entry.kind))
+                .collect::<Vec<_>>(),
+            vec![(
+                &ChangeCause::Other("second".into()),
+                ModificationKind::TextOverride
+            )]
+        );
+    }
+
+    #[test]
+    fn test_record_match_level() {
+        let mut act = test_act(&["a miniszter – rendeletben – állapítja meg"]);
+        let mut modifications: Vec<AppliableModification> = singleton_yaml::from_str(
+            r#"
+            - cause:
+                Other: first
+              modification:
+                TextAmendment:
+                  reference:
+                    SAE:
+                      reference:
+                        act:
+                          year: 2012
+                          number: 1
+                        article: '1'
+                        paragraph: '1'
+                  from: miniszter - rendeletben -
+                  to: Kormány rendeletben
+            "#,
+        )
+        .unwrap();
+        let paragraph_1 = paragraph_ref("1");
+        let date = NaiveDate::from_ymd(2013, 1, 1);
+
+        // Tolerant matching is only used if a fixup allows it
+        let mut history = ChangeHistory::default();
+        assert!(AppliableModificationSet::apply_to_act_with_history(
+            &mut act.clone(),
+            date,
+            modifications.clone(),
+            OnError::ReturnErr,
+            &mut history,
+            None,
+        )
+        .is_err());
+
+        modifications[0].max_match_level = MatchLevel::Typographic;
+        apply(&mut act, date, &modifications, &mut history, None);
+        assert_eq!(
+            history
+                .get(&paragraph_1)
+                .iter()
+                .map(|entry| entry.match_level)
+                .collect::<Vec<_>>(),
+            vec![MatchLevel::Typographic]
+        );
+    }
+}
@@ -18,9 +18,9 @@
 use anyhow::{bail, Result};
 use chrono::NaiveDate;
 use hun_law::{
-    identifier::IdentifierCommon,
+    identifier::{ActIdentifier, IdentifierCommon},
     reference::{structural::StructuralReference, to_element::ReferenceToElement, Reference},
-    semantic_info::{EnforcementDate, RepealReference, SpecialPhrase},
+    semantic_info::{RepealReference, SpecialPhrase},
     structure::{
         Act, ActChild, BlockAmendment, ChangeCause, ChildrenCommon, Paragraph, ParagraphChildren,
         SAEBody, SubArticleElement,
@@ -33,9 +33,12 @@ use hun_law::{
 use log::info;
 
 use super::{
-    auto_repeal::AutoRepealAccumulator, block_amendment::BlockAmendmentWithContent,
-    repeal::SimplifiedRepeal, structural_amendment::StructuralBlockAmendmentWithContent,
-    AppliableModification, AppliableModificationType,
+    auto_repeal::AutoRepealAccumulator,
+    block_amendment::BlockAmendmentWithContent,
+    pending_amendment::PendingAmendment,
+    repeal::{get_act_repeal, SimplifiedRepeal},
+    structural_amendment::StructuralBlockAmendmentWithContent,
+    AffectedAct, AppliableModification, AppliableModificationType,
 };
 use crate::{enforcement_date_set::EnforcementDateSet, fixups::ActFixups};
 
@@ -46,9 +49,23 @@ pub fn extract_modifications_from_act(
     act: &Act,
     date: NaiveDate,
 ) -> Result<Vec<AppliableModification>> {
+    extract_modifications_with_fixups(act, date, &ActFixups::load(act.identifier)?)
+}
+
+/// Same as `extract_modifications_from_act`, but with explicitly specified
+/// fixups, instead of the ones in the act's fixup file.
+pub fn extract_modifications_with_fixups(
+    act: &Act,
+    date: NaiveDate,
+    act_fixups: &ActFixups,
+) -> Result<Vec<AppliableModification>> {
+    if get_act_repeal(act).is_some() {
+        // Repealed acts cannot modify anything, and they don't have enforcement dates either.
+        return Ok(Vec::new());
+    }
     // TODO: this should probably be stored in the act_entry
     let ed_set = EnforcementDateSet::from_act(act)?;
-    let fixups = ActFixups::load(act.identifier)?.get_additional_modifications();
+    let fixups = act_fixups.get_additional_modifications();
     if !fixups.is_empty() {
         info!("Fixup: Using {} additional modifications", fixups.len());
     }
@@ -76,6 +93,42 @@ pub fn extract_modifications_from_act(
     }
     let mut result = visitor.result;
     result.extend(auto_repeals.get_result(&act.reference())?);
+    result.extend(get_expiry_repeals(act, &ed_set, date)?);
+    act_fixups.fix_modifications(&mut result);
+    result.extend(act_fixups.get_text_override_modifications(date));
+    if date < act.publication_date {
+        for modification in &mut result {
+            modification.retroactive_since = Some(act.publication_date);
+        }
+    }
+    Ok(result)
+}
+
+/// Repeals of the elements (or the whole act) that expire on the date.
+fn get_expiry_repeals(
+    act: &Act,
+    ed_set: &EnforcementDateSet,
+    date: NaiveDate,
+) -> Result<Vec<AppliableModification>> {
+    let act_ref = act.reference();
+    let mut result = Vec::new();
+    for expiry in ed_set.expiries_on(date) {
+        let positions = if expiry.positions.is_empty() {
+            vec![act_ref.clone()]
+        } else {
+            expiry
+                .positions
+                .iter()
+                .map(|p| p.relative_to(&act_ref))
+                .collect::<Result<_>>()?
+        };
+        for position in positions {
+            result.push(AppliableModification::new(
+                ChangeCause::Amendment(expiry.cause.clone()),
+                SimplifiedRepeal { position },
+            ));
+        }
+    }
     Ok(result)
 }
 
@@ -123,14 +176,17 @@ fn get_modifications_for_block_amendment(
     visitor: &mut ModificationAccumulator,
 ) -> Result<()> {
     if let Some(SpecialPhrase::BlockAmendment(ba_se)) = &paragraph.semantic_info.special_phrase {
-        visitor.result.push(AppliableModification {
-            modification: BlockAmendmentWithContent {
-                position: ba_se.position.clone(),
-                content: ba_content.children.clone(),
-            }
-            .into(),
-            cause: ChangeCause::Amendment(paragraph_ref),
-        })
+        let modification = BlockAmendmentWithContent {
+            position: ba_se.position.clone(),
+            content: ba_content.children.clone(),
+            body_part: Default::default(),
+        }
+        .into();
+        let modification = wrap_if_pending(modification, paragraph, &paragraph_ref)?;
+        visitor.result.push(AppliableModification::new(
+            ChangeCause::Amendment(paragraph_ref),
+            modification,
+        ))
     } else {
         bail!(
             "Invalid special phrase for BlockAmendment container: {:?}",
@@ -148,15 +204,17 @@ fn get_modifications_for_structural_block_amendment(
 ) -> Result<()> {
     match &paragraph.semantic_info.special_phrase {
         Some(SpecialPhrase::StructuralBlockAmendment(ba_se)) => {
-            visitor.result.push(AppliableModification {
-                modification: StructuralBlockAmendmentWithContent {
-                    position: ba_se.position.clone(),
-                    pure_insertion: ba_se.pure_insertion,
-                    content: ba_content.into(),
-                }
-                .into(),
-                cause: ChangeCause::Amendment(paragraph_ref),
-            })
+            let modification = StructuralBlockAmendmentWithContent {
+                position: ba_se.position.clone(),
+                pure_insertion: ba_se.pure_insertion,
+                content: ba_content.into(),
+            }
+            .into();
+            let modification = wrap_if_pending(modification, paragraph, &paragraph_ref)?;
+            visitor.result.push(AppliableModification::new(
+                ChangeCause::Amendment(paragraph_ref),
+                modification,
+            ))
         }
         _ => bail!(
             "Invalid special phrase for BlockAmendment container: {:?}",
@@ -191,17 +249,18 @@ impl<'a> SAEVisitor for ModificationAccumulator<'a> {
                                         position: reference.clone(),
                                     }
                                     .into(),
+                                    element,
                                     position,
-                                ),
+                                )?,
                                 RepealReference::StructuralReference(reference) => {
-                                    self.handle_structural_repeal(reference, position)
+                                    self.handle_structural_repeal(reference, element, position)?
                                 }
                             }
                         }
                     }
                     SpecialPhrase::TextAmendment(tas) => {
                         for ta in tas {
-                            self.add(ta.clone().into(), position)
+                            self.add(ta.clone().into(), element, position)?
                         }
                     }
                     // These are handled specially with get_modifications_for_block_amendment
@@ -218,38 +277,30 @@ impl<'a> SAEVisitor for ModificationAccumulator<'a> {
                 }
             }
         }
-        // Store inline repeals too
-        if let Some(SpecialPhrase::EnforcementDate(EnforcementDate {
-            inline_repeal: Some(inline_repeal),
-            ..
-        })) = &element.semantic_info.special_phrase
-        {
-            if *inline_repeal == self.date {
-                let act_id = position
-                    .act()
-                    .ok_or_else(|| anyhow::anyhow!("No act in reference"))?;
-                self.add(
-                    SimplifiedRepeal {
-                        position: act_id.into(),
-                    }
-                    .into(),
-                    position,
-                )
-            }
-        }
         Ok(())
     }
 }
 
 impl<'a> ModificationAccumulator<'a> {
-    fn add(&mut self, modification: AppliableModificationType, cause: &Reference) {
-        self.result.push(AppliableModification {
-            cause: ChangeCause::Amendment(cause.clone()),
-            modification,
-        })
+    fn add<IT: IdentifierCommon, CT: ChildrenCommon>(
+        &mut self,
+        modification: AppliableModificationType,
+        element: &SubArticleElement<IT, CT>,
+        cause: &Reference,
+    ) -> Result<()> {
+        self.result.push(AppliableModification::new(
+            ChangeCause::Amendment(cause.clone()),
+            wrap_if_pending(modification, element, cause)?,
+        ));
+        Ok(())
     }
 
-    fn handle_structural_repeal(&mut self, position: &StructuralReference, cause: &Reference) {
+    fn handle_structural_repeal<IT: IdentifierCommon, CT: ChildrenCommon>(
+        &mut self,
+        position: &StructuralReference,
+        element: &SubArticleElement<IT, CT>,
+        cause: &Reference,
+    ) -> Result<()> {
         self.add(
             StructuralBlockAmendmentWithContent {
                 position: position.clone(),
@@ -257,7 +308,73 @@ impl<'a> ModificationAccumulator<'a> {
                 content: Vec::new(),
             }
             .into(),
+            element,
             cause,
         )
     }
 }
+
+/// Wrap the modification into a `PendingAmendment`, if it modifies content
+/// established by a provision of another act (see `establishing_provision`).
+fn wrap_if_pending<IT: IdentifierCommon, CT: ChildrenCommon>(
+    modification: AppliableModificationType,
+    element: &SubArticleElement<IT, CT>,
+    position: &Reference,
+) -> Result<AppliableModificationType> {
+    let amended_act = modification.affected_act()?;
+    Ok(
+        match establishing_provision(element, position, amended_act) {
+            Some(provision) => PendingAmendment {
+                position: provision,
+                modification: Box::new(modification),
+            }
+            .into(),
+            None => modification,
+        },
+    )
+}
+
+/// The provision of another act that established the amended content, in
+/// texts like "az X. törvény 3. § (2) bekezdésével megállapított Y. törvény
+/// 5. § (1) bekezdés b) pontjában ...".
+///
+/// This form is used for amending block amendments that are not in force yet.
+/// `hun_law` resolves the position of the modification to the amended act, so
+/// the establishing provision is the reference right before the position,
+/// with "megállapított" between them.
+fn establishing_provision<IT: IdentifierCommon, CT: ChildrenCommon>(
+    element: &SubArticleElement<IT, CT>,
+    position: &Reference,
+    amended_act: ActIdentifier,
+) -> Option<Reference> {
+    let text = match &element.body {
+        SAEBody::Text(text) => text,
+        SAEBody::Children { intro, .. } => intro,
+    };
+    let mut references = element
+        .semantic_info
+        .outgoing_references
+        .iter()
+        .collect::<Vec<_>>();
+    references.sort_by_key(|r| r.start);
+    for pair in references.windows(2) {
+        // Unresolvable references are reported by the validation, they are not
+        // a problem here.
+        let (provision, next) = match (
+            pair[0].reference.relative_to(position),
+            pair[1].reference.relative_to(position),
+        ) {
+            (Ok(provision), Ok(next)) => (provision, next),
+            _ => continue,
+        };
+        let between = text.get(pair[0].end..pair[1].start).unwrap_or_default();
+        if next.act() == Some(amended_act)
+            && provision.act().map_or(false, |act| act != amended_act)
+            && provision.article().is_some()
+            && between.contains("megállapított")
+        {
+            return Some(provision);
+        }
+    }
+    None
+}
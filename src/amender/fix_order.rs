@@ -15,6 +15,9 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::BTreeSet;
+
+use anyhow::{anyhow, Result};
 use hun_law::{
     reference::{
         structural::{StructuralReference, StructuralReferenceElement},
@@ -25,74 +28,188 @@ use hun_law::{
 
 use super::{AppliableModification, AppliableModificationType};
 
-pub fn fix_amendment_order(modifications: &mut [AppliableModification]) {
-    let mut i = 0;
-    while let Some((earlier, rest)) = modifications[i..].split_first_mut() {
-        for later in rest {
-            if amendment_order_wrong(&earlier.modification, &later.modification) {
-                std::mem::swap(earlier, later);
+/// Reorder the modifications so that every modification comes after the ones
+/// it depends on (see `must_precede`). Modifications without dependencies
+/// between them keep their original relative order.
+///
+/// In case of a dependency cycle, the modifications in the cycle (and the ones
+/// depending on them) are put at the end in their original order, and an error
+/// describing them is returned. The slice is still reordered in this case.
+pub fn fix_amendment_order(modifications: &mut [AppliableModification]) -> Result<()> {
+    let n = modifications.len();
+    // successors[i] are the modifications that must come after modifications[i]
+    let mut successors = vec![Vec::new(); n];
+    let mut in_degree = vec![0usize; n];
+    for (i, first) in modifications.iter().enumerate() {
+        for (j, second) in modifications.iter().enumerate() {
+            if i != j && must_precede(&first.modification, &second.modification) {
+                successors[i].push(j);
+                in_degree[j] += 1;
             }
         }
-        i += 1;
     }
+
+    // Kahn's algorithm, always choosing the ready modification that was
+    // earliest in the original order, so that the sort is stable.
+    let mut ready: BTreeSet<usize> = (0..n).filter(|&i| in_degree[i] == 0).collect();
+    let mut order = Vec::with_capacity(n);
+    while let Some(i) = ready.pop_first() {
+        order.push(i);
+        for &j in &successors[i] {
+            in_degree[j] -= 1;
+            if in_degree[j] == 0 {
+                ready.insert(j);
+            }
+        }
+    }
+
+    let in_cycle: Vec<usize> = (0..n).filter(|&i| in_degree[i] != 0).collect();
+    let cycle_error = if in_cycle.is_empty() {
+        None
+    } else {
+        let mut description = String::new();
+        for &i in &in_cycle {
+            for &j in successors[i].iter().filter(|&&j| in_degree[j] != 0) {
+                description.push_str(&format!(
+                    "\n  {} ({:?}) must precede {} ({:?})",
+                    modification_type_name(&modifications[i].modification),
+                    modifications[i].cause,
+                    modification_type_name(&modifications[j].modification),
+                    modifications[j].cause,
+                ));
+            }
+        }
+        Some(anyhow!(
+            "Cyclic dependency between {} modifications, they were left in their original order:{}",
+            in_cycle.len(),
+            description
+        ))
+    };
+
+    order.extend(in_cycle);
+    let reordered: Vec<_> = order.iter().map(|&i| modifications[i].clone()).collect();
+    modifications.clone_from_slice(&reordered);
+    cycle_error.map_or(Ok(()), Err)
 }
 
-fn amendment_order_wrong(
-    earlier: &AppliableModificationType,
-    later: &AppliableModificationType,
-) -> bool {
-    match (earlier, later) {
+fn modification_type_name(modification: &AppliableModificationType) -> &'static str {
+    match modification {
+        AppliableModificationType::BlockAmendment(_) => "BlockAmendment",
+        AppliableModificationType::Repeal(_) => "Repeal",
+        AppliableModificationType::TextAmendment(_) => "TextAmendment",
+        AppliableModificationType::StructuralBlockAmendment(_) => "StructuralBlockAmendment",
+        AppliableModificationType::PendingAmendment(_) => "PendingAmendment",
+        AppliableModificationType::TextOverride(_) => "TextOverride",
+    }
+}
+
+/// Returns true if `first` has to be applied before `second`.
+fn must_precede(first: &AppliableModificationType, second: &AppliableModificationType) -> bool {
+    use AppliableModificationType as AMT;
+    match (first, second) {
+        // Repeals come last: amending something that was already repealed in
+        // the same step would fail.
+        (AMT::Repeal(_), _) => false,
+        (_, AMT::Repeal(repeal)) => affected_reference(first).map_or(false, |first_ref| {
+            first_ref.contains(&repeal.position) || repeal.position.contains(&first_ref)
+        }),
+        // Text overrides come after every other change of the element, as they
+        // are supposed to set the final text.
+        (AMT::TextOverride(_), _) => false,
+        (_, AMT::TextOverride(text_override)) => {
+            affected_reference(first).map_or(false, |first_ref| {
+                first_ref.contains(&text_override.position)
+                    || text_override.position.contains(&first_ref)
+            })
+        }
+        (AMT::TextAmendment(first), AMT::TextAmendment(second)) => {
+            text_amendment_must_precede(first, second)
+        }
+        (AMT::PendingAmendment(first), AMT::PendingAmendment(second)) => {
+            // Same rules inside the same pending block amendment
+            first.position == second.position
+                && must_precede(&first.modification, &second.modification)
+        }
         (
-            AppliableModificationType::TextAmendment(TextAmendment {
-                reference:
-                    TextAmendmentReference::SAE {
-                        reference: earlier, ..
-                    },
-                from: earlier_from,
-                to: earlier_to,
-            }),
-            AppliableModificationType::TextAmendment(TextAmendment {
-                reference:
-                    TextAmendmentReference::SAE {
-                        reference: later, ..
-                    },
-                from: later_from,
+            AMT::StructuralBlockAmendment(first),
+            AMT::TextAmendment(TextAmendment {
+                reference: TextAmendmentReference::Structural(second_ref),
                 ..
             }),
-        ) if earlier.contains(later) || later.contains(earlier) => {
-            // Substring case, e.g.
-            // - from: aaa
-            //     to: bbb
-            // - from: aaa xxx
-            //     to: bbb zzz
-            //
-            later_from.contains(earlier_from)
-            // Semi-swap case
-            // -from: a
-            //    to: b c d
-            // -from: c
-            //    to: x
-            || earlier_to.contains(later_from)
-        }
-        (
-            AppliableModificationType::BlockAmendment(earlier),
-            AppliableModificationType::BlockAmendment(later),
         ) => {
-            // Modify then modify sub-element case
-            later.position.contains(&earlier.position)
+            // Replace the structural element, then modify its title
+            first.position.act == second_ref.act
+                && first.position.book == second_ref.book
+                && first.position.structural_element == second_ref.structural_element
         }
-        (
-            AppliableModificationType::BlockAmendment(earlier),
-            AppliableModificationType::StructuralBlockAmendment(later),
-        ) => {
-            // Modify then modify sub-element case ofr articles only
-            structural_ref_to_ref_maybe(&later.position)
-                .map_or(false, |later_ref| later_ref.contains(&earlier.position))
+        (AMT::BlockAmendment(_) | AMT::StructuralBlockAmendment(_), _) => {
+            // Replace the container, then modify a sub-element, or the text
+            // of the container itself
+            match (affected_reference(first), affected_reference(second)) {
+                (Some(first_ref), Some(second_ref)) => {
+                    first_ref.contains(&second_ref)
+                        && (first_ref != second_ref || matches!(second, AMT::TextAmendment(_)))
+                }
+                _ => false,
+            }
         }
         _ => false,
     }
 }
 
+fn text_amendment_must_precede(first: &TextAmendment, second: &TextAmendment) -> bool {
+    if let (
+        TextAmendmentReference::SAE {
+            reference: first_ref,
+            ..
+        },
+        TextAmendmentReference::SAE {
+            reference: second_ref,
+            ..
+        },
+    ) = (&first.reference, &second.reference)
+    {
+        if !first_ref.contains(second_ref) && !second_ref.contains(first_ref) {
+            return false;
+        }
+        // Substring case, e.g.
+        // - from: aaa xxx
+        //     to: bbb zzz
+        // - from: aaa
+        //     to: bbb
+        (first.from != second.from && first.from.contains(&second.from))
+        // Semi-swap case
+        // - from: c
+        //     to: x
+        // - from: a
+        //     to: b c d
+        // Amendments of the same text are not ordered by this rule: if both
+        // results contain the original text, it would apply both ways.
+        || (first.from != second.from && second.to.contains(&first.from))
+    } else {
+        false
+    }
+}
+
+/// The (non-structural) reference of the element the modification changes,
+/// if there is one.
+pub fn affected_reference(modification: &AppliableModificationType) -> Option<Reference> {
+    match modification {
+        AppliableModificationType::BlockAmendment(ba) => Some(ba.position.clone()),
+        AppliableModificationType::Repeal(repeal) => Some(repeal.position.clone()),
+        AppliableModificationType::TextAmendment(ta) => match &ta.reference {
+            TextAmendmentReference::SAE { reference, .. } => Some(reference.clone()),
+            TextAmendmentReference::ArticleTitle(reference) => Some(reference.clone()),
+            TextAmendmentReference::Structural(_) => None,
+        },
+        AppliableModificationType::StructuralBlockAmendment(sba) => {
+            structural_ref_to_ref_maybe(&sba.position)
+        }
+        AppliableModificationType::PendingAmendment(pa) => Some(pa.position.clone()),
+        AppliableModificationType::TextOverride(to) => Some(to.position.clone()),
+    }
+}
+
 fn structural_ref_to_ref_maybe(sr: &StructuralReference) -> Option<Reference> {
     if let StructuralReference {
         act: Some(act),
@@ -105,3 +222,253 @@ fn structural_ref_to_ref_maybe(sr: &StructuralReference) -> Option<Reference> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use hun_law::util::singleton_yaml;
+
+    use super::*;
+
+    fn modifications_from_yaml(yaml: &str) -> Vec<AppliableModification> {
+        singleton_yaml::from_str(yaml).unwrap()
+    }
+
+    fn causes(modifications: &[AppliableModification]) -> Vec<String> {
+        modifications
+            .iter()
+            .map(|m| format!("{:?}", m.cause))
+            .collect()
+    }
+
+    #[test]
+    fn test_repeal_after_amendment() {
+        let mut modifications = modifications_from_yaml(
+            r#"
+            - cause:
+                Other: repeal
+              modification:
+                Repeal:
+                  position:
+                    act:
+                      year: 2012
+                      number: 1
+                    article: '2'
+            - cause:
+                Other: text
+              modification:
+                TextAmendment:
+                  reference:
+                    SAE:
+                      reference:
+                        act:
+                          year: 2012
+                          number: 1
+                        article: '2'
+                        paragraph: '1'
+                  from: aaa
+                  to: bbb
+            - cause:
+                Other: unrelated
+              modification:
+                Repeal:
+                  position:
+                    act:
+                      year: 2012
+                      number: 1
+                    article: '5'
+            "#,
+        );
+        let expected = vec![
+            modifications[1].clone(),
+            modifications[0].clone(),
+            modifications[2].clone(),
+        ];
+        fix_amendment_order(&mut modifications).unwrap();
+        assert_eq!(causes(&modifications), causes(&expected));
+    }
+
+    #[test]
+    fn test_block_amendment_before_text_amendment() {
+        let mut modifications = modifications_from_yaml(
+            r#"
+            - cause:
+                Other: text
+              modification:
+                TextAmendment:
+                  reference:
+                    SAE:
+                      reference:
+                        act:
+                          year: 2012
+                          number: 1
+                        article: '3'
+                        paragraph: '2'
+                  from: aaa
+                  to: bbb
+            - cause:
+                Other: block
+              modification:
+                BlockAmendment:
+                  position:
+                    act:
+                      year: 2012
+                      number: 1
+                    article: '3'
+                    paragraph: '2'
+                  content:
+                    Paragraph:
+                    - identifier: '2'
+                      body: aaa ccc
+            - cause:
+                Other: structural
+              modification:
+                StructuralBlockAmendment:
+                  position:
+                    act:
+                      year: 2012
+                      number: 1
+                    structural_element:
+                      Article: '3'
+                  pure_insertion: false
+                  content:
+                  - Article:
+                      identifier: '3'
+                      children:
+                      - identifier: '2'
+                        body: aaa
+            "#,
+        );
+        let expected = vec![
+            modifications[2].clone(),
+            modifications[1].clone(),
+            modifications[0].clone(),
+        ];
+        fix_amendment_order(&mut modifications).unwrap();
+        assert_eq!(causes(&modifications), causes(&expected));
+    }
+
+    #[test]
+    fn test_stable_without_dependencies() {
+        let mut modifications = modifications_from_yaml(
+            r#"
+            - cause:
+                Other: first
+              modification:
+                Repeal:
+                  position:
+                    act:
+                      year: 2012
+                      number: 1
+                    article: '3'
+            - cause:
+                Other: second
+              modification:
+                Repeal:
+                  position:
+                    act:
+                      year: 2012
+                      number: 1
+                    article: '1'
+            - cause:
+                Other: third
+              modification:
+                Repeal:
+                  position:
+                    act:
+                      year: 2012
+                      number: 1
+                    article: '2'
+            "#,
+        );
+        let expected = modifications.clone();
+        fix_amendment_order(&mut modifications).unwrap();
+        assert_eq!(modifications, expected);
+    }
+
+    #[test]
+    fn test_same_from_is_not_a_cycle() {
+        let mut modifications = modifications_from_yaml(
+            r#"
+            - cause:
+                Other: first
+              modification:
+                TextAmendment:
+                  reference:
+                    SAE:
+                      reference:
+                        act:
+                          year: 2012
+                          number: 1
+                        article: '1'
+                  from: miniszter
+                  to: miniszter vagy a Kormány
+            - cause:
+                Other: second
+              modification:
+                TextAmendment:
+                  reference:
+                    SAE:
+                      reference:
+                        act:
+                          year: 2012
+                          number: 1
+                        article: '1'
+                  from: miniszter
+                  to: illetékes miniszter
+            "#,
+        );
+        let expected = modifications.clone();
+        fix_amendment_order(&mut modifications).unwrap();
+        assert_eq!(modifications, expected);
+    }
+
+    #[test]
+    fn test_cycle_detection() {
+        let mut modifications = modifications_from_yaml(
+            r#"
+            - cause:
+                Other: unrelated
+              modification:
+                Repeal:
+                  position:
+                    act:
+                      year: 2012
+                      number: 1
+                    article: '5'
+            - cause:
+                Other: x to y
+              modification:
+                TextAmendment:
+                  reference:
+                    SAE:
+                      reference:
+                        act:
+                          year: 2012
+                          number: 1
+                        article: '1'
+                  from: x
+                  to: y
+            - cause:
+                Other: y to x
+              modification:
+                TextAmendment:
+                  reference:
+                    SAE:
+                      reference:
+                        act:
+                          year: 2012
+                          number: 1
+                        article: '1'
+                  from: y
+                  to: x
+            "#,
+        );
+        let expected = modifications.clone();
+        let err = fix_amendment_order(&mut modifications).unwrap_err();
+        assert_eq!(modifications, expected);
+        let message = format!("{err:?}");
+        assert!(message.contains("Cyclic dependency between 2 modifications"));
+        assert!(message.contains("x to y"));
+        assert!(message.contains("y to x"));
+    }
+}
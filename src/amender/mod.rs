@@ -17,11 +17,16 @@
 
 pub mod auto_repeal;
 pub mod block_amendment;
+pub mod change_history;
 pub mod extract;
 pub mod fix_order;
+pub mod pending_amendment;
 pub mod repeal;
 pub mod structural_amendment;
 pub mod text_amendment;
+pub mod text_override;
+
+use std::collections::BTreeMap;
 
 use anyhow::{Context, Result};
 use chrono::NaiveDate;
@@ -38,10 +43,23 @@ use multimap::MultiMap;
 use serde::{Deserialize, Serialize};
 
 use self::{
-    block_amendment::BlockAmendmentWithContent, extract::extract_modifications_from_act,
-    repeal::SimplifiedRepeal, structural_amendment::StructuralBlockAmendmentWithContent,
+    block_amendment::BlockAmendmentWithContent,
+    change_history::{ChangeHistory, ChangeRecorder},
+    extract::extract_modifications_from_act,
+    pending_amendment::PendingAmendment,
+    repeal::SimplifiedRepeal,
+    structural_amendment::StructuralBlockAmendmentWithContent,
+    text_amendment::text_replace::MatchLevel,
+    text_override::TextOverride,
+};
+use crate::{
+    amender::fix_order::fix_amendment_order,
+    annulment::{AnnulmentSet, DecisionIdentifier},
+    database::{ActSet, RetroactiveActs},
+    enforcement_date_set::EnforcementDateSet,
+    fixups::{FixupProvenance, GlobalFixups},
+    persistence::Persistence,
 };
-use crate::{amender::fix_order::fix_amendment_order, database::ActSet, fixups::GlobalFixups};
 
 #[derive(Debug, Default)]
 pub struct AppliableModificationSet {
@@ -63,31 +81,112 @@ impl AppliableModificationSet {
             debug!("Act not in database for amending: {}", act_id);
             return Ok(());
         }
-        if let Some(modifications) = self.modifications.get_vec(&act_id).cloned() {
-            let mut act = state.get_act(act_id)?.act()?;
+        if let Some(modifications) = self.modifications.get_vec(&act_id) {
+            let act_entry = state.get_act(act_id)?;
+            let mut act = act_entry.act()?;
+            let mut change_history = act_entry.change_history()?;
+            // Needed to detect changes of provisions that have not come into force yet
+            let enforcement_dates = if act.children.is_empty() || act_entry.repeal().is_some() {
+                None
+            } else {
+                Some(EnforcementDateSet::from_act(&act)?)
+            };
             let modifications_len = modifications.len();
-            Self::apply_to_act(&mut act, date, modifications, on_error)?;
-            state.store_act(act)?;
+            Self::apply_to_act_with_history(
+                &mut act,
+                date,
+                modifications.clone(),
+                on_error,
+                &mut change_history,
+                enforcement_dates.as_ref(),
+            )?;
+            state.store_act_with_history(act, &change_history)?;
             info!("Applied {:?} amendments to {}", modifications_len, act_id);
         }
         Ok(())
     }
 
     pub fn apply_to_act(
+        act: &mut Act,
+        date: NaiveDate,
+        modifications: Vec<AppliableModification>,
+        on_error: OnError,
+    ) -> Result<()> {
+        Self::apply_to_act_impl(act, date, modifications, on_error, None, None)
+    }
+
+    /// Same as apply_to_act, but also records the changed elements of every
+    /// single modification in `change_history`.
+    ///
+    /// `enforcement_dates` should be calculated from the act before applying
+    /// the modifications (see `ChangeHistory::record`).
+    pub fn apply_to_act_with_history(
+        act: &mut Act,
+        date: NaiveDate,
+        modifications: Vec<AppliableModification>,
+        on_error: OnError,
+        change_history: &mut ChangeHistory,
+        enforcement_dates: Option<&EnforcementDateSet>,
+    ) -> Result<()> {
+        Self::apply_to_act_impl(
+            act,
+            date,
+            modifications,
+            on_error,
+            None,
+            Some((change_history, enforcement_dates)),
+        )
+    }
+
+    /// Same as apply_to_act, but calls the observer after every single modification
+    /// with the state of the act before and after applying it.
+    /// Expensive, only meant for debugging.
+    pub fn apply_to_act_observed(
+        act: &mut Act,
+        date: NaiveDate,
+        modifications: Vec<AppliableModification>,
+        on_error: OnError,
+        observer: Option<&mut dyn ModificationObserver>,
+    ) -> Result<()> {
+        Self::apply_to_act_impl(act, date, modifications, on_error, observer, None)
+    }
+
+    fn apply_to_act_impl(
         act: &mut Act,
         date: NaiveDate,
         mut modifications: Vec<AppliableModification>,
         on_error: OnError,
+        mut observer: Option<&mut dyn ModificationObserver>,
+        mut history: Option<(&mut ChangeHistory, Option<&EnforcementDateSet>)>,
     ) -> Result<()> {
-        fix_amendment_order(&mut modifications);
+        if let Err(err) = fix_amendment_order(&mut modifications) {
+            match on_error {
+                OnError::Warn => warn!("{:?}\n\n", err),
+                OnError::ReturnErr => {
+                    return Err(err).with_elem_context("Error ordering modifications", act);
+                }
+            }
+        }
         let mut do_full_reparse = false;
         for modification in &modifications {
-            let result = modification.apply(act, date).with_context(|| {
-                format!(
-                    "Error applying single amendment to {} (cause: {:?})",
-                    act.identifier, modification.cause
-                )
-            });
+            let act_before = observer.as_ref().map(|_| act.clone());
+            let result = modification
+                .apply(act, date)
+                .map(|(needs_full_reparse, change_entry)| {
+                    if let Some((change_history, enforcement_dates)) = &mut history {
+                        change_history.record(modification, change_entry, *enforcement_dates);
+                    }
+                    needs_full_reparse
+                })
+                .with_context(|| {
+                    format!(
+                        "Error applying single amendment to {} (cause: {:?})",
+                        act.identifier, modification.cause
+                    )
+                });
+            if let (Some(observer), Some(act_before)) = (&mut observer, &act_before) {
+                observer.on_applied(modification, act_before, act, &result);
+            }
             match result {
                 Ok(NeedsFullReparse::No) => (),
                 Ok(NeedsFullReparse::Yes) => do_full_reparse = true,
@@ -128,7 +227,13 @@ impl AppliableModificationSet {
     pub fn add(&mut self, act: &Act, date: NaiveDate) -> Result<()> {
         let this_acts_modifications = extract_modifications_from_act(act, date)
             .with_elem_context("Error extracting modifications", act)?;
-        for modification in this_acts_modifications {
+        self.add_modifications(this_acts_modifications)
+    }
+
+    /// Add modifications that were already extracted, e.g. from an act that is
+    /// not in the database.
+    pub fn add_modifications(&mut self, modifications: Vec<AppliableModification>) -> Result<()> {
+        for modification in modifications {
             self.modifications
                 .insert(modification.affected_act()?, modification);
         }
@@ -143,6 +248,14 @@ impl AppliableModificationSet {
         self.modifications.keys().copied().collect()
     }
 
+    /// The modifications affecting a single act, not yet in application order
+    pub fn get_modifications_of(&self, act_id: ActIdentifier) -> Vec<AppliableModification> {
+        self.modifications
+            .get_vec(&act_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
     pub fn add_fixups(&mut self, date: NaiveDate) -> Result<()> {
         let fixups = GlobalFixups::load(date)?.get_additional_modifications();
         if !fixups.is_empty() {
@@ -156,15 +269,77 @@ impl AppliableModificationSet {
         }
         Ok(())
     }
-    /// Used only for testing
+
+    /// Add the modifications of acts that were published after `date`, but
+    /// have something coming into force on it (or the day before, for the
+    /// automatic repeals). These are not in the state of the date.
+    ///
+    /// Only the acts selected by `filter` (called with the act and its
+    /// publication date) are used.
+    pub fn add_retroactive(
+        &mut self,
+        persistence: &Persistence,
+        date: NaiveDate,
+        filter: impl Fn(ActIdentifier, NaiveDate) -> bool,
+    ) -> Result<()> {
+        let mut acts = BTreeMap::new();
+        for retroactive_date in [date.pred(), date] {
+            for (act_id, publication_date) in
+                RetroactiveActs::load(persistence, retroactive_date)?.acts()?
+            {
+                if filter(act_id, publication_date) {
+                    acts.insert(act_id, publication_date);
+                }
+            }
+        }
+        for (act_id, publication_date) in acts {
+            info!(
+                "Applying retroactive modifications of {act_id} (published on {publication_date})"
+            );
+            let act = ActSet::load(persistence, publication_date)?
+                .get_act(act_id)?
+                .act()?;
+            self.add(&act, date)?;
+        }
+        Ok(())
+    }
+
+    /// Add the Constitutional Court annulments that should be applied on `date`
+    pub fn add_annulments(&mut self, annulments: &AnnulmentSet, date: NaiveDate) -> Result<()> {
+        let modifications = annulments.get_modifications(date);
+        if !modifications.is_empty() {
+            info!("Applying {} annulments", modifications.len());
+        }
+        for modification in modifications {
+            self.modifications
+                .insert(modification.affected_act()?, modification)
+        }
+        Ok(())
+    }
+
+    /// All modifications, per affected act, in the order they would be applied.
+    /// Used for inspecting the modifications without applying them, e.g. in
+    /// tests, `ajdb explain` and simulations.
     pub fn get_modifications(mut self) -> MultiMap<ActIdentifier, AppliableModification> {
         for (_key, vals) in self.modifications.iter_all_mut() {
-            fix_amendment_order(vals);
+            if let Err(err) = fix_amendment_order(vals) {
+                warn!("{:?}", err);
+            }
         }
         self.modifications
     }
 }
 
+pub trait ModificationObserver {
+    fn on_applied(
+        &mut self,
+        modification: &AppliableModification,
+        act_before: &Act,
+        act_after: &Act,
+        result: &Result<NeedsFullReparse>,
+    );
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OnError {
     Warn,
@@ -187,10 +362,12 @@ impl From<AbbreviationsChanged> for NeedsFullReparse {
 }
 
 pub trait ModifyAct {
-    fn apply(&self, act: &mut Act, change_entry: &LastChange) -> Result<NeedsFullReparse>;
+    /// Apply the modification, setting the `last_change` of the changed
+    /// elements through `change_entry`.
+    fn apply(&self, act: &mut Act, change_entry: &ChangeRecorder) -> Result<NeedsFullReparse>;
 }
 
-trait AffectedAct {
+pub(crate) trait AffectedAct {
     fn affected_act(&self) -> Result<ActIdentifier>;
 }
 
@@ -198,6 +375,20 @@ trait AffectedAct {
 pub struct AppliableModification {
     pub cause: ChangeCause,
     pub modification: AppliableModificationType,
+    /// Set if the modification was added or changed by a fixup
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fixup: Option<FixupProvenance>,
+    /// Publication date of the source act, if the modification comes into
+    /// force before it (i.e. it has retroactive effect).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retroactive_since: Option<NaiveDate>,
+    /// Set if the modification is an annulment by the Constitutional Court.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annulment: Option<DecisionIdentifier>,
+    /// The most tolerant matching allowed for the replaced text of text
+    /// amendments. Only set by the `AllowTolerantMatch` fixup.
+    #[serde(default, skip_serializing_if = "MatchLevel::is_exact")]
+    pub max_match_level: MatchLevel,
 }
 
 #[derive(Debug, Clone, FromVariants, PartialEq, Eq, Serialize, Deserialize)]
@@ -206,17 +397,33 @@ pub enum AppliableModificationType {
     Repeal(SimplifiedRepeal),
     TextAmendment(TextAmendment),
     StructuralBlockAmendment(StructuralBlockAmendmentWithContent),
+    PendingAmendment(PendingAmendment),
+    TextOverride(TextOverride),
 }
 
 impl AppliableModification {
-    fn apply(&self, act: &mut Act, date: NaiveDate) -> Result<NeedsFullReparse> {
-        self.modification.apply(
-            act,
-            &LastChange {
-                date,
-                cause: self.cause.clone(),
-            },
-        )
+    /// A modification without any of the markers above, as extracted from
+    /// the amending act.
+    pub fn new(cause: ChangeCause, modification: impl Into<AppliableModificationType>) -> Self {
+        Self {
+            cause,
+            modification: modification.into(),
+            fixup: None,
+            retroactive_since: None,
+            annulment: None,
+            max_match_level: MatchLevel::Exact,
+        }
+    }
+
+    /// Returns the recorded changes along with the result
+    fn apply(&self, act: &mut Act, date: NaiveDate) -> Result<(NeedsFullReparse, ChangeRecorder)> {
+        let change_entry = ChangeRecorder::new(LastChange {
+            date,
+            cause: self.cause.clone(),
+        })
+        .with_max_match_level(self.max_match_level);
+        let result = self.modification.apply(act, &change_entry)?;
+        Ok((result, change_entry))
     }
 }
 
@@ -227,12 +434,14 @@ impl AffectedAct for AppliableModification {
 }
 
 impl ModifyAct for AppliableModificationType {
-    fn apply(&self, act: &mut Act, change_entry: &LastChange) -> Result<NeedsFullReparse> {
+    fn apply(&self, act: &mut Act, change_entry: &ChangeRecorder) -> Result<NeedsFullReparse> {
         match self {
             AppliableModificationType::BlockAmendment(m) => m.apply(act, change_entry),
             AppliableModificationType::Repeal(m) => m.apply(act, change_entry),
             AppliableModificationType::TextAmendment(m) => m.apply(act, change_entry),
             AppliableModificationType::StructuralBlockAmendment(m) => m.apply(act, change_entry),
+            AppliableModificationType::PendingAmendment(m) => m.apply(act, change_entry),
+            AppliableModificationType::TextOverride(m) => m.apply(act, change_entry),
         }
     }
 }
@@ -244,6 +453,8 @@ impl AffectedAct for AppliableModificationType {
             AppliableModificationType::Repeal(m) => m.affected_act(),
             AppliableModificationType::TextAmendment(m) => m.affected_act(),
             AppliableModificationType::StructuralBlockAmendment(m) => m.affected_act(),
+            AppliableModificationType::PendingAmendment(m) => m.affected_act(),
+            AppliableModificationType::TextOverride(m) => m.affected_act(),
         }
     }
 }
@@ -0,0 +1,234 @@
+// Copyright (c) 2022-2023, Alex Badics
+//
+// This file is part of AJDB
+//
+// AJDB is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// AJDB is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
+
+use anyhow::{anyhow, bail, ensure, Result};
+use hun_law::{
+    identifier::ActIdentifier,
+    reference::{to_element::ReferenceToElement, Reference},
+    semantic_info::SpecialPhrase,
+    structure::{
+        Act, ActChild, Article, BlockAmendmentChildren, Paragraph, ParagraphChildren, SAEBody,
+    },
+    util::debug::WithElemContext,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    change_history::ChangeRecorder, AffectedAct, AppliableModificationType, ModifyAct,
+    NeedsFullReparse,
+};
+
+/// Modification of the content of a block amendment that is not in force yet.
+///
+/// `position` is the amending provision (in the amending act) that contains
+/// the block amendment. `modification` is applied to the quoted content itself,
+/// with all references in it relative to the act the block amendment amends,
+/// e.g. "X. tv. 3. § (2) bekezdésével megállapított Y. tv. 5. § (1) bekezdés b) pont".
+///
+/// Once the amending provision comes into force, the modified content is
+/// extracted as usual.
+///
+/// These are extracted from modifications with a "... megállapított" phrase
+/// referring to a provision of another act before their position (see
+/// `extract::establishing_provision`).
+///
+/// Paragraph and point level block amendments, and structural block
+/// amendments are supported.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingAmendment {
+    pub position: Reference,
+    pub modification: Box<AppliableModificationType>,
+}
+
+impl ModifyAct for PendingAmendment {
+    fn apply(&self, act: &mut Act, change_entry: &ChangeRecorder) -> Result<NeedsFullReparse> {
+        let base_ref = act.reference();
+        let paragraph = find_paragraph(act, &base_ref, &self.position)?;
+        self.apply_to_paragraph(paragraph, change_entry)
+            .with_elem_context("Could not apply amendment of pending content", paragraph)?;
+        paragraph.last_change = change_entry.mark(self.position.clone());
+        Ok(NeedsFullReparse::No)
+    }
+}
+
+impl PendingAmendment {
+    fn apply_to_paragraph(
+        &self,
+        paragraph: &mut Paragraph,
+        change_entry: &ChangeRecorder,
+    ) -> Result<()> {
+        match (&mut paragraph.body, &paragraph.semantic_info.special_phrase) {
+            (
+                SAEBody::Children {
+                    children: ParagraphChildren::BlockAmendment(ba_content),
+                    ..
+                },
+                Some(SpecialPhrase::BlockAmendment(ba_se)),
+            ) => {
+                let article_id = ba_se
+                    .position
+                    .article()
+                    .ok_or_else(|| anyhow!("No article in block amendment position"))?
+                    .first_in_range();
+                // Points are wrapped into their paragraph, so that the references
+                // of the modification can be resolved in the temporary act.
+                let paragraphs = match &ba_content.children {
+                    BlockAmendmentChildren::Paragraph(paragraphs) => paragraphs.clone(),
+                    BlockAmendmentChildren::AlphabeticPoint(points) => vec![wrap_points(
+                        &ba_se.position,
+                        ParagraphChildren::AlphabeticPoint(points.clone()),
+                    )?],
+                    BlockAmendmentChildren::NumericPoint(points) => vec![wrap_points(
+                        &ba_se.position,
+                        ParagraphChildren::NumericPoint(points.clone()),
+                    )?],
+                    _ => bail!("Subpoint level pending block amendments cannot be amended"),
+                };
+                let content = vec![ActChild::Article(Article {
+                    identifier: article_id,
+                    title: None,
+                    children: paragraphs,
+                    last_change: None,
+                })];
+                let mut content =
+                    self.apply_to_content(ba_se.position.act(), content, change_entry)?;
+                let mut paragraphs = if let Some(ActChild::Article(article)) = content.pop() {
+                    article.children
+                } else {
+                    bail!("Article disappeared from the pending block amendment");
+                };
+                ba_content.children = match &ba_content.children {
+                    BlockAmendmentChildren::Paragraph(_) => {
+                        BlockAmendmentChildren::Paragraph(paragraphs)
+                    }
+                    _ => match paragraphs.pop().map(|p| p.body) {
+                        Some(SAEBody::Children {
+                            children: ParagraphChildren::AlphabeticPoint(points),
+                            ..
+                        }) => BlockAmendmentChildren::AlphabeticPoint(points),
+                        Some(SAEBody::Children {
+                            children: ParagraphChildren::NumericPoint(points),
+                            ..
+                        }) => BlockAmendmentChildren::NumericPoint(points),
+                        _ => bail!("Points disappeared from the pending block amendment"),
+                    },
+                };
+            }
+            (
+                SAEBody::Children {
+                    children: ParagraphChildren::StructuralBlockAmendment(sba_content),
+                    ..
+                },
+                Some(SpecialPhrase::StructuralBlockAmendment(sba_se)),
+            ) => {
+                sba_content.children = self.apply_to_content(
+                    sba_se.position.act,
+                    sba_content.children.clone(),
+                    change_entry,
+                )?;
+            }
+            _ => bail!("Amended provision does not contain a block amendment"),
+        }
+        Ok(())
+    }
+
+    /// Apply the modification to the content, by wrapping it into a temporary act.
+    fn apply_to_content(
+        &self,
+        amended_act_id: Option<ActIdentifier>,
+        content: Vec<ActChild>,
+        change_entry: &ChangeRecorder,
+    ) -> Result<Vec<ActChild>> {
+        let amended_act_id = amended_act_id
+            .ok_or_else(|| anyhow!("No act in the position of the pending block amendment"))?;
+        ensure!(
+            self.modification.affected_act()? == amended_act_id,
+            "Modification does not affect the act amended by the pending block amendment ({})",
+            amended_act_id
+        );
+        let mut content_act = Act {
+            identifier: amended_act_id,
+            subject: String::new(),
+            preamble: String::new(),
+            publication_date: change_entry.last_change().date,
+            contained_abbreviations: Default::default(),
+            children: content,
+        };
+        // The changed elements of the content are not elements of the amending
+        // act, so they are not recorded, only the amending paragraph itself.
+        let content_change_entry = ChangeRecorder::new(change_entry.last_change().clone());
+        if self
+            .modification
+            .apply(&mut content_act, &content_change_entry)?
+            == NeedsFullReparse::Yes
+        {
+            content_act.add_semantic_info()?;
+        }
+        Ok(content_act.children)
+    }
+}
+
+/// A paragraph containing only the points of a point level block amendment
+/// at `position`.
+fn wrap_points(position: &Reference, points: ParagraphChildren) -> Result<Paragraph> {
+    let paragraph_id = position
+        .paragraph()
+        .ok_or_else(|| anyhow!("No paragraph in block amendment position"))?
+        .first_in_range();
+    Ok(Paragraph {
+        identifier: paragraph_id.into(),
+        body: SAEBody::Children {
+            intro: String::new(),
+            children: points,
+            wrap_up: None,
+        },
+        semantic_info: Default::default(),
+        last_change: None,
+    })
+}
+
+fn find_paragraph<'a>(
+    act: &'a mut Act,
+    base_ref: &Reference,
+    position: &Reference,
+) -> Result<&'a mut Paragraph> {
+    for article in act.articles_mut() {
+        let article_ref = article.reference().relative_to(base_ref)?;
+        if !article_ref.contains(position) {
+            continue;
+        }
+        // Single paragraph articles are referred to by the article only ("3. §-ával")
+        let whole_article = article_ref == *position && article.children.len() == 1;
+        for paragraph in &mut article.children {
+            if whole_article || paragraph.reference().relative_to(&article_ref)? == *position {
+                return Ok(paragraph);
+            }
+        }
+    }
+    Err(anyhow!(
+        "Could not find the amending provision {:?}",
+        position
+    ))
+}
+
+impl AffectedAct for PendingAmendment {
+    fn affected_act(&self) -> Result<ActIdentifier> {
+        self.position
+            .act()
+            .ok_or_else(|| anyhow!("No act in reference in PendingAmendment"))
+    }
+}
@@ -19,12 +19,12 @@ use anyhow::{anyhow, ensure, Result};
 use hun_law::{
     identifier::{ActIdentifier, IdentifierCommon},
     reference::Reference,
-    structure::{Act, ChildrenCommon, LastChange, SAEBody, SubArticleElement},
+    structure::{Act, ActChild, Article, ChildrenCommon, LastChange, SAEBody, SubArticleElement},
     util::walker::SAEVisitorMut,
 };
 use serde::{Deserialize, Serialize};
 
-use super::{AffectedAct, ModifyAct, NeedsFullReparse};
+use super::{change_history::ChangeRecorder, AffectedAct, ModifyAct, NeedsFullReparse};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct SimplifiedRepeal {
@@ -32,12 +32,9 @@ pub struct SimplifiedRepeal {
 }
 
 impl ModifyAct for SimplifiedRepeal {
-    fn apply(&self, act: &mut Act, change_entry: &LastChange) -> Result<NeedsFullReparse> {
-        // TODO: A full act repeal will individually repeal all articles.
-        //       But structural elements stay in place
-        //       This may not be ideal.
+    fn apply(&self, act: &mut Act, change_entry: &ChangeRecorder) -> Result<NeedsFullReparse> {
         if self.position.is_act_only() {
-            act.children = Vec::new();
+            Self::repeal_whole_act(act, change_entry);
         } else {
             // TODO: Sanity check if it was actually applied
             let mut applier = RepealApplier {
@@ -55,23 +52,74 @@ impl ModifyAct for SimplifiedRepeal {
 }
 
 impl SimplifiedRepeal {
-    fn collate_repealed_paragraphs(act: &mut Act, change_entry: &LastChange) -> Result<()> {
+    /// Repeal the whole act, leaving it in the "repealed act" state (see `get_act_repeal`)
+    ///
+    /// The policy is the same as with structural repeals (e.g. chapter repeals):
+    /// structural elements and subtitles are removed, articles are kept as empty
+    /// stubs, so that references to them still work.
+    fn repeal_whole_act(act: &mut Act, change_entry: &ChangeRecorder) {
+        let act_id = act.identifier;
+        let children = std::mem::take(&mut act.children);
+        act.children = children
+            .into_iter()
+            .filter_map(|c| {
+                if let ActChild::Article(a) = c {
+                    Some(ActChild::Article(Article {
+                        identifier: a.identifier,
+                        title: None,
+                        children: Vec::new(),
+                        last_change: change_entry.mark((act_id, a.identifier).into()),
+                    }))
+                } else {
+                    None
+                }
+            })
+            .collect();
+    }
+
+    fn collate_repealed_paragraphs(act: &mut Act, change_entry: &ChangeRecorder) -> Result<()> {
         act.walk_saes_mut(&mut RepealCollater { change_entry })?;
+        let act_id = act.identifier;
         for article in act.articles_mut() {
             if !article.children.is_empty() && article.children.iter().all(|p| p.is_empty()) {
                 article.title = None;
                 article.children = Vec::new();
-                article.last_change = Some(change_entry.clone());
+                article.last_change = change_entry.mark((act_id, article.identifier).into());
             }
         }
         Ok(())
     }
 }
 
+/// Returns the change entry of the repeal, if the act was repealed as a whole.
+///
+/// An act is considered fully repealed if it only consists of empty article stubs,
+/// all repealed by the same change. This is what a full act repeal produces, but
+/// it is also the result of repealing every single article at the same time, which
+/// is effectively the same thing.
+pub fn get_act_repeal(act: &Act) -> Option<&LastChange> {
+    let mut result = None;
+    for child in &act.children {
+        let article = if let ActChild::Article(article) = child {
+            article
+        } else {
+            return None;
+        };
+        if !article.children.is_empty() {
+            return None;
+        }
+        let last_change = article.last_change.as_ref()?;
+        if *result.get_or_insert(last_change) != last_change {
+            return None;
+        }
+    }
+    result
+}
+
 struct RepealApplier<'a> {
     position: Reference,
     applied: bool,
-    change_entry: &'a LastChange,
+    change_entry: &'a ChangeRecorder,
 }
 
 impl<'a> SAEVisitorMut for RepealApplier<'a> {
@@ -84,7 +132,7 @@ impl<'a> SAEVisitorMut for RepealApplier<'a> {
             // TODO: Proper repealing. Maybe a separate SAEBody type
             element.body = SAEBody::Text("".to_owned());
             element.semantic_info = Default::default();
-            element.last_change = Some(self.change_entry.clone());
+            element.last_change = self.change_entry.mark(position.clone());
             self.applied = true;
         }
         Ok(())
@@ -100,13 +148,13 @@ impl AffectedAct for SimplifiedRepeal {
 }
 
 struct RepealCollater<'a> {
-    change_entry: &'a LastChange,
+    change_entry: &'a ChangeRecorder,
 }
 
 impl<'a> SAEVisitorMut for RepealCollater<'a> {
     fn on_exit<IT: IdentifierCommon, CT: ChildrenCommon>(
         &mut self,
-        _position: &Reference,
+        position: &Reference,
         element: &mut SubArticleElement<IT, CT>,
     ) -> Result<()> {
         if let SAEBody::Children { .. } = element.body {
@@ -114,7 +162,7 @@ impl<'a> SAEVisitorMut for RepealCollater<'a> {
                 element.body = SAEBody::Text("".to_owned());
                 element.semantic_info = Default::default();
                 // NOTE: we lose change information of the children here.
-                element.last_change = Some(self.change_entry.clone());
+                element.last_change = self.change_entry.mark(position.clone());
             }
         }
         Ok(())
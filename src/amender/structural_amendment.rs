@@ -19,12 +19,12 @@ use anyhow::{anyhow, Result};
 use hun_law::{
     identifier::ActIdentifier,
     reference::structural::{StructuralReference, StructuralReferenceElement},
-    structure::{Act, ActChild, Article, LastChange},
+    structure::{Act, ActChild, Article},
 };
 use serde::{Deserialize, Serialize};
 
-use super::{AffectedAct, ModifyAct, NeedsFullReparse};
-use crate::structural_cut_points::GetCutPoints;
+use super::{change_history::ChangeRecorder, AffectedAct, ModifyAct, NeedsFullReparse};
+use crate::structural_cut_points::{heading_section_end, GetCutPoints};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct StructuralBlockAmendmentWithContent {
@@ -34,31 +34,52 @@ pub struct StructuralBlockAmendmentWithContent {
 }
 
 impl ModifyAct for StructuralBlockAmendmentWithContent {
-    fn apply(&self, act: &mut Act, change_entry: &LastChange) -> Result<NeedsFullReparse> {
+    fn apply(&self, act: &mut Act, change_entry: &ChangeRecorder) -> Result<NeedsFullReparse> {
+        let act_id = act.identifier;
         let cut = self.position.get_cut_points(act, self.pure_insertion)?;
+        // When repealing an article range, headings inside the range that also
+        // introduce articles after it are kept.
+        let kept_headings: Vec<bool> = if let StructuralReferenceElement::Article(_) =
+            self.position.structural_element
+        {
+            cut.clone()
+                .map(|i| heading_section_end(&act.children, i).map_or(false, |end| end > cut.end))
+                .collect()
+        } else {
+            vec![false; cut.len()]
+        };
         let mut tail = act.children.split_off(cut.end);
         if self.content.is_empty() {
             let cut_out = act.children.split_off(cut.start);
-            act.children.extend(cut_out.into_iter().filter_map(|c| {
-                if let ActChild::Article(a) = c {
-                    Some(ActChild::Article(Article {
-                        identifier: a.identifier,
-                        title: None,
-                        children: Vec::new(),
-                        last_change: Some(change_entry.clone()),
-                    }))
-                } else {
-                    None
-                }
-            }));
+            act.children.extend(
+                cut_out
+                    .into_iter()
+                    .zip(kept_headings)
+                    .filter_map(|(c, kept)| match c {
+                        ActChild::Article(a) => Some(ActChild::Article(Article {
+                            identifier: a.identifier,
+                            title: None,
+                            children: Vec::new(),
+                            last_change: change_entry.mark((act_id, a.identifier).into()),
+                        })),
+                        _ if kept => Some(c),
+                        _ => None,
+                    }),
+            );
         } else {
             act.children.truncate(cut.start);
             let content = self.content.iter().map(|c| {
                 let mut result = c.clone();
                 match &mut result {
-                    ActChild::StructuralElement(x) => x.last_change = Some(change_entry.clone()),
-                    ActChild::Subtitle(x) => x.last_change = Some(change_entry.clone()),
-                    ActChild::Article(x) => x.last_change = Some(change_entry.clone()),
+                    ActChild::StructuralElement(x) => {
+                        x.last_change = Some(change_entry.last_change().clone())
+                    }
+                    ActChild::Subtitle(x) => {
+                        x.last_change = Some(change_entry.last_change().clone())
+                    }
+                    ActChild::Article(x) => {
+                        x.last_change = change_entry.mark((act_id, x.identifier).into())
+                    }
                 }
                 result
             });
@@ -18,18 +18,20 @@
 use anyhow::{ensure, Result};
 use hun_law::{
     reference::{to_element::ReferenceToElement, Reference},
-    structure::{Act, LastChange},
+    structure::Act,
 };
 
 use super::NeedsFullReparse;
-use crate::amender::text_amendment::text_replace::normalized_replace;
+use crate::amender::{
+    change_history::ChangeRecorder, text_amendment::text_replace::normalized_replace,
+};
 
 pub fn apply_article_title_amendment(
     reference: &Reference,
     from: &str,
     to: &str,
     act: &mut Act,
-    change_entry: &LastChange,
+    change_entry: &ChangeRecorder,
 ) -> Result<NeedsFullReparse> {
     let mut applied = false;
     let act_ref = act.reference();
@@ -40,7 +42,7 @@ pub fn apply_article_title_amendment(
                 if let Some(replaced) = normalized_replace(title, from, to) {
                     applied = true;
                     *title = replaced;
-                    article.last_change = Some(change_entry.clone());
+                    article.last_change = change_entry.mark(article_ref.clone());
                 }
             }
         }
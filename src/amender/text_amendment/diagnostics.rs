@@ -0,0 +1,174 @@
+// Copyright (c) 2022-2023, Alex Badics
+//
+// This file is part of AJDB
+//
+// AJDB is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// AJDB is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::fmt::{Display, Formatter};
+
+use anyhow::Result;
+use hun_law::{
+    identifier::IdentifierCommon,
+    reference::{to_element::ReferenceToElement, Reference},
+    semantic_info::TextAmendmentSAEPart,
+    structure::{Act, ChildrenCommon, SAEBody, SubArticleElement},
+    util::walker::{SAEVisitor, WalkSAE},
+};
+
+use super::text_replace::{closest_matches, contains_whole_word};
+
+const MAX_NEAR_MISSES: usize = 5;
+
+/// Details of a text amendment that did not have an effect.
+///
+/// Returned as the error of failed SAE text amendments, so callers with more
+/// context (e.g. access to earlier versions of the act) can downcast and
+/// extend the diagnostic.
+#[derive(Debug, Clone)]
+pub struct TextAmendmentDiagnostic {
+    pub reference: Reference,
+    pub amended_part: TextAmendmentSAEPart,
+    pub from: String,
+    pub to: String,
+    /// Substrings of the targeted elements closest to `from`, closest first
+    pub near_misses: Vec<NearMiss>,
+    /// Elements outside the target where `from` can be found as-is
+    pub found_elsewhere: Vec<Reference>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NearMiss {
+    pub position: Reference,
+    pub distance: usize,
+    pub text: String,
+}
+
+impl TextAmendmentDiagnostic {
+    pub fn new(
+        act: &Act,
+        reference: &Reference,
+        amended_part: &TextAmendmentSAEPart,
+        from: &str,
+        to: &str,
+    ) -> Result<Self> {
+        let mut near_misses = Vec::new();
+        let mut found_elsewhere = Vec::new();
+        for (position, texts) in collect_sae_texts(act)? {
+            if reference.contains(&position) {
+                for text in texts {
+                    for (distance, text) in closest_matches(&text, from, MAX_NEAR_MISSES) {
+                        near_misses.push(NearMiss {
+                            position: position.clone(),
+                            distance,
+                            text,
+                        });
+                    }
+                }
+            } else if texts.iter().any(|text| contains_whole_word(text, from)) {
+                found_elsewhere.push(position);
+            }
+        }
+        near_misses.sort_by_key(|nm| nm.distance);
+        near_misses.truncate(MAX_NEAR_MISSES);
+        Ok(Self {
+            reference: reference.clone(),
+            amended_part: amended_part.clone(),
+            from: from.to_string(),
+            to: to.to_string(),
+            near_misses,
+            found_elsewhere,
+        })
+    }
+
+    /// Returns true if the text to be replaced can be found in the targeted
+    /// elements of this (probably earlier) version of the act.
+    pub fn applies_to(&self, act: &Act) -> Result<bool> {
+        Ok(collect_sae_texts(act)?
+            .into_iter()
+            .filter(|(position, _)| self.reference.contains(position))
+            .any(|(_, texts)| {
+                texts
+                    .iter()
+                    .any(|text| contains_whole_word(text, &self.from))
+            }))
+    }
+}
+
+impl Display for TextAmendmentDiagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Text replacement @{:?} {:?} from={:?} to={:?} did not have an effect",
+            self.reference, self.amended_part, self.from, self.to
+        )?;
+        if self.near_misses.is_empty() {
+            write!(f, "\n  No similar text in the targeted elements")?;
+        } else {
+            write!(f, "\n  Closest matches in the targeted elements:")?;
+            for near_miss in &self.near_misses {
+                write!(
+                    f,
+                    "\n    {}: {:?} (distance: {})",
+                    near_miss.position, near_miss.text, near_miss.distance
+                )?;
+            }
+        }
+        if !self.found_elsewhere.is_empty() {
+            write!(f, "\n  The text to be replaced was found in:")?;
+            for position in &self.found_elsewhere {
+                write!(f, "\n    {position}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TextAmendmentDiagnostic {}
+
+/// The texts (body, or intro and wrap-up) of every SAE in the act
+fn collect_sae_texts(act: &Act) -> Result<Vec<(Reference, Vec<String>)>> {
+    let mut collector = TextCollector::default();
+    let act_ref = act.reference();
+    for article in act.articles() {
+        let article_ref = article.reference().relative_to(&act_ref)?;
+        for paragraph in &article.children {
+            paragraph.walk_saes(&article_ref, &mut collector)?;
+        }
+    }
+    Ok(collector.result)
+}
+
+#[derive(Debug, Default)]
+struct TextCollector {
+    result: Vec<(Reference, Vec<String>)>,
+}
+
+impl SAEVisitor for TextCollector {
+    fn on_enter<IT: IdentifierCommon, CT: ChildrenCommon>(
+        &mut self,
+        position: &Reference,
+        element: &SubArticleElement<IT, CT>,
+    ) -> Result<()> {
+        let texts = match &element.body {
+            SAEBody::Text(text) => vec![text.clone()],
+            SAEBody::Children { intro, wrap_up, .. } => {
+                let mut texts = vec![intro.clone()];
+                texts.extend(wrap_up.clone());
+                texts
+            }
+        };
+        self.result.push((position.clone(), texts));
+        Ok(())
+    }
+}
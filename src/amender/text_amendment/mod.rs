@@ -16,25 +16,26 @@
 // along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
 
 mod article_title;
+pub mod diagnostics;
 mod sae;
 mod structural;
-mod text_replace;
+pub mod text_replace;
 
 use anyhow::{anyhow, Result};
 use hun_law::{
     identifier::ActIdentifier,
     semantic_info::{TextAmendment, TextAmendmentReference},
-    structure::{Act, LastChange},
+    structure::Act,
 };
 
 use self::{
     article_title::apply_article_title_amendment, sae::apply_sae_text_amendment,
     structural::apply_structural_title_amendment,
 };
-use super::{AffectedAct, ModifyAct, NeedsFullReparse};
+use super::{change_history::ChangeRecorder, AffectedAct, ModifyAct, NeedsFullReparse};
 
 impl ModifyAct for TextAmendment {
-    fn apply(&self, act: &mut Act, change_entry: &LastChange) -> Result<NeedsFullReparse> {
+    fn apply(&self, act: &mut Act, change_entry: &ChangeRecorder) -> Result<NeedsFullReparse> {
         match &self.reference {
             TextAmendmentReference::SAE {
                 reference,
@@ -15,16 +15,22 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
 
-use anyhow::{anyhow, ensure, Result};
+use anyhow::{anyhow, Result};
 use hun_law::{
     identifier::IdentifierCommon,
     reference::Reference,
     semantic_info::TextAmendmentSAEPart,
-    structure::{Act, ChildrenCommon, LastChange, SAEBody, SubArticleElement},
+    structure::{Act, ChildrenCommon, SAEBody, SubArticleElement},
     util::walker::SAEVisitorMut,
 };
+use log::warn;
 
-use super::{text_replace::normalized_replace, NeedsFullReparse};
+use super::{
+    diagnostics::TextAmendmentDiagnostic,
+    text_replace::{tolerant_replace, MatchLevel},
+    NeedsFullReparse,
+};
+use crate::amender::change_history::ChangeRecorder;
 
 pub fn apply_sae_text_amendment(
     reference: &Reference,
@@ -32,21 +38,36 @@ pub fn apply_sae_text_amendment(
     from: &str,
     to: &str,
     act: &mut Act,
-    change_entry: &LastChange,
+    change_entry: &ChangeRecorder,
 ) -> Result<NeedsFullReparse> {
     let mut visitor = Visitor {
         reference,
         amended_part,
         from,
         to,
+        level: MatchLevel::Exact,
         applied: false,
         change_entry,
     };
-    act.walk_saes_mut(&mut visitor)?;
-    ensure!(
-        visitor.applied,
-        "Text replacement @{reference:?} {amended_part:?} from={from:?} to={to:?} did not have an effect",
-    );
+    // The whole amendment is applied with the same level, so that a tolerant
+    // match cannot replace something the amendment did not mean to.
+    for level in change_entry.max_match_level().ladder() {
+        visitor.level = level;
+        act.walk_saes_mut(&mut visitor)?;
+        if visitor.applied {
+            break;
+        }
+    }
+    if !visitor.applied {
+        return Err(TextAmendmentDiagnostic::new(act, reference, amended_part, from, to)?.into());
+    }
+    if visitor.level != MatchLevel::Exact {
+        change_entry.set_match_level(visitor.level);
+        warn!(
+            "Text replacement @{reference} from={from:?} to={to:?} only applied with {:?} matching (cause: {:?})",
+            visitor.level, change_entry.last_change().cause
+        );
+    }
     let article_ids = reference
         .article()
         .ok_or_else(|| anyhow!("No article in text amendment position"))?;
@@ -65,7 +86,8 @@ struct Visitor<'a> {
     amended_part: &'a TextAmendmentSAEPart,
     from: &'a str,
     to: &'a str,
-    change_entry: &'a LastChange,
+    level: MatchLevel,
+    change_entry: &'a ChangeRecorder,
     applied: bool,
 }
 
@@ -81,9 +103,9 @@ impl<'a> SAEVisitorMut for Visitor<'a> {
             match &mut element.body {
                 SAEBody::Text(text) => {
                     if self.amended_part == &TextAmendmentSAEPart::All {
-                        if let Some(replaced) = normalized_replace(text, from, to) {
+                        if let Some(replaced) = tolerant_replace(text, from, to, self.level) {
                             self.applied = true;
-                            element.last_change = Some(self.change_entry.clone());
+                            element.last_change = self.change_entry.mark(position.clone());
                             *text = replaced;
                         }
                     }
@@ -93,9 +115,9 @@ impl<'a> SAEVisitorMut for Visitor<'a> {
                         || self.amended_part == &TextAmendmentSAEPart::IntroOnly
                             && self.reference == position
                     {
-                        if let Some(replaced) = normalized_replace(intro, from, to) {
+                        if let Some(replaced) = tolerant_replace(intro, from, to, self.level) {
                             self.applied = true;
-                            element.last_change = Some(self.change_entry.clone());
+                            element.last_change = self.change_entry.mark(position.clone());
                             *intro = replaced;
                         }
                     }
@@ -104,9 +126,10 @@ impl<'a> SAEVisitorMut for Visitor<'a> {
                             || self.amended_part == &TextAmendmentSAEPart::WrapUpOnly
                                 && self.reference == position
                         {
-                            if let Some(replaced) = normalized_replace(wrap_up, from, to) {
+                            if let Some(replaced) = tolerant_replace(wrap_up, from, to, self.level)
+                            {
                                 self.applied = true;
-                                element.last_change = Some(self.change_entry.clone());
+                                element.last_change = self.change_entry.mark(position.clone());
                                 *wrap_up = replaced;
                             }
                         }
@@ -157,10 +180,10 @@ mod tests {
     #[test]
     fn test_could_not_apply() {
         let mut test_act: Act = singleton_yaml::from_str(TEST_ACT).unwrap();
-        let change_entry = LastChange {
+        let change_entry = ChangeRecorder::new(LastChange {
             date: NaiveDate::from_ymd(2013, 2, 3),
             cause: ChangeCause::Other("Test".to_string()),
-        };
+        });
 
         let mod_1: TextAmendment = singleton_yaml::from_str(
             r#"
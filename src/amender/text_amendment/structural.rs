@@ -18,12 +18,13 @@
 use anyhow::{bail, ensure, Result};
 use hun_law::{
     reference::structural::StructuralReference,
-    structure::{Act, ActChild, LastChange},
+    structure::{Act, ActChild},
 };
 
 use super::NeedsFullReparse;
 use crate::{
-    amender::text_amendment::text_replace::normalized_replace, structural_cut_points::GetCutPoints,
+    amender::{change_history::ChangeRecorder, text_amendment::text_replace::normalized_replace},
+    structural_cut_points::GetCutPoints,
 };
 
 pub fn apply_structural_title_amendment(
@@ -31,7 +32,7 @@ pub fn apply_structural_title_amendment(
     from: &str,
     to: &str,
     act: &mut Act,
-    change_entry: &LastChange,
+    change_entry: &ChangeRecorder,
 ) -> Result<NeedsFullReparse> {
     let mut applied = false;
     let cut_points = reference.get_cut_points(act, false)?;
@@ -40,14 +41,14 @@ pub fn apply_structural_title_amendment(
             if let Some(replaced) = normalized_replace(&se.title, from, to) {
                 se.title = replaced;
                 applied = true;
-                se.last_change = Some(change_entry.clone());
+                se.last_change = Some(change_entry.last_change().clone());
             }
         }
         ActChild::Subtitle(st) => {
             if let Some(replaced) = normalized_replace(&st.title, from, to) {
                 st.title = replaced;
                 applied = true;
-                st.last_change = Some(change_entry.clone());
+                st.last_change = Some(change_entry.last_change().clone());
             }
         }
         ActChild::Article(_) => {
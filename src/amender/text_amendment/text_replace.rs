@@ -17,16 +17,84 @@
 
 use std::str::CharIndices;
 
+use serde::{Deserialize, Serialize};
+
+/// How tolerant the matching of the replaced text is. Each level includes the
+/// normalizations of the previous ones.
+///
+/// Up to `Whitespace`, matches are whole word.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+pub enum MatchLevel {
+    /// Whole word, exact match
+    #[default]
+    Exact,
+    /// Quotes, dashes and non-breaking spaces are normalized
+    Typographic,
+    /// Runs of whitespace are treated as a single space
+    Whitespace,
+    /// The last word of the replaced text may continue with a suffix in the
+    /// amended text (e.g. "5. §-a" matches "5. §-ának"), which is kept after
+    /// the replacement ("6. §-ának").
+    ///
+    /// Only used if the replaced and the replacement text end in the same word,
+    /// otherwise the suffix may need to change too (e.g. "miniszternek" and
+    /// "Kormánynak").
+    Suffix,
+}
+
+impl MatchLevel {
+    pub const ALL: [MatchLevel; 4] = [
+        MatchLevel::Exact,
+        MatchLevel::Typographic,
+        MatchLevel::Whitespace,
+        MatchLevel::Suffix,
+    ];
+
+    pub fn is_exact(&self) -> bool {
+        *self == Self::Exact
+    }
+
+    /// All levels up to and including `self`, in increasing tolerance
+    pub fn ladder(self) -> impl Iterator<Item = MatchLevel> {
+        Self::ALL.into_iter().filter(move |level| *level <= self)
+    }
+}
+
 pub fn normalized_replace(text: &str, from: &str, to: &str) -> Option<String> {
+    tolerant_replace(text, from, to, MatchLevel::Exact)
+}
+
+/// Replace all whole word occurrences of `from` to `to`, matching with the
+/// specified level (only that level, see `MatchLevel::ladder` for trying
+/// multiple levels).
+pub fn tolerant_replace(text: &str, from: &str, to: &str, level: MatchLevel) -> Option<String> {
     let from = from.trim();
     let to = to.trim();
+    let allow_suffix = level >= MatchLevel::Suffix;
+    if allow_suffix && !same_last_word(from, to) {
+        return None;
+    }
+    let normalized_text = NormalizedText::new(text, level);
+    let normalized_from = NormalizedText::new(from, level).text;
     let mut result = None;
     let mut last_matched_index = 0;
-    for matched_index in WholeWordFinderIterator::new(text, from) {
+    let finder = WholeWordFinderIterator::new(&normalized_text.text, &normalized_from)
+        .with_suffix(allow_suffix);
+    for matched_index in finder {
+        let normalized_end = matched_index + normalized_from.len();
+        let start = normalized_text.original_indices[matched_index];
+        // The suffix (if any) is not part of the match, so it is kept as-is
+        let end = normalized_text.original_indices[normalized_end];
+        if start < last_matched_index {
+            // Overlapping match
+            continue;
+        }
         let result = result.get_or_insert_with(String::new);
-        result.push_str(&text[last_matched_index..matched_index]);
+        result.push_str(&text[last_matched_index..start]);
         result.push_str(to);
-        last_matched_index = matched_index + from.len();
+        last_matched_index = end;
     }
     if let Some(result) = &mut result {
         result.push_str(&text[last_matched_index..]);
@@ -36,6 +104,137 @@ pub fn normalized_replace(text: &str, from: &str, to: &str) -> Option<String> {
     result
 }
 
+/// Normalized version of a text, with the original byte index of every byte
+struct NormalizedText {
+    text: String,
+    /// original_indices[i] is the index in the original string where the
+    /// character at text[i] came from. Has an extra element for the end of the string.
+    original_indices: Vec<usize>,
+}
+
+impl NormalizedText {
+    fn new(s: &str, level: MatchLevel) -> Self {
+        let mut text = String::with_capacity(s.len());
+        let mut original_indices = Vec::with_capacity(s.len() + 1);
+        for (i, c) in s.char_indices() {
+            let c = if level >= MatchLevel::Typographic {
+                normalize_typography(c)
+            } else {
+                c
+            };
+            if level >= MatchLevel::Whitespace && c.is_whitespace() {
+                if text.ends_with(' ') {
+                    continue;
+                }
+                text.push(' ');
+                original_indices.push(i);
+            } else {
+                text.push(c);
+                original_indices.extend(std::iter::repeat(i).take(c.len_utf8()));
+            }
+        }
+        original_indices.push(s.len());
+        Self {
+            text,
+            original_indices,
+        }
+    }
+}
+
+fn normalize_typography(c: char) -> char {
+    match c {
+        '„' | '”' | '“' | '″' | '«' | '»' => '"',
+        '‘' | '’' | '‚' | '′' => '\'',
+        '–' | '—' | '‑' | '‐' | '−' => '-',
+        '\u{a0}' | '\u{202f}' | '\u{2009}' => ' ',
+        c => c,
+    }
+}
+
+fn same_last_word(a: &str, b: &str) -> bool {
+    match (word_spans(a).last(), word_spans(b).last()) {
+        (Some(&(a_start, a_end)), Some(&(b_start, b_end))) => {
+            a_end == a.len() && b_end == b.len() && a[a_start..] == b[b_start..]
+        }
+        _ => false,
+    }
+}
+
+pub fn contains_whole_word(text: &str, needle: &str) -> bool {
+    WholeWordFinderIterator::new(text, needle.trim())
+        .next()
+        .is_some()
+}
+
+/// Substrings of `text` that are closest to `needle` by edit distance.
+///
+/// Candidates start and end at word boundaries (see `is_hun_alphanumeric`),
+/// and have about the same number of words as `needle`. Candidates that are
+/// too far from the needle (more than half of its length) are dropped.
+/// Returns (distance, candidate) pairs, closest first.
+pub fn closest_matches(text: &str, needle: &str, max_results: usize) -> Vec<(usize, String)> {
+    let needle = needle.trim();
+    let needle_word_count = word_spans(needle).len().max(1);
+    let max_distance = needle.chars().count() / 2;
+    let words = word_spans(text);
+    let mut result = Vec::new();
+    for (first_word, (start, _)) in words.iter().enumerate() {
+        let min_words = needle_word_count.saturating_sub(1).max(1);
+        let max_words = needle_word_count + 1;
+        for word_count in min_words..=max_words {
+            if let Some((_, end)) = words.get(first_word + word_count - 1) {
+                let candidate = &text[*start..*end];
+                let distance = edit_distance(candidate, needle);
+                if distance <= max_distance {
+                    result.push((distance, candidate.to_string()));
+                }
+            }
+        }
+    }
+    result.sort();
+    result.dedup_by(|a, b| a.1 == b.1);
+    result.truncate(max_results);
+    result
+}
+
+/// Start and end byte indices of the words in the string
+fn word_spans(s: &str) -> Vec<(usize, usize)> {
+    let mut result = Vec::new();
+    let mut word_start = None;
+    for (i, c) in s.char_indices() {
+        match (is_hun_alphanumeric(c), word_start) {
+            (true, None) => word_start = Some(i),
+            (false, Some(start)) => {
+                result.push((start, i));
+                word_start = None;
+            }
+            _ => (),
+        }
+    }
+    if let Some(start) = word_start {
+        result.push((start, s.len()));
+    }
+    result
+}
+
+/// Levenshtein distance, in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous_row: Vec<usize> = (0..=b.len()).collect();
+    let mut current_row = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        current_row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution_cost = if ca == *cb { 0 } else { 1 };
+            current_row[j + 1] = (previous_row[j] + substitution_cost)
+                .min(previous_row[j + 1] + 1)
+                .min(current_row[j] + 1);
+        }
+        std::mem::swap(&mut previous_row, &mut current_row);
+    }
+    previous_row[b.len()]
+}
+
 struct WordBoundaryIterator<'a> {
     chars_iter: CharIndices<'a>,
     last_was_alphanumeric: bool,
@@ -75,6 +274,7 @@ struct WholeWordFinderIterator<'a> {
     s: &'a str,
     needle: &'a str,
     words_iter: WordBoundaryIterator<'a>,
+    allow_suffix: bool,
 }
 
 impl<'a> WholeWordFinderIterator<'a> {
@@ -83,8 +283,15 @@ impl<'a> WholeWordFinderIterator<'a> {
             s,
             needle,
             words_iter: WordBoundaryIterator::new(s),
+            allow_suffix: false,
         }
     }
+
+    /// Also find the needle if its last word continues in `s`
+    pub fn with_suffix(mut self, allow_suffix: bool) -> Self {
+        self.allow_suffix = allow_suffix;
+        self
+    }
 }
 
 impl<'a> Iterator for WholeWordFinderIterator<'a> {
@@ -93,7 +300,7 @@ impl<'a> Iterator for WholeWordFinderIterator<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         self.words_iter.by_ref().find(|&pos| {
             self.s[pos..].starts_with(self.needle)
-                && !index_is_alphanumeric(self.s, pos + self.needle.len())
+                && (self.allow_suffix || !index_is_alphanumeric(self.s, pos + self.needle.len()))
         })
     }
 }
@@ -147,4 +354,111 @@ mod tests {
             "aaa aaa aaa aaa aaa aaa"
         );
     }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("kettő", "kettő"), 0);
+        assert_eq!(edit_distance("kettő", "ketto"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_closest_matches() {
+        let text = "A munkáltató a munkavállaló részére írásban tájékoztatást ad.";
+        assert_eq!(
+            closest_matches(text, "munkavállalók részére", 1),
+            vec![(1, "munkavállaló részére".to_string())]
+        );
+        assert_eq!(
+            closest_matches(text, "munkáltatónak", 1),
+            vec![(3, "munkáltató".to_string())]
+        );
+        assert!(closest_matches(text, "teljesen más szöveg", 3).is_empty());
+        assert!(contains_whole_word(text, "írásban"));
+        assert!(!contains_whole_word(text, "írás"));
+    }
+
+    #[test]
+    fn test_tolerant_replace() {
+        let text = "az 5.\u{a0}§-ának (2)  bekezdése „a” pontja";
+        assert!(tolerant_replace(text, "5. §-a", "6. §-a", MatchLevel::Exact).is_none());
+        assert!(tolerant_replace(text, "5. §-a", "6. §-a", MatchLevel::Whitespace).is_none());
+        assert_eq!(
+            tolerant_replace(text, "5. §-ának", "6. §-ának", MatchLevel::Typographic).unwrap(),
+            "az 6. §-ának (2) bekezdése „a” pontja"
+        );
+        assert_eq!(
+            tolerant_replace(
+                text,
+                "\"a\" pontja",
+                "\"b\" pontja",
+                MatchLevel::Typographic
+            )
+            .unwrap(),
+            "az 5.\u{a0}§-ának (2) bekezdése \"b\" pontja"
+        );
+        assert!(tolerant_replace(
+            text,
+            "(2) bekezdése",
+            "(3) bekezdése",
+            MatchLevel::Typographic
+        )
+        .is_none());
+        assert_eq!(
+            tolerant_replace(
+                text,
+                "(2) bekezdése",
+                "(3) bekezdése",
+                MatchLevel::Whitespace
+            )
+            .unwrap(),
+            "az 5.\u{a0}§-ának (3) bekezdése „a” pontja"
+        );
+        assert!(tolerant_replace(text, "bekez", "x", MatchLevel::Whitespace).is_none());
+        assert!(tolerant_replace(
+            "a miniszternek kell",
+            "miniszter",
+            "Kormány",
+            MatchLevel::Whitespace
+        )
+        .is_none());
+        assert_eq!(
+            MatchLevel::Whitespace.ladder().collect::<Vec<_>>(),
+            vec![
+                MatchLevel::Exact,
+                MatchLevel::Typographic,
+                MatchLevel::Whitespace
+            ]
+        );
+    }
+
+    #[test]
+    fn test_suffix_tolerant_replace() {
+        let text = "az 5.\u{a0}§-ának (2)  bekezdése";
+        assert_eq!(
+            tolerant_replace(text, "5. §-a", "6. §-a", MatchLevel::Suffix).unwrap(),
+            "az 6. §-ának (2) bekezdése"
+        );
+        // Only suffixes are accepted, the match still starts at a word boundary
+        assert!(tolerant_replace(text, "nak", "nak", MatchLevel::Suffix).is_none());
+        // The suffix would need to change with the last word
+        assert!(tolerant_replace(
+            "a miniszternek kell",
+            "miniszter",
+            "Kormány",
+            MatchLevel::Suffix
+        )
+        .is_none());
+        assert_eq!(
+            tolerant_replace(
+                "az oktatásért felelős miniszternek kell",
+                "oktatásért felelős miniszter",
+                "egészségügyért felelős miniszter",
+                MatchLevel::Suffix
+            )
+            .unwrap(),
+            "az egészségügyért felelős miniszternek kell"
+        );
+    }
 }
@@ -0,0 +1,83 @@
+// Copyright (c) 2022-2023, Alex Badics
+//
+// This file is part of AJDB
+//
+// AJDB is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// AJDB is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
+
+use anyhow::{anyhow, ensure, Result};
+use hun_law::{
+    identifier::{ActIdentifier, IdentifierCommon},
+    reference::Reference,
+    structure::{Act, ChildrenCommon, SAEBody, SubArticleElement},
+    util::walker::SAEVisitorMut,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{change_history::ChangeRecorder, AffectedAct, ModifyAct, NeedsFullReparse};
+
+/// Set the text of a single SAE, regardless of its current content.
+///
+/// Only created from fixups (see `ActFixup::OverrideElementText`), for cases
+/// where the amendments cannot be applied properly.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextOverride {
+    pub position: Reference,
+    pub text: String,
+}
+
+impl ModifyAct for TextOverride {
+    fn apply(&self, act: &mut Act, change_entry: &ChangeRecorder) -> Result<NeedsFullReparse> {
+        let mut visitor = Visitor {
+            text_override: self,
+            change_entry,
+            applied: false,
+        };
+        act.walk_saes_mut(&mut visitor)?;
+        ensure!(
+            visitor.applied,
+            "Text override {self:?} did not have an effect"
+        );
+        Ok(NeedsFullReparse::Yes)
+    }
+}
+
+struct Visitor<'a> {
+    text_override: &'a TextOverride,
+    change_entry: &'a ChangeRecorder,
+    applied: bool,
+}
+
+impl<'a> SAEVisitorMut for Visitor<'a> {
+    fn on_enter<IT: IdentifierCommon, CT: ChildrenCommon>(
+        &mut self,
+        position: &Reference,
+        element: &mut SubArticleElement<IT, CT>,
+    ) -> Result<()> {
+        if *position == self.text_override.position {
+            element.body = SAEBody::Text(self.text_override.text.clone());
+            element.semantic_info = Default::default();
+            element.last_change = self.change_entry.mark(position.clone());
+            self.applied = true;
+        }
+        Ok(())
+    }
+}
+
+impl AffectedAct for TextOverride {
+    fn affected_act(&self) -> Result<ActIdentifier> {
+        self.position
+            .act()
+            .ok_or_else(|| anyhow!("No act in reference in TextOverride"))
+    }
+}
@@ -0,0 +1,302 @@
+// Copyright (c) 2022-2023, Alex Badics
+//
+// This file is part of AJDB
+//
+// AJDB is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// AJDB is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Annulments by the Constitutional Court (Alkotmánybíróság)
+//!
+//! Annulments are maintained by hand in `data/annulments/<year>/<number>.yml`,
+//! one file per decision. They are applied exactly like repeals, but the
+//! modifications (and the change history entries) are marked with the
+//! decision (see `AppliableModification::annulment`).
+//!
+//! Adding or changing an annulment file does not trigger anything by itself:
+//! the states from its application date on have to be recalculated, e.g. with
+//! `ajdb recalculate <retroactive_from or date> <last date>`. Until then, only
+//! the known-on views (`bitemporal`) reflect it.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use hun_law::{reference::Reference, structure::ChangeCause, util::singleton_yaml};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::amender::{repeal::SimplifiedRepeal, AppliableModification};
+
+/// Identifier of a Constitutional Court decision, e.g. "33/2013. AB határozat"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct DecisionIdentifier {
+    pub year: u16,
+    pub number: u16,
+}
+
+impl DecisionIdentifier {
+    /// The change cause used for the annulled elements. It is only a
+    /// human-readable `ChangeCause::Other`, the decision itself is stored in
+    /// `AppliableModification::annulment` and `ChangeHistoryEntry::annulment`.
+    pub fn to_change_cause(self) -> ChangeCause {
+        ChangeCause::Other(self.to_string())
+    }
+}
+
+impl Display for DecisionIdentifier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}. AB határozat", self.number, self.year)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Annulment {
+    /// The full title of the decision, as published.
+    /// E.g. "33/2013. (XI. 22.) AB határozat"
+    pub title: String,
+    /// The first day the annulled provisions are not in force
+    pub date: NaiveDate,
+    /// Only set in case of annulments with retroactive effect (ex tunc):
+    /// the first day the annulled provisions should be considered not in force.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retroactive_from: Option<NaiveDate>,
+    pub positions: Vec<Reference>,
+}
+
+impl Annulment {
+    pub fn load(id: DecisionIdentifier) -> Result<Option<Self>> {
+        Self::load_from(id, "./data/annulments/".into())
+    }
+
+    pub fn load_from(id: DecisionIdentifier, base_dir: PathBuf) -> Result<Option<Self>> {
+        let path = base_dir
+            .join(id.year.to_string())
+            .join(format!("{}.yml", id.number));
+        if path.exists() {
+            Ok(Some(Self::load_file(&path)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn load_file(path: &Path) -> Result<Self> {
+        singleton_yaml::from_reader(File::open(path)?)
+            .with_context(|| format!("Error loading annulment {}", path.display()))
+    }
+
+    /// The date the annulment is applied to the database.
+    ///
+    /// Retroactive annulments are applied at the retroactive date, so they only
+    /// take effect after the database was recalculated from that date.
+    pub fn application_date(&self) -> NaiveDate {
+        self.retroactive_from.unwrap_or(self.date)
+    }
+
+    pub fn get_modifications(&self, id: DecisionIdentifier) -> Vec<AppliableModification> {
+        self.positions
+            .iter()
+            .map(|position| AppliableModification {
+                annulment: Some(id),
+                ..AppliableModification::new(
+                    id.to_change_cause(),
+                    SimplifiedRepeal {
+                        position: position.clone(),
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AnnulmentSet {
+    annulments: BTreeMap<DecisionIdentifier, Annulment>,
+}
+
+impl AnnulmentSet {
+    pub fn load() -> Result<Self> {
+        Self::load_from("./data/annulments/".into())
+    }
+
+    /// Load all annulments in `base_dir`. Files and directories not named
+    /// like `<year>/<number>.yml` are skipped with a warning.
+    pub fn load_from(base_dir: PathBuf) -> Result<Self> {
+        let mut annulments = BTreeMap::new();
+        if !base_dir.exists() {
+            return Ok(Self { annulments });
+        }
+        for year_dir in base_dir.read_dir()? {
+            let year_dir = year_dir?.path();
+            let year = match year_dir.file_name().and_then(|n| n.to_str()) {
+                Some(name) if year_dir.is_dir() => match name.parse() {
+                    Ok(year) => year,
+                    Err(_) => {
+                        warn!("Skipping unexpected entry {}", year_dir.display());
+                        continue;
+                    }
+                },
+                _ => {
+                    warn!("Skipping unexpected entry {}", year_dir.display());
+                    continue;
+                }
+            };
+            for file in year_dir.read_dir()? {
+                let file = file?.path();
+                let number = match (
+                    file.extension().and_then(|e| e.to_str()),
+                    file.file_stem().and_then(|n| n.to_str()),
+                ) {
+                    (Some("yml"), Some(stem)) if file.is_file() => match stem.parse() {
+                        Ok(number) => number,
+                        Err(_) => {
+                            warn!("Skipping unexpected entry {}", file.display());
+                            continue;
+                        }
+                    },
+                    _ => {
+                        warn!("Skipping unexpected entry {}", file.display());
+                        continue;
+                    }
+                };
+                annulments.insert(
+                    DecisionIdentifier { year, number },
+                    Annulment::load_file(&file)?,
+                );
+            }
+        }
+        Ok(Self { annulments })
+    }
+
+    /// Only the annulments that were already in effect on `known_on`
+    pub fn known_on(&self, known_on: NaiveDate) -> Self {
+        Self {
+            annulments: self
+                .annulments
+                .iter()
+                .filter(|(_, annulment)| annulment.date <= known_on)
+                .map(|(id, annulment)| (*id, annulment.clone()))
+                .collect(),
+        }
+    }
+
+    /// The earliest date that is changed by annulments that were not yet in
+    /// effect on `known_on`, i.e. the retroactive ones.
+    pub fn first_application_date_after(&self, known_on: NaiveDate) -> Option<NaiveDate> {
+        self.annulments
+            .values()
+            .filter(|annulment| annulment.date > known_on)
+            .map(|annulment| annulment.application_date())
+            .min()
+    }
+
+    /// All modifications of the annulments that should be applied on `date`
+    pub fn get_modifications(&self, date: NaiveDate) -> Vec<AppliableModification> {
+        self.annulments
+            .iter()
+            .filter(|(_, annulment)| annulment.application_date() == date)
+            .flat_map(|(id, annulment)| annulment.get_modifications(*id))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_change_cause() {
+        let id = DecisionIdentifier {
+            year: 2013,
+            number: 33,
+        };
+        assert_eq!(
+            id.to_change_cause(),
+            ChangeCause::Other("33/2013. AB határozat".to_string())
+        );
+    }
+
+    #[test]
+    fn test_load_skips_stray_entries() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let base_dir = tmp_dir.path().to_path_buf();
+        std::fs::create_dir_all(base_dir.join("2013/drafts")).unwrap();
+        std::fs::write(
+            base_dir.join("2013/33.yml"),
+            "title: 33/2013. (XI. 22.) AB határozat\ndate: 2013-11-23\npositions: []\n",
+        )
+        .unwrap();
+        std::fs::write(base_dir.join("2013/README.md"), "notes").unwrap();
+        std::fs::write(base_dir.join("2013/33.yml~"), "backup").unwrap();
+        std::fs::write(base_dir.join("README.md"), "notes").unwrap();
+        std::fs::create_dir_all(base_dir.join("old")).unwrap();
+
+        let annulments = AnnulmentSet::load_from(base_dir).unwrap();
+        assert_eq!(
+            annulments.annulments.keys().collect::<Vec<_>>(),
+            vec![&DecisionIdentifier {
+                year: 2013,
+                number: 33
+            }]
+        );
+    }
+
+    #[test]
+    fn test_retroactive_annulment() {
+        let annulment: Annulment = singleton_yaml::from_str(
+            r#"
+            title: 33/2013. (XI. 22.) AB határozat
+            date: 2013-11-23
+            retroactive_from: 2013-07-01
+            positions:
+            - act:
+                year: 2012
+                number: 1
+              article: '2'
+              paragraph: '3'
+            "#,
+        )
+        .unwrap();
+        let id = DecisionIdentifier {
+            year: 2013,
+            number: 33,
+        };
+        let annulments = AnnulmentSet {
+            annulments: [(id, annulment)].into_iter().collect(),
+        };
+        assert!(annulments
+            .get_modifications(NaiveDate::from_ymd(2013, 11, 23))
+            .is_empty());
+        let modifications = annulments.get_modifications(NaiveDate::from_ymd(2013, 7, 1));
+        assert_eq!(modifications.len(), 1);
+        assert_eq!(modifications[0].cause, id.to_change_cause());
+        assert_eq!(modifications[0].annulment, Some(id));
+
+        assert_eq!(
+            annulments.first_application_date_after(NaiveDate::from_ymd(2013, 10, 1)),
+            Some(NaiveDate::from_ymd(2013, 7, 1))
+        );
+        assert_eq!(
+            annulments.first_application_date_after(NaiveDate::from_ymd(2013, 11, 23)),
+            None
+        );
+        let known_before = annulments.known_on(NaiveDate::from_ymd(2013, 10, 1));
+        assert!(known_before
+            .get_modifications(NaiveDate::from_ymd(2013, 7, 1))
+            .is_empty());
+    }
+}
@@ -15,25 +15,64 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::path::{Path, PathBuf};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
-use ajdb::{database::ActSet, persistence::Persistence, util::read_all};
-use anyhow::{anyhow, Context, Result};
-use hun_law::structure::Act;
-use log::info;
+use ajdb::{
+    database::{ActMetadata, ActSet},
+    persistence::Persistence,
+    util::read_all,
+    validation::ActValidation,
+};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use chrono::NaiveDate;
+use hun_law::{identifier::ActIdentifier, structure::Act};
+use log::{error, info, warn};
+
+use crate::{
+    explain::{act_to_text, print_text_diff},
+    recalculate::recalculate_until_end,
+    remove::remove_act,
+};
 
 #[derive(Debug, clap::Args)]
 pub struct AddArgs {
     #[clap(required = true, name = "path")]
     paths: Vec<PathBuf>,
+    /// Do not recalculate the database after adding acts with retroactive
+    /// effect. Useful if the whole database is recalculated afterwards anyway.
+    #[clap(long)]
+    no_recalculate: bool,
+    /// Only validate the acts, and show how they differ from the versions
+    /// already in the database. Nothing is stored.
+    #[clap(long)]
+    dry_run: bool,
 }
 
 pub fn cli_add_raw(args: AddArgs) -> Result<()> {
+    let persistence = Persistence::new("db");
+    let mut stored_acts = ActSet::stored_acts(&persistence)?;
     let mut everything_ok = true;
+    let mut recalculate_from: Option<NaiveDate> = None;
     for path in &args.paths {
-        if let Err(err) = add_path(path) {
-            log::error!("{err:?}");
-            everything_ok = false;
+        match add_path(&persistence, path, &mut stored_acts, args.dry_run) {
+            Ok(Some(date)) => {
+                recalculate_from = Some(recalculate_from.map_or(date, |d| d.min(date)))
+            }
+            Ok(None) => (),
+            Err(err) => {
+                log::error!("{err:?}");
+                everything_ok = false;
+            }
+        }
+    }
+    if let Some(from) = recalculate_from {
+        if args.no_recalculate {
+            warn!("Not recalculating the retroactively modified states from {from}");
+        } else {
+            recalculate_until_end(&persistence, from)?;
         }
     }
     if everything_ok {
@@ -43,16 +82,220 @@ pub fn cli_add_raw(args: AddArgs) -> Result<()> {
     }
 }
 
-fn add_path(path: &Path) -> Result<()> {
-    let act: Act = hun_law::util::singleton_yaml::from_slice(
+#[derive(Debug, clap::Args)]
+pub struct ReplaceArgs {
+    /// The new version of an act that is already in the database
+    #[clap(required = true)]
+    path: PathBuf,
+    /// Do not recalculate the affected states. The database will be
+    /// inconsistent until it is recalculated.
+    #[clap(long)]
+    no_recalculate: bool,
+}
+
+/// Replace an act with a new version (e.g. after reparsing it), and
+/// recalculate every state that either version contributed to.
+pub fn cli_replace(args: ReplaceArgs) -> Result<()> {
+    let act = load_act(&args.path)?;
+    let persistence = Persistence::new("db");
+    let stored_acts = ActSet::stored_acts(&persistence)?;
+    let stored_date = stored_acts.get(&act.identifier).ok_or_else(|| {
+        anyhow!(
+            "{} is not in the database, use 'ajdb add' instead",
+            act.identifier
+        )
+    })?;
+    validate_act(&act, &stored_acts)?;
+    let stored_act = stored_version(&persistence, act.identifier, *stored_date)?;
+    if stored_act == act {
+        info!("{} did not change, nothing to do", act.identifier);
+        return Ok(());
+    }
+    print_change_summary(&stored_act, &act);
+
+    let removed_from = remove_act(&persistence, act.identifier)?;
+    // The new version has to be copied to all states after its publication
+    // date, not just the ones where it modifies something.
+    let mut recalculate_from = act.publication_date;
+    if let Some(date) = add_act(&persistence, act)? {
+        recalculate_from = recalculate_from.min(date);
+    }
+    if let Some(date) = removed_from {
+        recalculate_from = recalculate_from.min(date);
+    }
+    if args.no_recalculate {
+        warn!("Not recalculating the affected states from {recalculate_from}");
+        Ok(())
+    } else {
+        recalculate_until_end(&persistence, recalculate_from)
+    }
+}
+
+fn load_act(path: &Path) -> Result<Act> {
+    hun_law::util::singleton_yaml::from_slice(
         &read_all(path).with_context(|| anyhow!("Error reading {path:?}"))?,
     )
-    .with_context(|| anyhow!("Error deserializing {path:?}"))?;
-    let date = act.publication_date;
-    info!("Adding {} to state at {date}", act.identifier);
-    let persistence = Persistence::new("db");
-    let mut state = ActSet::load(&persistence, date)?;
-    state.store_act(act)?;
-    state.save()?;
+    .with_context(|| anyhow!("Error deserializing {path:?}"))
+}
+
+/// Validate the act, and add it to the state of its publication date,
+/// unless it is already in the database.
+/// Returns the first retroactive date of the act, if it has any.
+fn add_path(
+    persistence: &Persistence,
+    path: &Path,
+    stored_acts: &mut BTreeMap<ActIdentifier, NaiveDate>,
+    dry_run: bool,
+) -> Result<Option<NaiveDate>> {
+    let act = load_act(path)?;
+    validate_act(&act, stored_acts)?;
+    if let Some(stored_date) = stored_acts.get(&act.identifier) {
+        let stored_act = stored_version(persistence, act.identifier, *stored_date)?;
+        if stored_act == act {
+            info!("{} is already in the database", act.identifier);
+            return Ok(None);
+        }
+        print_change_summary(&stored_act, &act);
+        bail!(
+            "{} is already in the database with different content, use 'ajdb replace' to overwrite it",
+            act.identifier
+        );
+    }
+    if dry_run {
+        info!(
+            "{} would be added to state at {}",
+            act.identifier, act.publication_date
+        );
+        return Ok(None);
+    }
+    stored_acts.insert(act.identifier, act.publication_date);
+    add_act(persistence, act)
+}
+
+/// The version of the act that was added to the database
+fn stored_version(
+    persistence: &Persistence,
+    act_id: ActIdentifier,
+    stored_date: NaiveDate,
+) -> Result<Act> {
+    match ActMetadata::load(persistence, act_id)?.published_act()? {
+        Some(act) => Ok(act),
+        // Acts added before the published versions were recorded
+        None => ActSet::load(persistence, stored_date)?
+            .get_act(act_id)?
+            .act(),
+    }
+}
+
+/// Log the problems found in the act. Fails if the act should not be stored.
+fn validate_act(act: &Act, stored_acts: &BTreeMap<ActIdentifier, NaiveDate>) -> Result<()> {
+    let validation = ActValidation::validate(act, |act_id| stored_acts.contains_key(&act_id))?;
+    for warning in &validation.warnings {
+        warn!("{}: {warning}", act.identifier);
+    }
+    if !validation.unknown_targets.is_empty() {
+        warn!(
+            "{} modifies acts that are not in the database: {}",
+            act.identifier,
+            validation
+                .unknown_targets
+                .iter()
+                .map(|act_id| act_id.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    for error in &validation.errors {
+        error!("{}: {error}", act.identifier);
+    }
+    ensure!(
+        validation.is_ok(),
+        "{} is invalid, not storing it",
+        act.identifier
+    );
     Ok(())
 }
+
+/// Print how the new version of an act differs from the one in the database
+fn print_change_summary(stored_act: &Act, act: &Act) {
+    println!("=== Changes in {} ===", act.identifier);
+    if stored_act.publication_date != act.publication_date {
+        println!(
+            "Publication date: {} -> {}",
+            stored_act.publication_date, act.publication_date
+        );
+    }
+    if !print_text_diff(&act_to_text(stored_act, 105), &act_to_text(act, 105)) {
+        println!("The text of the act did not change, only its metadata");
+    }
+}
+
+/// Add the act to the state of its publication date. Returns the first
+/// retroactive date of the act, if it has any.
+pub fn add_act(persistence: &Persistence, act: Act) -> Result<Option<NaiveDate>> {
+    info!(
+        "Adding {} to state at {}",
+        act.identifier, act.publication_date
+    );
+    let act_id = act.identifier;
+    let retroactive_dates = ActSet::add_new_act(persistence, act)?;
+    if !retroactive_dates.is_empty() {
+        warn!(
+            "{} has retroactive effect on {}",
+            act_id,
+            retroactive_dates
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    Ok(retroactive_dates.first().copied())
+}
+
+#[cfg(test)]
+mod tests {
+    use ajdb::testing::{amending_act, pin_fixup_registry, AMENDING};
+    use hun_law::util::singleton_yaml;
+
+    use super::*;
+
+    #[test]
+    fn test_add_path() {
+        let _lock = pin_fixup_registry(Vec::new());
+        let dir = tempfile::tempdir().unwrap();
+        let persistence = Persistence::new(dir.path().join("db"));
+        let path = dir.path().join("act.yml");
+        let mut act = amending_act();
+        std::fs::write(&path, singleton_yaml::to_string(&act).unwrap()).unwrap();
+        let mut stored_acts = BTreeMap::new();
+
+        // Modifying acts that are not in the database is only a warning
+        assert_eq!(
+            add_path(&persistence, &path, &mut stored_acts, true).unwrap(),
+            None
+        );
+        assert!(stored_acts.is_empty());
+        assert!(ActSet::stored_acts(&persistence).unwrap().is_empty());
+
+        assert_eq!(
+            add_path(&persistence, &path, &mut stored_acts, false).unwrap(),
+            None
+        );
+        assert!(stored_acts.contains_key(&AMENDING));
+        assert_eq!(stored_acts, ActSet::stored_acts(&persistence).unwrap());
+
+        // The same version is not added again
+        assert_eq!(
+            add_path(&persistence, &path, &mut stored_acts, false).unwrap(),
+            None
+        );
+
+        act.subject.push_str(" (javított)");
+        std::fs::write(&path, singleton_yaml::to_string(&act).unwrap()).unwrap();
+        let error = add_path(&persistence, &path, &mut stored_acts, false).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("already in the database with different content"));
+    }
+}
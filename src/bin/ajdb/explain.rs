@@ -0,0 +1,321 @@
+// Copyright (c) 2022-2023, Alex Badics
+//
+// This file is part of AJDB
+//
+// AJDB is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// AJDB is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+
+use ajdb::{
+    amender::{
+        text_amendment::diagnostics::TextAmendmentDiagnostic, AppliableModification,
+        AppliableModificationSet, ModificationObserver, NeedsFullReparse, OnError,
+    },
+    annulment::AnnulmentSet,
+    database::{ActMetadata, ActSet},
+    enforcement_date_set::EnforcementDateSet,
+    events::EventRegistry,
+    persistence::Persistence,
+};
+use anyhow::{bail, Result};
+use chrono::NaiveDate;
+use hun_law::{
+    identifier::ActIdentifier,
+    output::{CliOutput, OutputFormat},
+    structure::{Act, ChangeCause},
+    util::singleton_yaml,
+};
+use similar::{ChangeTag, TextDiff};
+
+#[derive(Debug, clap::Args)]
+pub struct ExplainArgs {
+    #[clap(value_parser, required = true)]
+    /// The Act to explain in Year/ISSUE format. Example: '2013/31'
+    act: ActIdentifier,
+    #[clap(value_parser, required = true)]
+    /// The date of the modifications to explain. Format is "2013-12-31".
+    date: NaiveDate,
+    /// Width of the word-wrapped text in the before/after diffs
+    #[clap(long, short, default_value = "105")]
+    width: usize,
+}
+
+/// Dry-run the recalculation of a single date for a single act, and print
+/// everything that happens. Nothing is written to the database.
+///
+/// The modifications are extracted from the acts as they were stored the day
+/// before, i.e. amendments of the amending acts that come into force on the
+/// same day are not taken into account.
+pub fn cli_explain(args: ExplainArgs) -> Result<()> {
+    let persistence = Persistence::new("db");
+    let state = ActSet::load(&persistence, args.date.pred())?;
+    if state.is_empty() {
+        bail!("The database is empty at date {}", args.date.pred());
+    }
+    let mut act = state.get_act(args.act)?.act()?;
+
+    let (modifications, source_acts) = collect_modifications(
+        &state,
+        args.date,
+        &AnnulmentSet::load()?,
+        &EventRegistry::load()?,
+    )?;
+    let modifications = modifications
+        .get_modifications()
+        .remove(&args.act)
+        .unwrap_or_default();
+    if modifications.is_empty() {
+        println!("No modifications affect {} on {}", args.act, args.date);
+        return Ok(());
+    }
+
+    println!(
+        "{} modifications affect {} on {}, in application order:",
+        modifications.len(),
+        args.act,
+        args.date
+    );
+    for (i, modification) in modifications.iter().enumerate() {
+        println!();
+        println!("#{i}: {}", describe_origin(modification));
+        println!(
+            "    Enforcement date: {}",
+            explain_enforcement_date(modification, args.date, &source_acts)
+        );
+        for line in singleton_yaml::to_string(&modification.modification)?.lines() {
+            println!("    {line}");
+        }
+    }
+
+    println!();
+    println!("Applying modifications:");
+    let mut observer = ExplainObserver {
+        persistence: &persistence,
+        act_id: args.act,
+        date: args.date,
+        width: args.width,
+        step: 0,
+    };
+    AppliableModificationSet::apply_to_act_observed(
+        &mut act,
+        args.date,
+        modifications,
+        OnError::Warn,
+        Some(&mut observer),
+    )?;
+
+    let stored_state = ActSet::load(&persistence, args.date)?;
+    if stored_state.has_act(args.act) {
+        let stored_act = stored_state.get_act(args.act)?.act()?;
+        if stored_act.children == act.children {
+            println!("The result matches the stored state at {}", args.date);
+        } else {
+            println!(
+                "The result DOES NOT match the stored state at {}. Recalculation may be needed.",
+                args.date
+            );
+        }
+    }
+    Ok(())
+}
+
+/// All modifications that should be applied on `date`, extracted from the
+/// acts in `state` (which should be the state of the day before), along with
+/// the acts they were extracted from.
+pub fn collect_modifications(
+    state: &ActSet,
+    date: NaiveDate,
+    annulments: &AnnulmentSet,
+    events: &EventRegistry,
+) -> Result<(AppliableModificationSet, BTreeMap<ActIdentifier, Act>)> {
+    let mut source_acts = BTreeMap::new();
+    let mut modifications = AppliableModificationSet::default();
+    modifications.add_fixups(date)?;
+    modifications.add_annulments(annulments, date)?;
+    modifications.add_retroactive(state.persistence(), date, |act_id, _| {
+        !state.has_act(act_id)
+    })?;
+    for act_entry in state.get_acts()? {
+        if act_entry.is_date_interesting(date, events) {
+            let source_act = act_entry.act()?;
+            modifications.add(&source_act, date)?;
+            source_acts.insert(source_act.identifier, source_act);
+        }
+    }
+    Ok((modifications, source_acts))
+}
+
+pub fn describe_origin(modification: &AppliableModification) -> String {
+    if let Some(fixup) = &modification.fixup {
+        let mut result = format!(
+            "Fixup {} #{} (cause: {:?})",
+            fixup.file, fixup.index, modification.cause
+        );
+        if let Some(note) = &fixup.note {
+            result.push_str(&format!(", note: {note}"));
+        }
+        return result;
+    }
+    if let Some(decision) = modification.annulment {
+        return format!("Annulment by the Constitutional Court ({decision})");
+    }
+    match &modification.cause {
+        ChangeCause::Amendment(cause_ref) => format!("Extracted from {cause_ref}"),
+        ChangeCause::AutoRepeal => "Automatic repeal (Jat. 12/A. §)".to_string(),
+        ChangeCause::Other(text) => format!("Other: {text}"),
+    }
+}
+
+fn explain_enforcement_date(
+    modification: &AppliableModification,
+    date: NaiveDate,
+    source_acts: &BTreeMap<ActIdentifier, Act>,
+) -> String {
+    let cause_ref = match &modification.cause {
+        ChangeCause::Amendment(cause_ref) => cause_ref,
+        ChangeCause::AutoRepeal => {
+            return format!("the repealed provision came into force on {}", date.pred())
+        }
+        ChangeCause::Other(_) => return "not applicable".to_string(),
+    };
+    let source_act = match cause_ref.act().and_then(|act_id| source_acts.get(&act_id)) {
+        Some(source_act) => source_act,
+        None => return "source act was not found in the database".to_string(),
+    };
+    match EnforcementDateSet::from_act(source_act) {
+        Ok(ed_set) => {
+            let explanation = match ed_set.effective_enforcement_date_with_reason(cause_ref) {
+                (ed_date, Some(ed_position)) => {
+                    format!("{ed_date}, specifically set for {ed_position}")
+                }
+                (ed_date, None) => format!("{ed_date}, the default date of the act"),
+            };
+            if ed_set.warnings().is_empty() {
+                explanation
+            } else {
+                format!(
+                    "{explanation} (best guess, warnings: {})",
+                    ed_set
+                        .warnings()
+                        .iter()
+                        .map(|w| w.to_string())
+                        .collect::<Vec<_>>()
+                        .join("; ")
+                )
+            }
+        }
+        Err(err) => format!("could not be calculated: {err:?}"),
+    }
+}
+
+struct ExplainObserver<'p> {
+    persistence: &'p Persistence,
+    act_id: ActIdentifier,
+    date: NaiveDate,
+    width: usize,
+    step: usize,
+}
+
+/// The act as rendered by `ajdb show`, word-wrapped to `width`
+pub fn act_to_text(act: &Act, width: usize) -> String {
+    let mut result = Vec::new();
+    match act.cli_output(width, OutputFormat::default(), &mut result) {
+        Ok(_) => String::from_utf8_lossy(&result).into_owned(),
+        Err(err) => format!("Could not render act: {err:?}"),
+    }
+}
+
+/// Print the changed lines with one line of context, in a unified diff-like
+/// format. Returns false if the texts are the same.
+pub fn print_text_diff(text_before: &str, text_after: &str) -> bool {
+    let diff = TextDiff::from_lines(text_before, text_after);
+    let mut changed = false;
+    for group in diff.grouped_ops(1) {
+        for op in group {
+            for change in diff.iter_changes(&op) {
+                let tag = match change.tag() {
+                    ChangeTag::Delete => "-",
+                    ChangeTag::Insert => "+",
+                    ChangeTag::Equal => " ",
+                };
+                print!("{tag}{change}");
+                if change.missing_newline() {
+                    println!();
+                }
+            }
+        }
+        println!("...");
+        changed = true;
+    }
+    changed
+}
+
+impl<'p> ExplainObserver<'p> {
+    /// The last date before the current one where the failed text amendment
+    /// would have been applicable.
+    fn last_applicable_date(
+        &self,
+        diagnostic: &TextAmendmentDiagnostic,
+    ) -> Result<Option<NaiveDate>> {
+        let mut dates = ActMetadata::load(self.persistence, self.act_id)?.modification_dates();
+        dates.retain(|d| *d < self.date);
+        dates.sort();
+        for date in dates.into_iter().rev() {
+            let state = ActSet::load(self.persistence, date)?;
+            if state.has_act(self.act_id)
+                && diagnostic.applies_to(&state.get_act(self.act_id)?.act()?)?
+            {
+                return Ok(Some(date));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<'p> ModificationObserver for ExplainObserver<'p> {
+    fn on_applied(
+        &mut self,
+        modification: &AppliableModification,
+        act_before: &Act,
+        act_after: &Act,
+        result: &Result<NeedsFullReparse>,
+    ) {
+        println!();
+        println!(
+            "--- Step #{} (cause: {:?}) ---",
+            self.step, modification.cause
+        );
+        self.step += 1;
+        if let Err(err) = result {
+            println!("FAILED: {err:?}");
+            if let Some(diagnostic) = err.downcast_ref::<TextAmendmentDiagnostic>() {
+                match self.last_applicable_date(diagnostic) {
+                    Ok(Some(date)) => println!(
+                        "The text to be replaced was last present in the target on {date}"
+                    ),
+                    Ok(None) => println!(
+                        "The text to be replaced was never present in the target in earlier versions"
+                    ),
+                    Err(err) => println!("Could not check earlier versions: {err:?}"),
+                }
+            }
+            return;
+        }
+        let text_before = act_to_text(act_before, self.width);
+        let text_after = act_to_text(act_after, self.width);
+        if !print_text_diff(&text_before, &text_after) {
+            println!("The text of the act did not change");
+        }
+    }
+}
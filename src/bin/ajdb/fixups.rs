@@ -0,0 +1,1026 @@
+// Copyright (c) 2022-2023, Alex Badics
+//
+// This file is part of AJDB
+//
+// AJDB is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// AJDB is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use ajdb::{
+    amender::{
+        extract::{extract_modifications_from_act, extract_modifications_with_fixups},
+        fix_order::affected_reference,
+        text_amendment::text_replace::MatchLevel,
+        AppliableModification, AppliableModificationSet, ModificationObserver, NeedsFullReparse,
+        OnError,
+    },
+    annulment::AnnulmentSet,
+    database::ActSet,
+    enforcement_date_set::EnforcementDateSet,
+    events::EventRegistry,
+    fixups::{
+        list_fixup_files, ActFixup, ActFixups, FixupConflict, FixupOrigin, FixupRegistry,
+        GlobalFixup, GlobalFixups, ModificationSelector,
+    },
+    persistence::Persistence,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{NaiveDate, Utc};
+use hun_law::{
+    identifier::ActIdentifier,
+    structure::{Act, ChangeCause},
+};
+use log::info;
+
+use crate::explain::collect_modifications;
+
+#[derive(Debug, clap::Args)]
+pub struct FixupsArgs {
+    #[clap(subcommand)]
+    command: FixupsCommand,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum FixupsCommand {
+    /// Report fixups that are never used, duplicate what is extracted from the
+    /// acts anyway, or fail to apply. Needs a fully calculated database.
+    Check(CheckArgs),
+    /// Create a fixup for a modification of an act (usually a failing one), and
+    /// show whether the modifications apply with the new fixup.
+    New(NewArgs),
+}
+
+#[derive(Debug, clap::Args)]
+pub struct CheckArgs {
+    #[clap(value_parser, long, short, default_value_t=Utc::today().naive_utc())]
+    /// The acts in the database on this date are checked. Format is "2013-12-31". Defaults to today.
+    date: NaiveDate,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct NewArgs {
+    #[clap(value_parser, required = true)]
+    /// The modified Act in Year/ISSUE format. Example: '2013/31'
+    act: ActIdentifier,
+    #[clap(value_parser, required = true)]
+    /// The date of the modification. Format is "2013-12-31".
+    date: NaiveDate,
+    /// The number of the modification, as printed by 'ajdb explain'. Defaults
+    /// to the first one that fails to apply.
+    #[clap(long, short)]
+    index: Option<usize>,
+    /// The kind of fixup to create
+    #[clap(value_enum, long, short, default_value_t)]
+    kind: NewFixupKind,
+}
+
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+enum NewFixupKind {
+    /// ReplaceModification in the fixups of the amending act, pre-filled with
+    /// the original modification
+    #[default]
+    Replace,
+    /// RemoveModification in the fixups of the amending act
+    Remove,
+    /// AllowTolerantMatch in the fixups of the amending act, allowing
+    /// typographic and whitespace differences. Suffix tolerant matching has
+    /// to be set by hand.
+    Tolerant,
+    /// AddModification in the fixups of the date, pre-filled with the original
+    /// modification
+    Add,
+}
+
+pub fn cli_fixups(args: FixupsArgs) -> Result<()> {
+    match args.command {
+        FixupsCommand::Check(a) => cli_fixups_check(a),
+        FixupsCommand::New(a) => cli_fixups_new(a),
+    }
+}
+
+#[derive(Debug)]
+struct FixupProblem {
+    path: PathBuf,
+    /// Index of the entry in the fixup file. None if it's about the whole file.
+    index: Option<usize>,
+    kind: &'static str,
+    problem: ProblemType,
+}
+
+#[derive(Debug)]
+enum ProblemType {
+    UnknownAct,
+    InvalidFileName,
+    Overridden {
+        by: FixupOrigin,
+    },
+    Unused,
+    Duplicate {
+        date: Option<NaiveDate>,
+    },
+    TargetNotInDatabase {
+        act_id: ActIdentifier,
+    },
+    Failed {
+        date: Option<NaiveDate>,
+        error: String,
+    },
+}
+
+impl Display for FixupProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some(index) = self.index {
+            write!(f, " #{index}")?;
+        }
+        if !self.kind.is_empty() {
+            write!(f, " ({})", self.kind)?;
+        }
+        write!(f, ": ")?;
+        match &self.problem {
+            ProblemType::UnknownAct => write!(f, "the act is not in the database"),
+            ProblemType::InvalidFileName => write!(f, "invalid date fixup file name"),
+            ProblemType::Overridden { by } => write!(f, "overridden by {by}"),
+            ProblemType::Unused => write!(f, "never used"),
+            ProblemType::Duplicate { date: Some(date) } => {
+                write!(f, "duplicates a modification extracted on {date}")
+            }
+            ProblemType::Duplicate { date: None } => {
+                write!(f, "duplicates an entry extracted from the act")
+            }
+            ProblemType::TargetNotInDatabase { act_id } => {
+                write!(f, "the modified act ({act_id}) is not in the database")
+            }
+            ProblemType::Failed {
+                date: Some(date),
+                error,
+            } => write!(f, "failed to apply on {date}: {error}"),
+            ProblemType::Failed { date: None, error } => write!(f, "failed: {error}"),
+        }
+    }
+}
+
+fn cli_fixups_check(args: CheckArgs) -> Result<()> {
+    let persistence = Persistence::new("db");
+    let problems = check_fixups(&persistence, args.date)?;
+    for problem in &problems {
+        println!("{problem}");
+    }
+    if problems.is_empty() {
+        println!("All fixups are used and applicable");
+        Ok(())
+    } else {
+        Err(anyhow!("Found {} problems with fixups", problems.len()))
+    }
+}
+
+/// Check the fixups of the acts in the database on `date`, and all date fixups
+fn check_fixups(persistence: &Persistence, date: NaiveDate) -> Result<Vec<FixupProblem>> {
+    let state = ActSet::load(persistence, date)?;
+    if state.is_empty() {
+        bail!("The database is empty at date {date}");
+    }
+    let mut checker = FixupChecker {
+        persistence,
+        annulments: AnnulmentSet::load()?,
+        events: EventRegistry::load()?,
+        application_results: BTreeMap::new(),
+        problems: Vec::new(),
+    };
+
+    let layers = FixupRegistry::get()?.layers().to_vec();
+    let mut known_files = BTreeSet::new();
+    for act_entry in state.get_acts()? {
+        let act_id = act_entry.identifier();
+        let act_fixups = ActFixups::load(act_id)?;
+        if !act_fixups.entries().is_empty() {
+            info!("Checking fixups of {act_id}");
+            checker
+                .check_act_fixups(&act_entry.act()?, &act_fixups)
+                .with_context(|| anyhow!("Error checking fixups of {act_id}"))?;
+        }
+        known_files.extend(layers.iter().map(|layer| layer.act_path(act_id)));
+    }
+    let mut dates = BTreeSet::new();
+    for layer in &layers {
+        for path in list_fixup_files(&layer.act_dir())? {
+            if !known_files.contains(&path) {
+                checker.report(&path, None, "", ProblemType::UnknownAct);
+            }
+        }
+        for path in list_fixup_files(&layer.date_dir())? {
+            match path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok())
+            {
+                Some(date) => {
+                    dates.insert(date);
+                }
+                None => checker.report(&path, None, "", ProblemType::InvalidFileName),
+            }
+        }
+    }
+    for date in dates {
+        info!("Checking date fixups of {date}");
+        checker
+            .check_date_fixups(date)
+            .with_context(|| anyhow!("Error checking date fixups of {date}"))?;
+    }
+    Ok(checker.problems)
+}
+
+fn cli_fixups_new(args: NewArgs) -> Result<()> {
+    let persistence = Persistence::new("db");
+    let new_fixup = new_fixup(&persistence, &args)?;
+    println!(
+        "Fixup for modification #{} written to {}",
+        new_fixup.index,
+        new_fixup.path.display()
+    );
+    let mut failures = 0;
+    for (i, result) in new_fixup.results.iter().enumerate() {
+        let from_new_file = result
+            .modification
+            .fixup
+            .as_ref()
+            .map_or(false, |fixup| Path::new(&fixup.file) == new_fixup.path);
+        let marker = if from_new_file { " (fixup)" } else { "" };
+        match &result.error {
+            Some(error) => {
+                failures += 1;
+                println!("#{i}{marker}: FAILED: {error}");
+            }
+            None => println!("#{i}{marker}: OK"),
+        }
+    }
+    if failures == 0 {
+        println!("All modifications of {} apply on {}", args.act, args.date);
+    } else {
+        println!(
+            "{failures} modifications of {} still fail on {}. Edit {} and check with 'ajdb explain'.",
+            args.act,
+            args.date,
+            new_fixup.path.display()
+        );
+    }
+    Ok(())
+}
+
+struct NewFixup {
+    /// The index of the modification the fixup was created for
+    index: usize,
+    /// The fixup file the new entry was written to
+    path: PathBuf,
+    /// The results of applying the modifications with the new fixup
+    results: Vec<ApplicationResult>,
+}
+
+fn new_fixup(persistence: &Persistence, args: &NewArgs) -> Result<NewFixup> {
+    let state = ActSet::load(persistence, args.date.pred())?;
+    if state.is_empty() {
+        bail!("The database is empty at date {}", args.date.pred());
+    }
+    let annulments = AnnulmentSet::load()?;
+    let events = EventRegistry::load()?;
+    let modifications = modifications_of_act(&state, args.act, args.date, &annulments, &events)?;
+    if modifications.is_empty() {
+        bail!("No modifications affect {} on {}", args.act, args.date);
+    }
+    let results = apply_modifications(&state, args.act, args.date, modifications.clone())?;
+    let index = match args.index {
+        Some(index) => index,
+        None => {
+            let failed = results.iter().find(|r| r.error.is_some()).ok_or_else(|| {
+                anyhow!(
+                    "All modifications of {} apply on {}. Use --index to select one.",
+                    args.act,
+                    args.date
+                )
+            })?;
+            modifications
+                .iter()
+                .position(|m| *m == failed.modification)
+                .ok_or_else(|| anyhow!("Failed modification not found"))?
+        }
+    };
+    let modification = modifications.get(index).ok_or_else(|| {
+        anyhow!(
+            "There are only {} modifications affecting {} on {}",
+            modifications.len(),
+            args.act,
+            args.date
+        )
+    })?;
+    let error = results
+        .iter()
+        .find(|r| r.modification == *modification)
+        .and_then(|r| r.error.clone());
+
+    let mut comment = match &modification.cause {
+        ChangeCause::Amendment(cause_ref) => format!("@ {cause_ref}"),
+        cause => format!("@ {cause:?}"),
+    };
+    if let Some(error) = &error {
+        comment.push_str(&format!("\nFailed on {}: {error}", args.date));
+    }
+    if let Some(fixup) = &modification.fixup {
+        bail!(
+            "The modification already comes from {} #{}, edit it directly",
+            fixup.file,
+            fixup.index
+        );
+    }
+    let selector = ModificationSelector {
+        cause: modification.cause.clone(),
+        position: affected_reference(&modification.modification),
+    };
+    let path = match args.kind {
+        NewFixupKind::Replace | NewFixupKind::Remove | NewFixupKind::Tolerant => {
+            let source_act_id = match &modification.cause {
+                ChangeCause::Amendment(cause_ref) => cause_ref.act(),
+                _ => None,
+            }
+            .ok_or_else(|| {
+                anyhow!("Only modifications extracted from acts can be removed or replaced")
+            })?;
+            let fixup = match args.kind {
+                NewFixupKind::Remove => ActFixup::RemoveModification(selector),
+                NewFixupKind::Tolerant => ActFixup::AllowTolerantMatch {
+                    selector,
+                    max_match_level: MatchLevel::Whitespace,
+                },
+                _ => ActFixup::ReplaceModification {
+                    selector,
+                    modification: modification.modification.clone(),
+                },
+            };
+            ActFixups::append(source_act_id, &fixup, &comment)?
+        }
+        NewFixupKind::Add => GlobalFixups::append(
+            args.date,
+            &GlobalFixup::AddModification(modification.clone()),
+            &comment,
+        )?,
+    };
+
+    // Re-run the date with the new fixup, the same way `ajdb explain` would
+    let modifications = modifications_of_act(&state, args.act, args.date, &annulments, &events)?;
+    let results = apply_modifications(&state, args.act, args.date, modifications)?;
+    Ok(NewFixup {
+        index,
+        path,
+        results,
+    })
+}
+
+/// Modifications affecting `act_id` on `date`, in application order, with all
+/// the current fixups.
+fn modifications_of_act(
+    state: &ActSet,
+    act_id: ActIdentifier,
+    date: NaiveDate,
+    annulments: &AnnulmentSet,
+    events: &EventRegistry,
+) -> Result<Vec<AppliableModification>> {
+    let (modifications, _) = collect_modifications(state, date, annulments, events)?;
+    Ok(modifications
+        .get_modifications()
+        .remove(&act_id)
+        .unwrap_or_default())
+}
+
+/// Apply modifications to the act the same way recalculation does, and
+/// collect the result of each of them.
+fn apply_modifications(
+    state: &ActSet,
+    act_id: ActIdentifier,
+    date: NaiveDate,
+    modifications: Vec<AppliableModification>,
+) -> Result<Vec<ApplicationResult>> {
+    let mut act = state.get_act(act_id)?.act()?;
+    let mut observer = ResultCollector::default();
+    AppliableModificationSet::apply_to_act_observed(
+        &mut act,
+        date,
+        modifications,
+        OnError::Warn,
+        Some(&mut observer),
+    )?;
+    Ok(observer.results)
+}
+
+struct FixupChecker<'p> {
+    persistence: &'p Persistence,
+    annulments: AnnulmentSet,
+    events: EventRegistry,
+    /// Cached results of applying all modifications of a date to an act
+    application_results: BTreeMap<(NaiveDate, ActIdentifier), Vec<ApplicationResult>>,
+    problems: Vec<FixupProblem>,
+}
+
+#[derive(Debug)]
+struct ApplicationResult {
+    modification: AppliableModification,
+    error: Option<String>,
+}
+
+impl<'p> FixupChecker<'p> {
+    fn report(
+        &mut self,
+        path: &Path,
+        index: Option<usize>,
+        kind: &'static str,
+        problem: ProblemType,
+    ) {
+        self.problems.push(FixupProblem {
+            path: path.to_owned(),
+            index,
+            kind,
+            problem,
+        })
+    }
+
+    fn check_act_fixups(&mut self, act: &Act, act_fixups: &ActFixups) -> Result<()> {
+        self.report_conflicts(act_fixups.conflicts());
+        let (raw_enforcement_dates, raw_expiries) = EnforcementDateSet::extract_from_act(act)?;
+
+        // Modifications are extracted from the state of the previous day, just
+        // like during recalculation.
+        let mut extracted = Vec::new();
+        if act_fixups.fixups().any(|fixup| {
+            matches!(
+                fixup,
+                ActFixup::AddModification(_)
+                    | ActFixup::RemoveModification(_)
+                    | ActFixup::ReplaceModification { .. }
+                    | ActFixup::AllowTolerantMatch { .. }
+                    | ActFixup::OverrideElementText(_)
+            )
+        }) {
+            let mut dates = EnforcementDateSet::from_act(act)?.get_all_dates();
+            dates.sort();
+            dates.dedup();
+            for date in dates {
+                let state = ActSet::load(self.persistence, date.pred())?;
+                if !state.has_act(act.identifier) {
+                    continue;
+                }
+                let source_act = state.get_act(act.identifier)?.act()?;
+                extracted.push((
+                    date,
+                    extract_modifications_with_fixups(&source_act, date, act_fixups)?,
+                    extract_modifications_with_fixups(&source_act, date, &ActFixups::default())?,
+                ));
+            }
+        }
+
+        for entry in act_fixups.entries() {
+            let (path, index) = (entry.origin.path.as_path(), entry.origin.index);
+            match &entry.fixup {
+                ActFixup::AddModification(modification) => {
+                    let modification = &AppliableModification {
+                        fixup: Some(entry.provenance()),
+                        ..modification.clone()
+                    };
+                    let mut used = false;
+                    for (date, with_fixups, without_fixups) in &extracted {
+                        if !with_fixups.contains(modification) {
+                            continue;
+                        }
+                        used = true;
+                        if without_fixups
+                            .iter()
+                            .any(|m| same_modification(m, modification))
+                        {
+                            self.report(
+                                path,
+                                Some(index),
+                                "AddModification",
+                                ProblemType::Duplicate { date: Some(*date) },
+                            );
+                        } else {
+                            self.check_application(
+                                path,
+                                index,
+                                "AddModification",
+                                modification,
+                                *date,
+                            )?;
+                        }
+                    }
+                    if !used {
+                        self.report(path, Some(index), "AddModification", ProblemType::Unused);
+                    }
+                }
+                ActFixup::AddEnforcementDate(ed) => {
+                    if raw_enforcement_dates.iter().any(|(_, raw_ed)| raw_ed == ed) {
+                        self.report(
+                            path,
+                            Some(index),
+                            "AddEnforcementDate",
+                            ProblemType::Duplicate { date: None },
+                        );
+                    }
+                }
+                ActFixup::AddEventEnforcementDate(event_ed) => {
+                    if let Err(error) = self.events.get(&event_ed.event) {
+                        self.report(
+                            path,
+                            Some(index),
+                            "AddEventEnforcementDate",
+                            ProblemType::Failed {
+                                date: None,
+                                error: error.to_string(),
+                            },
+                        );
+                    }
+                }
+                ActFixup::AddExpiry(expiry) => {
+                    if raw_expiries.contains(expiry) {
+                        self.report(
+                            path,
+                            Some(index),
+                            "AddExpiry",
+                            ProblemType::Duplicate { date: None },
+                        );
+                    }
+                }
+                ActFixup::RemoveModification(selector) => {
+                    if !extracted.iter().any(|(_, _, without_fixups)| {
+                        without_fixups.iter().any(|m| selector.matches(m))
+                    }) {
+                        self.report(path, Some(index), "RemoveModification", ProblemType::Unused);
+                    }
+                }
+                ActFixup::ReplaceModification {
+                    selector,
+                    modification: replacement,
+                } => {
+                    let mut used = false;
+                    for (date, with_fixups, without_fixups) in &extracted {
+                        if !without_fixups.iter().any(|m| selector.matches(m)) {
+                            continue;
+                        }
+                        used = true;
+                        let replaced = with_fixups
+                            .iter()
+                            .find(|m| m.cause == selector.cause && m.modification == *replacement);
+                        if let Some(replaced) = replaced {
+                            self.check_application(
+                                path,
+                                index,
+                                "ReplaceModification",
+                                replaced,
+                                *date,
+                            )?;
+                        }
+                    }
+                    if !used {
+                        self.report(
+                            path,
+                            Some(index),
+                            "ReplaceModification",
+                            ProblemType::Unused,
+                        );
+                    }
+                }
+                ActFixup::AllowTolerantMatch { selector, .. } => {
+                    let provenance = entry.provenance();
+                    let mut used = false;
+                    for (date, with_fixups, without_fixups) in &extracted {
+                        if !without_fixups.iter().any(|m| selector.matches(m)) {
+                            continue;
+                        }
+                        used = true;
+                        for allowed in with_fixups
+                            .iter()
+                            .filter(|m| m.fixup.as_ref() == Some(&provenance))
+                        {
+                            self.check_application(
+                                path,
+                                index,
+                                "AllowTolerantMatch",
+                                allowed,
+                                *date,
+                            )?;
+                        }
+                    }
+                    if !used {
+                        self.report(path, Some(index), "AllowTolerantMatch", ProblemType::Unused);
+                    }
+                }
+                ActFixup::RemoveEnforcementDate(position) => {
+                    if !raw_enforcement_dates.iter().any(|(p, _)| p == position) {
+                        self.report(
+                            path,
+                            Some(index),
+                            "RemoveEnforcementDate",
+                            ProblemType::Unused,
+                        );
+                    }
+                }
+                ActFixup::OverrideElementText(text_override) => {
+                    let modification = AppliableModification {
+                        fixup: Some(entry.provenance()),
+                        ..text_override.to_modification()
+                    };
+                    let used = extracted.iter().any(|(date, with_fixups, _)| {
+                        *date == text_override.date && with_fixups.contains(&modification)
+                    });
+                    if used {
+                        self.check_application(
+                            path,
+                            index,
+                            "OverrideElementText",
+                            &modification,
+                            text_override.date,
+                        )?;
+                    } else {
+                        self.report(
+                            path,
+                            Some(index),
+                            "OverrideElementText",
+                            ProblemType::Unused,
+                        );
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_date_fixups(&mut self, date: NaiveDate) -> Result<()> {
+        let global_fixups = GlobalFixups::load(date)?;
+        self.report_conflicts(global_fixups.conflicts());
+        let state = ActSet::load(self.persistence, date.pred())?;
+        let mut extracted = Vec::new();
+        for act_entry in state.get_acts()? {
+            if act_entry.is_date_interesting(date, &self.events) {
+                let act = act_entry.act()?;
+                extracted.extend(extract_modifications_from_act(&act, date)?);
+            }
+        }
+        for entry in global_fixups.entries() {
+            let (path, index) = (entry.origin.path.as_path(), entry.origin.index);
+            match &entry.fixup {
+                GlobalFixup::AddModification(modification) => {
+                    let modification = &AppliableModification {
+                        fixup: Some(entry.provenance()),
+                        ..modification.clone()
+                    };
+                    if extracted.iter().any(|m| same_modification(m, modification)) {
+                        self.report(
+                            path,
+                            Some(index),
+                            "AddModification",
+                            ProblemType::Duplicate { date: Some(date) },
+                        );
+                    } else {
+                        self.check_application(path, index, "AddModification", modification, date)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn report_conflicts(&mut self, conflicts: &[FixupConflict]) {
+        for conflict in conflicts {
+            self.report(
+                &conflict.overridden.path,
+                Some(conflict.overridden.index),
+                "",
+                ProblemType::Overridden {
+                    by: conflict.by.clone(),
+                },
+            );
+        }
+    }
+
+    /// Apply all modifications of the date to the affected act, the same way
+    /// recalculation does, and report if the fixup failed.
+    fn check_application(
+        &mut self,
+        path: &Path,
+        index: usize,
+        kind: &'static str,
+        modification: &AppliableModification,
+        date: NaiveDate,
+    ) -> Result<()> {
+        let state = ActSet::load(self.persistence, date.pred())?;
+        let (modifications, _) =
+            collect_modifications(&state, date, &self.annulments, &self.events)?;
+        let affected = modifications
+            .get_modifications()
+            .into_iter()
+            .find(|(_, modifications)| modifications.contains(modification));
+        let (act_id, modifications) = if let Some(affected) = affected {
+            affected
+        } else {
+            // Not extracted from the stored state at all, e.g. because the
+            // amending act itself was modified on the same day.
+            self.report(path, Some(index), kind, ProblemType::Unused);
+            return Ok(());
+        };
+        if !state.has_act(act_id) {
+            self.report(
+                path,
+                Some(index),
+                kind,
+                ProblemType::TargetNotInDatabase { act_id },
+            );
+            return Ok(());
+        }
+        if !self.application_results.contains_key(&(date, act_id)) {
+            let results = apply_modifications(&state, act_id, date, modifications)?;
+            self.application_results.insert((date, act_id), results);
+        }
+        let error = self.application_results[&(date, act_id)]
+            .iter()
+            .find(|r| r.modification == *modification)
+            .and_then(|r| r.error.clone());
+        if let Some(error) = error {
+            self.report(
+                path,
+                Some(index),
+                kind,
+                ProblemType::Failed {
+                    date: Some(date),
+                    error,
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct ResultCollector {
+    results: Vec<ApplicationResult>,
+}
+
+impl ModificationObserver for ResultCollector {
+    fn on_applied(
+        &mut self,
+        modification: &AppliableModification,
+        _act_before: &Act,
+        _act_after: &Act,
+        result: &Result<NeedsFullReparse>,
+    ) {
+        self.results.push(ApplicationResult {
+            modification: modification.clone(),
+            error: result.as_ref().err().map(|e| format!("{e:#}")),
+        })
+    }
+}
+
+/// Equality of modifications, regardless of where they come from
+fn same_modification(a: &AppliableModification, b: &AppliableModification) -> bool {
+    a.cause == b.cause && a.modification == b.modification
+}
+
+#[cfg(test)]
+mod tests {
+    use ajdb::{fixups::FixupLayer, testing::pin_fixup_registry};
+    use hun_law::util::singleton_yaml;
+
+    use super::*;
+
+    const AMENDED: ActIdentifier = ActIdentifier {
+        year: 2012,
+        number: 1,
+    };
+    const AMENDING: ActIdentifier = ActIdentifier {
+        year: 2013,
+        number: 1,
+    };
+
+    fn publication_date() -> NaiveDate {
+        NaiveDate::from_ymd(2013, 1, 15)
+    }
+
+    fn amendment_date() -> NaiveDate {
+        NaiveDate::from_ymd(2013, 1, 16)
+    }
+
+    fn parse_act(yaml: &str) -> Act {
+        let mut act: Act = singleton_yaml::from_str(yaml).unwrap();
+        act.add_semantic_info().unwrap();
+        act
+    }
+
+    /// A database with an act, and an act amending its text the day after
+    /// its publication. Both are in the state of the publication date.
+    fn test_database(dir: &Path, amended_text: &str) -> Persistence {
+        let persistence = Persistence::new(dir);
+        let amended = parse_act(&format!(
+            r#"
+            identifier:
+              year: 2012
+              number: 1
+            subject: Az AJDB teszteléséről
+            preamble: ''
+            publication_date: 2012-01-01
+            children:
+            - Article:
+                identifier: '1'
+                children:
+                - identifier: '1'
+                  body: {amended_text}
+            "#
+        ));
+        let amending = parse_act(
+            r#"
+            identifier:
+              year: 2013
+              number: 1
+            subject: Az AJDB teszteléséről szóló törvény módosításáról
+            preamble: ''
+            publication_date: 2013-01-15
+            children:
+            - Article:
+                identifier: '1'
+                children:
+                - identifier: '1'
+                  body: Az AJDB teszteléséről szóló 2012. évi I. törvény 1. § (1) bekezdésében a „miniszter” szövegrész helyébe a „Kormány” szöveg lép.
+            - Article:
+                identifier: '2'
+                children:
+                - identifier: '1'
+                  body: Ez a törvény a kihirdetését követő napon lép hatályba.
+            "#,
+        );
+        let mut state = ActSet::load(&persistence, publication_date()).unwrap();
+        state.store_act(amended).unwrap();
+        state.store_act(amending).unwrap();
+        state.save().unwrap();
+        persistence
+    }
+
+    fn problem_name(problem: &ProblemType) -> &'static str {
+        match problem {
+            ProblemType::UnknownAct => "UnknownAct",
+            ProblemType::InvalidFileName => "InvalidFileName",
+            ProblemType::Overridden { .. } => "Overridden",
+            ProblemType::Unused => "Unused",
+            ProblemType::Duplicate { .. } => "Duplicate",
+            ProblemType::TargetNotInDatabase { .. } => "TargetNotInDatabase",
+            ProblemType::Failed { .. } => "Failed",
+        }
+    }
+
+    #[test]
+    fn test_check_fixups() {
+        let dir = tempfile::tempdir().unwrap();
+        let upstream = FixupLayer::new(dir.path().join("upstream"));
+        let private = FixupLayer::new(dir.path().join("private"));
+        let _lock = pin_fixup_registry(vec![upstream.clone()]);
+        let persistence = test_database(
+            &dir.path().join("db"),
+            "A miniszter rendeletben állapítja meg.",
+        );
+        let amending = ActSet::load(&persistence, publication_date())
+            .unwrap()
+            .get_act(AMENDING)
+            .unwrap()
+            .act()
+            .unwrap();
+        let extracted = extract_modifications_from_act(&amending, amendment_date())
+            .unwrap()
+            .into_iter()
+            .find(|m| matches!(m.cause, ChangeCause::Amendment(_)))
+            .unwrap();
+        let unused_selector = ModificationSelector {
+            cause: ChangeCause::Other("nem létező".into()),
+            position: None,
+        };
+        let failing: AppliableModification = singleton_yaml::from_str(
+            r#"
+            cause:
+              Other: teszt
+            modification:
+              TextOverride:
+                position:
+                  act:
+                    year: 2012
+                    number: 1
+                  article: '9'
+                text: Nem létező szakasz.
+            "#,
+        )
+        .unwrap();
+
+        // Used, and applies
+        ActFixups::append(
+            AMENDING,
+            &ActFixup::ReplaceModification {
+                selector: ModificationSelector {
+                    cause: extracted.cause.clone(),
+                    position: None,
+                },
+                modification: extracted.modification.clone(),
+            },
+            "",
+        )
+        .unwrap();
+        // Overridden in the private layer
+        ActFixups::append(
+            AMENDING,
+            &ActFixup::RemoveModification(unused_selector.clone()),
+            "",
+        )
+        .unwrap();
+        GlobalFixups::append(amendment_date(), &GlobalFixup::AddModification(failing), "").unwrap();
+        std::fs::write(
+            upstream.act_path(ActIdentifier {
+                year: 2013,
+                number: 9,
+            }),
+            "[]",
+        )
+        .unwrap();
+        std::fs::write(upstream.date_dir().join("invalid.yml"), "[]").unwrap();
+
+        FixupRegistry::pin(vec![upstream, private]).unwrap();
+        ActFixups::append(AMENDING, &ActFixup::RemoveModification(unused_selector), "").unwrap();
+
+        let problems = check_fixups(&persistence, publication_date()).unwrap();
+        let summary: Vec<_> = problems
+            .iter()
+            .map(|p| {
+                (
+                    p.path.strip_prefix(dir.path()).unwrap().to_owned(),
+                    p.index,
+                    problem_name(&p.problem),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    PathBuf::from("upstream/act/2013/1.yml"),
+                    Some(1),
+                    "Overridden"
+                ),
+                (PathBuf::from("private/act/2013/1.yml"), Some(0), "Unused"),
+                (PathBuf::from("upstream/act/2013/9.yml"), None, "UnknownAct"),
+                (
+                    PathBuf::from("upstream/date/invalid.yml"),
+                    None,
+                    "InvalidFileName"
+                ),
+                (
+                    PathBuf::from("upstream/date/2013-01-16.yml"),
+                    Some(0),
+                    "Failed"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_new_fixup() {
+        let dir = tempfile::tempdir().unwrap();
+        let layer = FixupLayer::new(dir.path().join("fixups"));
+        let _lock = pin_fixup_registry(vec![layer.clone()]);
+        // The amended text is not in the act, so the amendment fails
+        let persistence = test_database(
+            &dir.path().join("db"),
+            "A miniszterelnök rendeletben állapítja meg.",
+        );
+        let args = NewArgs {
+            act: AMENDED,
+            date: amendment_date(),
+            index: None,
+            kind: NewFixupKind::Remove,
+        };
+        let new = new_fixup(&persistence, &args).unwrap();
+        assert_eq!(new.index, 0);
+        assert_eq!(new.path, layer.act_path(AMENDING));
+        let content = std::fs::read_to_string(&new.path).unwrap();
+        assert!(content.starts_with("# @ "));
+        assert!(content.contains("# Failed on 2013-01-16: "));
+        assert!(content.contains("RemoveModification"));
+        assert!(new.results.iter().all(|r| r.error.is_none()));
+
+        // The modification is removed by the new fixup
+        assert!(new_fixup(&persistence, &args).is_err());
+    }
+}
@@ -16,16 +16,24 @@
 // along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
 
 mod add;
+mod explain;
+mod fixups;
 mod recalculate;
+mod remove;
 mod show;
+mod simulate;
 
 use std::io::Write;
 
-use add::{cli_add_raw, AddArgs};
+use add::{cli_add_raw, cli_replace, AddArgs, ReplaceArgs};
 use anyhow::Result;
 use clap::Parser;
+use explain::{cli_explain, ExplainArgs};
+use fixups::{cli_fixups, FixupsArgs};
 use recalculate::{cli_recalculate, RecalculateArgs};
+use remove::{cli_remove, RemoveArgs};
 use show::{cli_show, ShowArgs};
+use simulate::{cli_simulate, SimulateArgs};
 
 /// AJDB command line interface
 ///
@@ -40,11 +48,22 @@ struct AjdbArgs {
 enum AjdbCommand {
     /// Add raw acts as parsed from MK. Usually created by the default invocation of hun_law
     Add(AddArgs),
+    /// Remove an act from the DB, and recalculate the states it modified
+    Remove(RemoveArgs),
+    /// Replace an act in the DB with a new version (e.g. a reparsed one), and recalculate the
+    /// states either version modified
+    Replace(ReplaceArgs),
     /// Recalculate amendments in the given date range. Be sure that the end date range is the end
     /// of the actual database, or else the db will be inconsistent.
     Recalculate(RecalculateArgs),
     /// Show a single act at a specific date
     Show(ShowArgs),
+    /// Explain the modifications of a single act at a specific date, without changing the DB
+    Explain(ExplainArgs),
+    /// Maintenance of the fixup files in data/fixups
+    Fixups(FixupsArgs),
+    /// Apply a draft act that is not in the DB and show its effects, without changing the DB
+    Simulate(SimulateArgs),
 }
 
 fn main() -> Result<()> {
@@ -57,7 +76,12 @@ fn main() -> Result<()> {
     let args = AjdbArgs::parse();
     match args.command {
         AjdbCommand::Add(a) => cli_add_raw(a),
+        AjdbCommand::Remove(a) => cli_remove(a),
+        AjdbCommand::Replace(a) => cli_replace(a),
         AjdbCommand::Recalculate(a) => cli_recalculate(a),
         AjdbCommand::Show(a) => cli_show(a),
+        AjdbCommand::Explain(a) => cli_explain(a),
+        AjdbCommand::Fixups(a) => cli_fixups(a),
+        AjdbCommand::Simulate(a) => cli_simulate(a),
     }
 }
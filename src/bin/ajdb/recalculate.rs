@@ -16,10 +16,8 @@
 // along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
 
 use ajdb::{
-    amender::{AppliableModificationSet, OnError},
-    database::{ActMetadata, ActSet},
-    persistence::Persistence,
-    util::NaiveDateRange,
+    annulment::AnnulmentSet, database::ActSet, events::EventRegistry, persistence::Persistence,
+    recalculation::recalculate_stored_date, util::NaiveDateRange,
 };
 use anyhow::{anyhow, Context, Result};
 use chrono::NaiveDate;
@@ -37,57 +35,26 @@ pub struct RecalculateArgs {
 
 pub fn cli_recalculate(args: RecalculateArgs) -> Result<()> {
     let persistence = Persistence::new("db");
-    for date in NaiveDateRange::new(args.from.succ(), args.to) {
-        recalculate_one_date(&persistence, date)
+    recalculate_range(&persistence, args.from, args.to)
+}
+
+/// Recalculate the states after `from` (exclusive), until `to` (exclusive)
+pub fn recalculate_range(persistence: &Persistence, from: NaiveDate, to: NaiveDate) -> Result<()> {
+    let annulments = AnnulmentSet::load()?;
+    let events = EventRegistry::load()?;
+    for date in NaiveDateRange::new(from.succ(), to) {
+        recalculate_stored_date(persistence, &annulments, &events, date)
             .with_context(|| anyhow!("Recalculating date {} failed", date))?;
     }
     Ok(())
 }
 
-fn recalculate_one_date(persistence: &Persistence, date: NaiveDate) -> Result<()> {
-    info!("Recalculating {}", date);
-    ActSet::copy(persistence, date.pred(), date)?;
-    let mut state = ActSet::load(persistence, date)?;
-    let mut act_ids: Vec<_> = state
-        .get_acts()?
-        .iter()
-        .filter(|ae| ae.is_date_interesting(date))
-        .map(|ae| ae.identifier())
-        .collect();
-    if act_ids.is_empty() {
-        return Ok(());
-    }
-
-    // NOTE: It's important to go in reverse, since there may be later acts
-    //       that modify earlier acts on the same enforcement day.
-    //       E.g. 2020. évi LXXIV. törvény.yml modifies 2020. évi XLIII. törvény.yml,
-    //       both with enforcement dates 2021-01-01, leading to a conflict in Btk.
-    act_ids.sort();
-    act_ids.reverse();
-
-    let mut modifications = AppliableModificationSet::default();
-    modifications.add_fixups(date)?;
-    for act_id in &act_ids {
-        // NOTE: And then there's the case where an Act is modified by one Act, and then another,
-        //       Both coming into force at the same time. This is resolved by the internal
-        //       ordering fix in modifications.apply_to_act(...)
-        modifications.apply_to_act_in_state(*act_id, date, &mut state, OnError::Warn)?;
-        modifications.remove_affecting(*act_id);
-        let act = state.get_act(*act_id)?.act()?;
-        modifications.add(&act, date)?;
-    }
-
-    let mut modified_acts = act_ids; //no clone necessary
-    modified_acts.append(&mut modifications.affected_acts());
-    for act_id in modified_acts {
-        if state.has_act(act_id) {
-            let mut act_metadata = ActMetadata::load(persistence, act_id)?;
-            act_metadata.add_modification_date(date)?;
-            act_metadata.save()?;
-        }
+/// Recalculate the states from `from` (inclusive) until the end of the
+/// database, e.g. after acts were added or removed.
+pub fn recalculate_until_end(persistence: &Persistence, from: NaiveDate) -> Result<()> {
+    if let Some(last_date) = ActSet::last_stored_date(persistence)? {
+        info!("Recalculating the states from {from} to {last_date}");
+        recalculate_range(persistence, from.pred(), last_date.succ())?;
     }
-
-    modifications.apply_rest(date, &mut state, OnError::Warn)?;
-    state.save()?;
     Ok(())
 }
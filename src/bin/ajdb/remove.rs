@@ -0,0 +1,197 @@
+// Copyright (c) 2022-2023, Alex Badics
+//
+// This file is part of AJDB
+//
+// AJDB is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// AJDB is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeSet;
+
+use ajdb::{
+    amender::AppliableModificationSet,
+    database::{ActMetadata, ActSet, CachedEnforcementDates, RetroactiveActs},
+    events::EventRegistry,
+    persistence::Persistence,
+};
+use anyhow::{bail, Result};
+use chrono::NaiveDate;
+use hun_law::{identifier::ActIdentifier, structure::Act};
+use log::{info, warn};
+
+use crate::recalculate::recalculate_until_end;
+
+#[derive(Debug, clap::Args)]
+pub struct RemoveArgs {
+    #[clap(value_parser, required = true)]
+    /// The Act to remove in Year/ISSUE format. Example: '2013/31'
+    act: ActIdentifier,
+    /// Do not recalculate the affected states. The database will be
+    /// inconsistent until it is recalculated.
+    #[clap(long)]
+    no_recalculate: bool,
+}
+
+pub fn cli_remove(args: RemoveArgs) -> Result<()> {
+    let persistence = Persistence::new("db");
+    match remove_act(&persistence, args.act)? {
+        Some(from) if args.no_recalculate => {
+            warn!("Not recalculating the affected states from {from}");
+            Ok(())
+        }
+        Some(from) => recalculate_until_end(&persistence, from),
+        None => Ok(()),
+    }
+}
+
+/// Remove the act from every state it is in, from the registry of
+/// retroactive acts and the act index, and its metadata. The modification dates of the acts it
+/// modified are removed from the first returned date on, the recalculation
+/// records the remaining ones again.
+///
+/// Returns the first date where the act may have modified other acts. The
+/// states from that date on have to be recalculated, the ones before it are
+/// already consistent.
+pub fn remove_act(persistence: &Persistence, act_id: ActIdentifier) -> Result<Option<NaiveDate>> {
+    let dates = ActSet::dates_with_act(persistence, act_id)?;
+    let publication_date = match dates.first() {
+        Some(date) => *date,
+        None => bail!("{act_id} is not in the database"),
+    };
+    // The modifications of the act are only applied on its enforcement dates,
+    // and are extracted from the version that was added.
+    let act = match ActMetadata::load(persistence, act_id)?.published_act()? {
+        Some(act) => act,
+        None => ActSet::load(persistence, publication_date)?
+            .get_act(act_id)?
+            .act()?,
+    };
+    let enforcement_dates = CachedEnforcementDates::from_act(&act)?.dates(&EventRegistry::load()?);
+    let retroactive_dates = RetroactiveActs::unregister(persistence, act_id)?;
+
+    info!("Removing {act_id} from {} states", dates.len());
+    for date in dates {
+        let mut state = ActSet::load(persistence, date)?;
+        if state.remove_act(act_id)? {
+            state.save()?;
+        }
+    }
+    let recalculate_from = enforcement_dates
+        .iter()
+        .copied()
+        .chain(retroactive_dates)
+        .min();
+    if let Some(from) = recalculate_from {
+        for modified_act in modified_acts(&act, &enforcement_dates)? {
+            let mut act_metadata = ActMetadata::load(persistence, modified_act)?;
+            act_metadata.remove_modification_dates_from(from)?;
+            act_metadata.save()?;
+        }
+    }
+    ActMetadata::remove(persistence, act_id)?;
+    let mut act_index = ActSet::act_index(persistence)?;
+    act_index.remove_act(act_id)?;
+    act_index.save()?;
+    Ok(recalculate_from)
+}
+
+/// The other acts the act modifies on the dates
+fn modified_acts(act: &Act, dates: &[NaiveDate]) -> Result<BTreeSet<ActIdentifier>> {
+    let mut modifications = AppliableModificationSet::default();
+    for date in dates {
+        modifications.add(act, *date)?;
+    }
+    Ok(modifications
+        .affected_acts()
+        .into_iter()
+        .filter(|modified_act| *modified_act != act.identifier)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use ajdb::testing::{amended_act, amending_act, pin_fixup_registry, AMENDED, AMENDING};
+    use hun_law::structure::{ActChild, SAEBody};
+
+    use super::*;
+    use crate::{add::add_act, recalculate::recalculate_range};
+
+    fn first_paragraph(persistence: &Persistence, date: NaiveDate) -> String {
+        let act = ActSet::load(persistence, date)
+            .unwrap()
+            .get_act(AMENDED)
+            .unwrap()
+            .act()
+            .unwrap();
+        match &act.children[0] {
+            ActChild::Article(article) => match &article.children[0].body {
+                SAEBody::Text(text) => text.clone(),
+                SAEBody::Children { .. } => panic!("Unexpected children"),
+            },
+            _ => panic!("Unexpected act child"),
+        }
+    }
+
+    #[test]
+    fn test_remove_act() {
+        let _lock = pin_fixup_registry(Vec::new());
+        let dir = tempfile::tempdir().unwrap();
+        let persistence = Persistence::new(dir.path());
+        add_act(&persistence, amended_act()).unwrap();
+        add_act(&persistence, amending_act()).unwrap();
+        let first_date = NaiveDate::from_ymd(2098, 1, 1);
+        let amendment_date = NaiveDate::from_ymd(2098, 2, 2);
+        let last_date = NaiveDate::from_ymd(2098, 2, 10);
+        recalculate_range(&persistence, first_date.pred(), last_date.succ()).unwrap();
+        assert_eq!(
+            first_paragraph(&persistence, last_date),
+            "A Kormány rendeletben állapítja meg."
+        );
+        let modification_dates = ActMetadata::load(&persistence, AMENDED)
+            .unwrap()
+            .modification_dates();
+        assert!(modification_dates.contains(&amendment_date));
+
+        assert_eq!(
+            remove_act(&persistence, AMENDING).unwrap(),
+            Some(amendment_date)
+        );
+        let amending_metadata = ActMetadata::load(&persistence, AMENDING).unwrap();
+        assert_eq!(amending_metadata.published_act().unwrap(), None);
+        assert!(amending_metadata.modification_dates().is_empty());
+        assert!(!ActSet::stored_acts(&persistence)
+            .unwrap()
+            .contains_key(&AMENDING));
+        assert!(ActSet::dates_with_act(&persistence, AMENDING)
+            .unwrap()
+            .is_empty());
+        assert!(!ActSet::load(&persistence, last_date)
+            .unwrap()
+            .has_act(AMENDING));
+
+        recalculate_until_end(&persistence, amendment_date).unwrap();
+        assert_eq!(
+            first_paragraph(&persistence, last_date),
+            "A miniszter rendeletben állapítja meg."
+        );
+        assert_eq!(
+            ActMetadata::load(&persistence, AMENDED)
+                .unwrap()
+                .modification_dates(),
+            modification_dates
+                .into_iter()
+                .filter(|date| *date != amendment_date)
+                .collect::<Vec<_>>()
+        );
+        assert!(remove_act(&persistence, AMENDING).is_err());
+    }
+}
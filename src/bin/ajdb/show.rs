@@ -17,7 +17,7 @@
 
 use std::io::stdout;
 
-use ajdb::{database::ActSet, persistence::Persistence};
+use ajdb::{bitemporal::KnownState, database::ActSet, persistence::Persistence};
 use anyhow::{bail, Result};
 use chrono::{NaiveDate, Utc};
 use hun_law::{
@@ -33,6 +33,10 @@ pub struct ShowArgs {
     #[clap(value_parser, long, short, default_value_t=Utc::today().naive_utc())]
     /// Get state on the specific date. Format is "2013-12-31". Defaults to today.
     date: NaiveDate,
+    #[clap(value_parser, long, short)]
+    /// Only consider acts published up to this date, i.e. show the state as it
+    /// could have been known on this date. Format is "2013-12-31".
+    known_on: Option<NaiveDate>,
     /// Output format
     #[clap(value_enum, long, short = 't', default_value_t)]
     output_format: OutputFormat,
@@ -43,11 +47,22 @@ pub struct ShowArgs {
 
 pub fn cli_show(args: ShowArgs) -> Result<()> {
     let persistence = Persistence::new("db");
-    let state = ActSet::load(&persistence, args.date)?;
-    if state.is_empty() {
-        bail!("The database is empty at date {}", args.date);
-    }
-    let act = state.get_act(args.act)?.act()?;
+    let act = if let Some(known_on) = args.known_on {
+        let state = KnownState::load(&persistence, args.date, known_on)?;
+        if state.is_empty() {
+            bail!(
+                "The database is empty at date {}, as known on {known_on}",
+                args.date
+            );
+        }
+        state.act(args.act)?
+    } else {
+        let state = ActSet::load(&persistence, args.date)?;
+        if state.is_empty() {
+            bail!("The database is empty at date {}", args.date);
+        }
+        state.get_act(args.act)?.act()?
+    };
     act.cli_output(args.width, args.output_format, &mut stdout())?;
     Ok(())
 }
@@ -0,0 +1,90 @@
+// Copyright (c) 2022-2023, Alex Badics
+//
+// This file is part of AJDB
+//
+// AJDB is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// AJDB is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::path::PathBuf;
+
+use ajdb::{
+    database::ActSet,
+    persistence::Persistence,
+    simulation::{first_enforcement_date, load_draft, Simulation},
+};
+use anyhow::{bail, Result};
+use chrono::NaiveDate;
+
+use crate::explain::{act_to_text, describe_origin, print_text_diff};
+
+#[derive(Debug, clap::Args)]
+pub struct SimulateArgs {
+    #[clap(value_parser, required = true)]
+    /// The draft act in the same YAML format as the acts added with 'ajdb add'
+    path: PathBuf,
+    /// The date to simulate. Format is "2013-12-31". Defaults to the first
+    /// enforcement date of the draft.
+    #[clap(long, short)]
+    date: Option<NaiveDate>,
+    /// Width of the word-wrapped text in the before/after diffs
+    #[clap(long, short, default_value = "105")]
+    width: usize,
+}
+
+/// Apply a draft act that is not in the database to the stored state of a
+/// date, and print the resulting changes. Nothing is written to the database.
+pub fn cli_simulate(args: SimulateArgs) -> Result<()> {
+    let draft = load_draft(&args.path)?;
+    let date = match args.date {
+        Some(date) => date,
+        None => first_enforcement_date(&draft)?,
+    };
+    let persistence = Persistence::new("db");
+    let state = ActSet::load(&persistence, date)?;
+    if state.is_empty() {
+        bail!("The database is empty at date {date}");
+    }
+    let simulation = Simulation::run(&state, &draft, date)?;
+
+    println!(
+        "Simulating {} on {date}: {} acts affected",
+        simulation.draft_id,
+        simulation.acts.len() + simulation.missing_acts.len()
+    );
+    for act_id in &simulation.missing_acts {
+        println!();
+        println!("=== {act_id}: NOT IN THE DATABASE ===");
+    }
+    for (act_id, simulated) in &simulation.acts {
+        println!();
+        println!(
+            "=== {act_id}: {} modifications ===",
+            simulated.modifications.len()
+        );
+        for (modification, error) in &simulated.failures {
+            println!("FAILED: {}", describe_origin(modification));
+            for line in error.lines() {
+                println!("    {line}");
+            }
+        }
+        let text_before = act_to_text(&simulated.before, args.width);
+        let text_after = act_to_text(&simulated.after, args.width);
+        if !print_text_diff(&text_before, &text_after) {
+            println!("The text of the act did not change");
+        }
+    }
+    if simulation.has_failures() {
+        bail!("Some modifications of the draft could not be applied");
+    }
+    Ok(())
+}
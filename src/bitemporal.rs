@@ -0,0 +1,305 @@
+// Copyright (c) 2022-2023, Alex Badics
+//
+// This file is part of AJDB
+//
+// AJDB is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// AJDB is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Bitemporal queries: the state of the acts on a date, as it could have been
+//! known on an earlier (or later) date.
+//!
+//! The stored states contain everything that is known today, e.g. later
+//! amendments and annulments with retroactive effect, or the modifications of
+//! acts that were published after the date of interest. A `KnownState` only considers the
+//! acts published (and annulments in effect) up to the `known_on` date.
+//!
+//! It is calculated by taking the latest stored state that was not affected by
+//! anything published later, and replaying the amendments from there in
+//! memory, with the same routine `ajdb recalculate` uses (see
+//! `recalculation`). Acts published during the replay are taken as they were
+//! published, not from the stored states. Nothing is persisted.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use hun_law::{identifier::ActIdentifier, structure::Act};
+use log::{debug, info, warn};
+
+use crate::{
+    amender::{
+        change_history::ChangeHistory, repeal::get_act_repeal, AppliableModificationSet, OnError,
+    },
+    annulment::AnnulmentSet,
+    database::{ActEntry, ActMetadata, ActSet, CachedEnforcementDates, RetroactiveActs},
+    enforcement_date_set::EnforcementDateSet,
+    events::EventRegistry,
+    persistence::Persistence,
+    recalculation::{recalculate_date, RecalculatedState},
+    util::NaiveDateRange,
+};
+
+/// The state of all acts at `date`, as known on `known_on`.
+pub struct KnownState<'p> {
+    date: NaiveDate,
+    known_on: NaiveDate,
+    base_date: NaiveDate,
+    base: ActSet<'p>,
+    /// Acts that differ from the ones in `base`
+    overlay: BTreeMap<ActIdentifier, RecalculatedAct>,
+}
+
+struct RecalculatedAct {
+    act: Act,
+    change_history: ChangeHistory,
+    enforcement_dates: CachedEnforcementDates,
+}
+
+impl RecalculatedAct {
+    fn new(act: Act, change_history: ChangeHistory) -> Result<Self> {
+        let enforcement_dates = CachedEnforcementDates::from_act(&act)?;
+        Ok(Self {
+            act,
+            change_history,
+            enforcement_dates,
+        })
+    }
+}
+
+impl<'p> KnownState<'p> {
+    pub fn load(
+        persistence: &'p Persistence,
+        date: NaiveDate,
+        known_on: NaiveDate,
+    ) -> Result<Self> {
+        Self::load_with_annulments(persistence, date, known_on, &AnnulmentSet::load()?)
+    }
+
+    pub fn load_with_annulments(
+        persistence: &'p Persistence,
+        date: NaiveDate,
+        known_on: NaiveDate,
+        annulments: &AnnulmentSet,
+    ) -> Result<Self> {
+        // Stored states up to known_on only contain acts published up to known_on,
+        // but retroactive amendments and annulments may have changed even those.
+        let mut base_date = date.min(known_on);
+        if let Some(first_unknown) = annulments.first_application_date_after(known_on) {
+            base_date = base_date.min(first_unknown.pred());
+        }
+        if let Some(first_unknown) =
+            RetroactiveActs::first_date_published_after(persistence, known_on)?
+        {
+            base_date = base_date.min(first_unknown.pred());
+        }
+        let mut result = Self {
+            date,
+            known_on,
+            base_date,
+            base: ActSet::load(persistence, base_date)?,
+            overlay: BTreeMap::new(),
+        };
+        if base_date == date {
+            return Ok(result);
+        }
+        info!("Recalculating {date} as known on {known_on}, starting from {base_date}");
+        let published = result.acts_published_after_base(persistence, date.min(known_on))?;
+        let annulments = annulments.known_on(known_on);
+        let events = EventRegistry::load()?;
+        for day in NaiveDateRange::new(base_date.succ(), date.succ()) {
+            for act_id in published.get(&day).into_iter().flatten() {
+                let act = Self::published_act(persistence, *act_id, day)?;
+                result.overlay.insert(
+                    *act_id,
+                    RecalculatedAct::new(act, ChangeHistory::default())?,
+                );
+            }
+            debug!("Recalculating {day} in memory");
+            recalculate_date(
+                &mut result,
+                persistence,
+                day,
+                &annulments,
+                &events,
+                Some(known_on),
+            )?;
+        }
+        Ok(result)
+    }
+
+    /// The act as it was published on `day`, before anything was applied to it.
+    fn published_act(
+        persistence: &Persistence,
+        act_id: ActIdentifier,
+        day: NaiveDate,
+    ) -> Result<Act> {
+        if let Some(act) = ActMetadata::load(persistence, act_id)?.published_act()? {
+            return Ok(act);
+        }
+        // The stored version may already contain changes published after
+        // known_on (e.g. retroactive amendments of the act itself).
+        warn!("The published version of {act_id} is not stored, using the state of {day}");
+        let entry = ActSet::load(persistence, day)?.get_act(act_id)?;
+        entry.act()
+    }
+
+    /// The acts published after the base state, up to `last_date`, keyed by
+    /// their publication date.
+    fn acts_published_after_base(
+        &self,
+        persistence: &Persistence,
+        last_date: NaiveDate,
+    ) -> Result<BTreeMap<NaiveDate, BTreeSet<ActIdentifier>>> {
+        let mut result: BTreeMap<_, BTreeSet<_>> = BTreeMap::new();
+        for (act_id, publication_date) in ActSet::stored_acts(persistence)? {
+            if publication_date <= last_date && !self.base.has_act(act_id) {
+                result.entry(publication_date).or_default().insert(act_id);
+            }
+        }
+        Ok(result)
+    }
+
+    fn apply_modifications_impl(
+        &mut self,
+        act_id: ActIdentifier,
+        date: NaiveDate,
+        modifications: &AppliableModificationSet,
+    ) -> Result<()> {
+        let modifications = modifications.get_modifications_of(act_id);
+        if modifications.is_empty() {
+            return Ok(());
+        }
+        let mut act = self.act(act_id)?;
+        let mut change_history = self.change_history(act_id)?;
+        // Needed to detect changes of provisions that have not come into force yet
+        let enforcement_dates = if act.children.is_empty() || get_act_repeal(&act).is_some() {
+            None
+        } else {
+            Some(EnforcementDateSet::from_act(&act)?)
+        };
+        AppliableModificationSet::apply_to_act_with_history(
+            &mut act,
+            date,
+            modifications,
+            OnError::Warn,
+            &mut change_history,
+            enforcement_dates.as_ref(),
+        )?;
+        self.overlay
+            .insert(act_id, RecalculatedAct::new(act, change_history)?);
+        Ok(())
+    }
+
+    fn is_date_interesting_impl(
+        &self,
+        act_id: ActIdentifier,
+        date: NaiveDate,
+        events: &EventRegistry,
+    ) -> Result<bool> {
+        if let Some(recalculated) = self.overlay.get(&act_id) {
+            Ok(recalculated
+                .enforcement_dates
+                .is_date_interesting(date, events))
+        } else {
+            Ok(self.base.get_act(act_id)?.is_date_interesting(date, events))
+        }
+    }
+
+    pub fn date(&self) -> NaiveDate {
+        self.date
+    }
+
+    pub fn known_on(&self) -> NaiveDate {
+        self.known_on
+    }
+
+    /// True if the stored state could not be used as is, because it may
+    /// have been changed by something published after `known_on`.
+    pub fn is_recalculated(&self) -> bool {
+        self.base_date != self.date
+    }
+
+    pub fn has_act(&self, id: ActIdentifier) -> bool {
+        self.overlay.contains_key(&id) || self.base.has_act(id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.overlay.is_empty() && self.base.is_empty()
+    }
+
+    pub fn act_ids(&self) -> Result<Vec<ActIdentifier>> {
+        let mut result: BTreeSet<_> = self.overlay.keys().copied().collect();
+        for entry in self.base.get_acts()? {
+            result.insert(entry.identifier());
+        }
+        Ok(result.into_iter().collect())
+    }
+
+    pub fn act(&self, id: ActIdentifier) -> Result<Act> {
+        match self.overlay.get(&id) {
+            Some(recalculated) => Ok(recalculated.act.clone()),
+            None => self.base_entry(id)?.act(),
+        }
+    }
+
+    pub fn change_history(&self, id: ActIdentifier) -> Result<ChangeHistory> {
+        match self.overlay.get(&id) {
+            Some(recalculated) => Ok(recalculated.change_history.clone()),
+            None => self.base_entry(id)?.change_history(),
+        }
+    }
+
+    fn base_entry(&self, id: ActIdentifier) -> Result<ActEntry> {
+        if self.base.has_act(id) {
+            self.base.get_act(id)
+        } else {
+            Err(anyhow!(
+                "Could not find act {id} in the database at date {}, as known on {}",
+                self.date,
+                self.known_on
+            ))
+        }
+    }
+}
+
+impl RecalculatedState for KnownState<'_> {
+    fn act_ids(&self) -> Result<Vec<ActIdentifier>> {
+        KnownState::act_ids(self)
+    }
+
+    fn has_act(&self, id: ActIdentifier) -> bool {
+        KnownState::has_act(self, id)
+    }
+
+    fn is_date_interesting(
+        &self,
+        id: ActIdentifier,
+        date: NaiveDate,
+        events: &EventRegistry,
+    ) -> Result<bool> {
+        self.is_date_interesting_impl(id, date, events)
+    }
+
+    fn act(&self, id: ActIdentifier) -> Result<Act> {
+        KnownState::act(self, id)
+    }
+
+    fn apply_modifications(
+        &mut self,
+        id: ActIdentifier,
+        date: NaiveDate,
+        modifications: &AppliableModificationSet,
+    ) -> Result<()> {
+        self.apply_modifications_impl(id, date, modifications)
+    }
+}
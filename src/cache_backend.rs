@@ -113,6 +113,10 @@ impl<K: Hash + Eq, T: Clone> CacheBackend<K, T> {
             .put(k, Arc::new(OnceCell::new_with(Some(v))));
     }
 
+    pub fn remove(&self, k: &K) {
+        self.data.lock().expect("Cache lock was poisoned").pop(k);
+    }
+
     // TODO: Synchronous get() and set()
 }
 
@@ -25,14 +25,70 @@ use std::{
 
 use anyhow::{anyhow, Context, Result};
 use chrono::NaiveDate;
-use hun_law::{identifier::ActIdentifier, structure::Act};
+use hun_law::{
+    identifier::ActIdentifier,
+    structure::{Act, LastChange},
+};
+use log::info;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    enforcement_date_set::EnforcementDateSet,
+    amender::{change_history::ChangeHistory, repeal::get_act_repeal},
+    enforcement_date_set::{EnforcementDateSet, EnforcementDateWarning},
+    events::EventRegistry,
     persistence::{KeyType, Persistence, PersistenceKey},
 };
 
+/// The enforcement dates and warnings that are cached in the act entries, so
+/// that the acts do not have to be loaded to decide whether they have to be
+/// processed on a date.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CachedEnforcementDates {
+    /// Empty for repealed acts, which have nothing left to come into force.
+    enforcement_dates: Vec<NaiveDate>,
+    /// The events some enforcement dates depend on. The dates of these are
+    /// looked up in the current `EventRegistry` instead, so that changes to
+    /// `events.yml` are picked up by the next recalculation.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    enforcement_events: Vec<String>,
+    /// Inconsistencies in the enforcement dates, where a best guess was used.
+    #[serde(default)]
+    enforcement_date_warnings: Vec<EnforcementDateWarning>,
+}
+
+impl CachedEnforcementDates {
+    pub fn from_act(act: &Act) -> Result<Self> {
+        if act.children.is_empty() || get_act_repeal(act).is_some() {
+            Ok(Self::default())
+        } else {
+            let ed_set = EnforcementDateSet::from_act(act)?;
+            Ok(Self {
+                enforcement_dates: ed_set.get_all_dates(),
+                enforcement_events: ed_set.events().to_vec(),
+                enforcement_date_warnings: ed_set.warnings().to_vec(),
+            })
+        }
+    }
+
+    /// All dates something comes into force, with the current dates of the
+    /// events.
+    pub fn dates(&self, events: &EventRegistry) -> Vec<NaiveDate> {
+        let mut result = self.enforcement_dates.clone();
+        result.extend(
+            self.enforcement_events
+                .iter()
+                .filter_map(|id| events.get(id).ok()?.date),
+        );
+        result
+    }
+
+    /// Returns true if anything comes into force on the date or the day before it.
+    pub fn is_date_interesting(&self, date: NaiveDate, events: &EventRegistry) -> bool {
+        let dates = self.dates(events);
+        dates.contains(&date) || dates.contains(&date.pred())
+    }
+}
+
 /// The actual data that's stored for the act set.
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct ActSetSerialized {
@@ -122,27 +178,134 @@ impl<'p> ActSet<'p> {
     /// Converts Act to ActEntry, calculating all kinds of cached data,
     /// and storing it as a blob. Keep in mind that the ActSet
     /// object itself should be saved, or else the act will dangle.
+    ///
+    /// The change history of the act (if it was already in the set) is kept.
     pub fn store_act(&mut self, act: Act) -> Result<ActEntry> {
+        let change_history_key = self
+            .data
+            .acts
+            .get(&Self::act_key(act.identifier))
+            .and_then(|entry| entry.change_history_key.clone());
+        self.store_act_impl(act, change_history_key)
+    }
+
+    /// Same as `store_act`, but also stores a new version of the change history.
+    pub fn store_act_with_history(
+        &mut self,
+        act: Act,
+        change_history: &ChangeHistory,
+    ) -> Result<ActEntry> {
+        let change_history_key = self
+            .persistence
+            .store(KeyType::Calculated("change_history"), change_history)?;
+        self.store_act_impl(act, Some(change_history_key))
+    }
+
+    fn store_act_impl(
+        &mut self,
+        act: Act,
+        change_history_key: Option<PersistenceKey>,
+    ) -> Result<ActEntry> {
         let act_key = self.persistence.store(KeyType::Calculated("act"), &act)?;
-        let enforcement_dates = if act.children.is_empty() {
-            Vec::new()
-        } else {
-            EnforcementDateSet::from_act(&act)?.get_all_dates()
-        };
+        let repeal = get_act_repeal(&act).cloned();
+        let enforcement_dates = CachedEnforcementDates::from_act(&act)?;
         self.data_mut()?.acts.insert(
             Self::act_key(act.identifier),
             ActEntrySerialized {
                 act_key,
                 enforcement_dates,
+                repeal,
+                change_history_key,
             },
         );
         self.get_act(act.identifier)
     }
 
+    /// Remove the act from the set. Returns false if it was not in the set.
+    pub fn remove_act(&mut self, id: ActIdentifier) -> Result<bool> {
+        Ok(self.data_mut()?.acts.remove(&Self::act_key(id)).is_some())
+    }
+
     pub fn is_empty(&self) -> bool {
         self.data.acts.is_empty()
     }
 
+    /// Add a new act to the state of its publication date, and record it in
+    /// the act index and its metadata. Returns the retroactive dates of the
+    /// act, which should be recalculated.
+    pub fn add_new_act(persistence: &'p Persistence, act: Act) -> Result<Vec<NaiveDate>> {
+        let date = act.publication_date;
+        let retroactive_dates = RetroactiveActs::register(persistence, &act)?;
+        let mut act_metadata = ActMetadata::load(persistence, act.identifier)?;
+        act_metadata.set_published_act(&act)?;
+        act_metadata.save()?;
+        let mut act_index = Self::act_index(persistence)?;
+        act_index.add_act(act.identifier, date)?;
+        act_index.save()?;
+        let mut state = Self::load(persistence, date)?;
+        state.store_act(act)?;
+        state.save()?;
+        Ok(retroactive_dates)
+    }
+
+    /// The dates of the stored states that contain the act, not counting the
+    /// ones that are only links to an earlier state. The first one is the
+    /// publication date of the act.
+    pub fn dates_with_act(persistence: &Persistence, id: ActIdentifier) -> Result<Vec<NaiveDate>> {
+        let publication_date = match Self::stored_acts(persistence)?.get(&id) {
+            Some(date) => *date,
+            None => return Ok(Vec::new()),
+        };
+        // Acts are kept in all later states by `copy`
+        let mut result = Vec::new();
+        for key in persistence.list_keys("state")? {
+            let date = NaiveDate::parse_from_str(&key, "state/%Y/%m/%d")?;
+            if date >= publication_date && !persistence.is_link(&key)? {
+                result.push(date);
+            }
+        }
+        Ok(result)
+    }
+
+    /// All acts in the database, with their publication dates (i.e. the
+    /// first date of the stored states they are in).
+    pub fn stored_acts(persistence: &Persistence) -> Result<BTreeMap<ActIdentifier, NaiveDate>> {
+        Self::act_index(persistence)?.acts()
+    }
+
+    /// The index of the acts in the database. It is built from the stored
+    /// states if the database was created without one.
+    pub fn act_index(persistence: &Persistence) -> Result<ActIndex> {
+        if persistence.exists(&ActIndexSpecifics::persistence_key(ActIndexKey))? {
+            return ActIndex::load(persistence, ActIndexKey);
+        }
+        info!("Building the act index");
+        let mut act_index = ActIndex::load(persistence, ActIndexKey)?;
+        let mut acts = BTreeMap::new();
+        for key in persistence.list_keys("state")? {
+            if persistence.is_link(&key)? {
+                continue;
+            }
+            let date = NaiveDate::parse_from_str(&key, "state/%Y/%m/%d")?;
+            for entry in ActSet::load(persistence, date)?.get_acts()? {
+                acts.entry(entry.identifier()).or_insert(date);
+            }
+        }
+        for (id, publication_date) in acts {
+            act_index.add_act(id, publication_date)?;
+        }
+        act_index.save()?;
+        ActIndex::load(persistence, ActIndexKey)
+    }
+
+    /// The last date with a stored state, i.e. the end of the database.
+    pub fn last_stored_date(persistence: &Persistence) -> Result<Option<NaiveDate>> {
+        Ok(persistence
+            .last_key("state")?
+            .map(|key| NaiveDate::parse_from_str(&key, "state/%Y/%m/%d"))
+            .transpose()?)
+    }
+
     fn act_key(id: ActIdentifier) -> String {
         format!("{}/{}", id.year, id.number)
     }
@@ -156,7 +319,14 @@ pub struct ActEntrySerialized {
     act_key: PersistenceKey,
     /// Cached enforcement dates so that we don't load the act all the time for
     /// the amendment processing.
-    enforcement_dates: Vec<NaiveDate>,
+    #[serde(flatten)]
+    enforcement_dates: CachedEnforcementDates,
+    /// Date and cause of the repeal, if the act was repealed as a whole.
+    #[serde(default)]
+    repeal: Option<LastChange>,
+    /// The storage key of the change history of the act, if it was ever amended.
+    #[serde(default)]
+    change_history_key: Option<PersistenceKey>,
     // TODO: Incoming refs in separate structure
 }
 
@@ -178,23 +348,53 @@ impl<'a> ActEntry<'a> {
         self.persistence.load_async(&self.data.act_key)
     }
 
+    /// Load the full change history of the act's elements from persistence.
+    pub fn change_history(&self) -> Result<ChangeHistory> {
+        match &self.data.change_history_key {
+            Some(key) => self.persistence.load(key),
+            None => Ok(Default::default()),
+        }
+    }
+
+    pub async fn change_history_cached(&self) -> Result<Arc<ChangeHistory>> {
+        match &self.data.change_history_key {
+            Some(key) => self.persistence.load_async(key).await,
+            None => Ok(Default::default()),
+        }
+    }
+
     // TODO: partial loads for snippet support
 
     /// Returns true if anything comes into force on the date or the day before it.
-    pub fn is_date_interesting(&self, date: NaiveDate) -> bool {
-        self.data.enforcement_dates.contains(&date)
-            || self.data.enforcement_dates.contains(&date.pred())
+    pub fn is_date_interesting(&self, date: NaiveDate, events: &EventRegistry) -> bool {
+        self.data
+            .enforcement_dates
+            .is_date_interesting(date, events)
     }
 
     pub fn identifier(&self) -> ActIdentifier {
         self.identifier
     }
+
+    /// Date and cause of the repeal, if the act is not in force anymore.
+    pub fn repeal(&self) -> Option<&LastChange> {
+        self.data.repeal.as_ref()
+    }
+
+    /// Problems found while calculating the enforcement dates of the act.
+    pub fn enforcement_date_warnings(&self) -> &[EnforcementDateWarning] {
+        &self.data.enforcement_dates.enforcement_date_warnings
+    }
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ActMetadataSerialized {
     /// Contains both modifiactions by others, and enforcement dates
     modification_dates: BTreeSet<NaiveDate>,
+    /// The storage key of the act as it was added, before any modifications
+    /// were applied to it. Replays of its publication date start from this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    published_act_key: Option<PersistenceKey>,
 }
 
 pub type ActMetadata<'p> = DirectObjectHandle<'p, ActMetadataSpecifics>;
@@ -219,6 +419,182 @@ impl<'p> ActMetadata<'p> {
     pub fn modification_dates(&self) -> Vec<NaiveDate> {
         self.data.modification_dates.iter().copied().collect()
     }
+
+    /// Forget the modification dates from `date` on, e.g. before recalculating
+    /// the states without an act that modified this one.
+    pub fn remove_modification_dates_from(&mut self, date: NaiveDate) -> Result<()> {
+        self.data_mut()?.modification_dates.split_off(&date);
+        Ok(())
+    }
+
+    /// Store the act as it was published, i.e. before the modifications of its
+    /// publication date were applied to it.
+    pub fn set_published_act(&mut self, act: &Act) -> Result<()> {
+        let act_key = self.persistence.store(KeyType::Calculated("act"), act)?;
+        self.data_mut()?.published_act_key = Some(act_key);
+        Ok(())
+    }
+
+    /// The act as it was published. None for acts added before the published
+    /// versions were recorded.
+    pub fn published_act(&self) -> Result<Option<Act>> {
+        match &self.data.published_act_key {
+            Some(key) => Ok(Some(self.persistence.load(key)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ActIndexSerialized {
+    /// Publication dates, keyed by act
+    acts: BTreeMap<String, NaiveDate>,
+}
+
+/// All acts in the database, so that the states do not have to be scanned
+/// to find them. Use `ActSet::act_index` to load it.
+pub type ActIndex<'p> = DirectObjectHandle<'p, ActIndexSpecifics>;
+
+/// There is only one act index
+#[derive(Debug, Clone, Copy)]
+pub struct ActIndexKey;
+
+impl Display for ActIndexKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "act_index")
+    }
+}
+
+pub struct ActIndexSpecifics;
+
+impl DirectObjectSpecifics for ActIndexSpecifics {
+    type Key = ActIndexKey;
+    type Data = ActIndexSerialized;
+
+    fn persistence_key(key: Self::Key) -> PersistenceKey {
+        key.to_string()
+    }
+}
+
+impl<'p> ActIndex<'p> {
+    pub fn add_act(&mut self, id: ActIdentifier, publication_date: NaiveDate) -> Result<()> {
+        self.data_mut()?
+            .acts
+            .insert(ActSet::act_key(id), publication_date);
+        Ok(())
+    }
+
+    /// Remove the act from the index. Returns false if it was not in it.
+    pub fn remove_act(&mut self, id: ActIdentifier) -> Result<bool> {
+        Ok(self.data_mut()?.acts.remove(&ActSet::act_key(id)).is_some())
+    }
+
+    /// The acts and their publication dates
+    pub fn acts(&self) -> Result<BTreeMap<ActIdentifier, NaiveDate>> {
+        self.data
+            .acts
+            .iter()
+            .map(|(act_id, publication_date)| Ok((act_id.parse()?, *publication_date)))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetroactiveActsSerialized {
+    /// Publication dates, keyed by act
+    acts: BTreeMap<String, NaiveDate>,
+}
+
+/// Acts that have something coming into force on a specific date, but were
+/// published only later (i.e. they have retroactive effect).
+///
+/// These acts are not in the state of the date, so their modifications have
+/// to be collected separately during recalculation.
+pub type RetroactiveActs<'p> = DirectObjectHandle<'p, RetroactiveActsSpecifics>;
+
+pub struct RetroactiveActsSpecifics;
+
+impl DirectObjectSpecifics for RetroactiveActsSpecifics {
+    type Key = NaiveDate;
+    type Data = RetroactiveActsSerialized;
+
+    fn persistence_key(key: Self::Key) -> PersistenceKey {
+        key.format("retroactive/%Y/%m/%d").to_string()
+    }
+}
+
+impl<'p> RetroactiveActs<'p> {
+    pub fn add_act(&mut self, id: ActIdentifier, publication_date: NaiveDate) -> Result<()> {
+        self.data_mut()?
+            .acts
+            .insert(ActSet::act_key(id), publication_date);
+        Ok(())
+    }
+
+    /// Remove the act from the set. Returns false if it was not in the set.
+    pub fn remove_act(&mut self, id: ActIdentifier) -> Result<bool> {
+        Ok(self.data_mut()?.acts.remove(&ActSet::act_key(id)).is_some())
+    }
+
+    /// The acts and their publication dates
+    pub fn acts(&self) -> Result<Vec<(ActIdentifier, NaiveDate)>> {
+        self.data
+            .acts
+            .iter()
+            .map(|(act_id, publication_date)| Ok((act_id.parse()?, *publication_date)))
+            .collect()
+    }
+
+    /// Register the retroactive dates of `act`. Returns the dates, which
+    /// should be recalculated.
+    pub fn register(persistence: &'p Persistence, act: &Act) -> Result<Vec<NaiveDate>> {
+        if act.children.is_empty() || get_act_repeal(act).is_some() {
+            return Ok(Vec::new());
+        }
+        let dates = EnforcementDateSet::from_act(act)?.retroactive_dates(act.publication_date);
+        for date in &dates {
+            let mut retroactive_acts = Self::load(persistence, *date)?;
+            retroactive_acts.add_act(act.identifier, act.publication_date)?;
+            retroactive_acts.save()?;
+        }
+        Ok(dates)
+    }
+
+    /// Remove the act from the registry of every date. Returns the dates it
+    /// was registered on.
+    pub fn unregister(persistence: &'p Persistence, id: ActIdentifier) -> Result<Vec<NaiveDate>> {
+        let mut result = Vec::new();
+        for key in persistence.list_keys("retroactive")? {
+            let date = NaiveDate::parse_from_str(&key, "retroactive/%Y/%m/%d")?;
+            let mut retroactive_acts = Self::load(persistence, date)?;
+            if retroactive_acts.remove_act(id)? {
+                retroactive_acts.save()?;
+                result.push(date);
+            }
+        }
+        Ok(result)
+    }
+
+    /// The earliest retroactive date of the acts published after `known_on`
+    pub fn first_date_published_after(
+        persistence: &Persistence,
+        known_on: NaiveDate,
+    ) -> Result<Option<NaiveDate>> {
+        for key in persistence.list_keys("retroactive")? {
+            let date = NaiveDate::parse_from_str(&key, "retroactive/%Y/%m/%d")?;
+            let retroactive_acts = RetroactiveActs::load(persistence, date)?;
+            if retroactive_acts
+                .data
+                .acts
+                .values()
+                .any(|publication_date| *publication_date > known_on)
+            {
+                // Keys are sorted by date
+                return Ok(Some(date));
+            }
+        }
+        Ok(None)
+    }
 }
 
 pub trait DirectObjectSpecifics {
@@ -282,7 +658,203 @@ impl<'p, S: DirectObjectSpecifics> DirectObjectHandle<'p, S> {
         Ok(())
     }
 
+    pub fn persistence(&self) -> &'p Persistence {
+        self.persistence
+    }
+
+    /// Remove the stored object. Loading it afterwards returns the default.
+    pub fn remove(persistence: &'p Persistence, key: S::Key) -> Result<()> {
+        persistence.remove(&S::persistence_key(key))
+    }
+
     fn data_mut(&mut self) -> Result<&mut S::Data> {
         Arc::get_mut(&mut self.data).ok_or_else(|| anyhow!("Concurrent write access to Database"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use hun_law::util::singleton_yaml;
+
+    use super::*;
+    use crate::testing::pin_fixup_registry;
+
+    #[test]
+    fn test_event_dates_are_not_cached() {
+        let cached = CachedEnforcementDates {
+            enforcement_dates: vec![NaiveDate::from_ymd(2013, 1, 1)],
+            enforcement_events: vec!["accession".into()],
+            enforcement_date_warnings: Vec::new(),
+        };
+        let undetermined: EventRegistry = singleton_yaml::from_str(
+            r#"
+            - id: accession
+              name: Csatlakozás
+            "#,
+        )
+        .unwrap();
+        let determined: EventRegistry = singleton_yaml::from_str(
+            r#"
+            - id: accession
+              name: Csatlakozás
+              date: 2014-05-01
+            "#,
+        )
+        .unwrap();
+        let event_date = NaiveDate::from_ymd(2014, 5, 1);
+        assert!(cached.is_date_interesting(NaiveDate::from_ymd(2013, 1, 1), &undetermined));
+        assert!(!cached.is_date_interesting(event_date, &undetermined));
+        assert!(cached.is_date_interesting(event_date, &determined));
+        assert!(cached.is_date_interesting(event_date.succ(), &determined));
+
+        // Stored states without the events field are still readable
+        let old_format: ActEntrySerialized =
+            serde_json::from_str(r#"{"act_key": "act/1234", "enforcement_dates": ["2013-01-01"]}"#)
+                .unwrap();
+        assert!(old_format.enforcement_dates.enforcement_events.is_empty());
+    }
+
+    fn test_act(number: u16, publication_date: &str) -> Act {
+        singleton_yaml::from_str(&format!(
+            r#"
+            identifier:
+              year: 2098
+              number: {number}
+            subject: Az AJDB teszteléséről
+            preamble: ''
+            publication_date: {publication_date}
+            children: []
+            "#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_stored_dates() {
+        let _lock = pin_fixup_registry(Vec::new());
+        let dir = tempfile::tempdir().unwrap();
+        let persistence = Persistence::new(dir.path());
+        let first = NaiveDate::from_ymd(2098, 1, 1);
+        let second = NaiveDate::from_ymd(2098, 1, 2);
+        let third = NaiveDate::from_ymd(2098, 1, 3);
+        assert_eq!(ActSet::last_stored_date(&persistence).unwrap(), None);
+        for act in [test_act(1, "2098-01-01"), test_act(2, "2098-01-02")] {
+            ActSet::add_new_act(&persistence, act).unwrap();
+        }
+
+        // The second state was stored in this process, but it is not a link
+        ActSet::copy(&persistence, first, second).unwrap();
+        let state = ActSet::load(&persistence, second).unwrap();
+        assert_eq!(state.get_acts().unwrap().len(), 2);
+        ActSet::copy(&persistence, second, third).unwrap();
+        assert!(ActSet::load(&persistence, third)
+            .unwrap()
+            .has_act(ActIdentifier {
+                year: 2098,
+                number: 2
+            }));
+
+        assert_eq!(ActSet::last_stored_date(&persistence).unwrap(), Some(third));
+        let first_act = ActIdentifier {
+            year: 2098,
+            number: 1,
+        };
+        // The third state is a link to the second one
+        assert_eq!(
+            ActSet::dates_with_act(&persistence, first_act).unwrap(),
+            vec![first, second]
+        );
+        assert_eq!(
+            ActSet::stored_acts(&persistence).unwrap(),
+            [
+                (first_act, first),
+                (
+                    ActIdentifier {
+                        year: 2098,
+                        number: 2
+                    },
+                    second
+                )
+            ]
+            .into_iter()
+            .collect()
+        );
+    }
+
+    #[test]
+    fn test_act_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let persistence = Persistence::new(dir.path());
+        let act = test_act(1, "2098-01-01");
+        let mut act_metadata = ActMetadata::load(&persistence, act.identifier).unwrap();
+        act_metadata.set_published_act(&act).unwrap();
+        for day in [1, 5, 10] {
+            act_metadata
+                .add_modification_date(NaiveDate::from_ymd(2098, 2, day))
+                .unwrap();
+        }
+        act_metadata
+            .remove_modification_dates_from(NaiveDate::from_ymd(2098, 2, 5))
+            .unwrap();
+        act_metadata.save().unwrap();
+
+        let act_metadata = ActMetadata::load(&persistence, act.identifier).unwrap();
+        assert_eq!(
+            act_metadata.modification_dates(),
+            vec![NaiveDate::from_ymd(2098, 2, 1)]
+        );
+        assert_eq!(act_metadata.published_act().unwrap(), Some(act.clone()));
+
+        ActMetadata::remove(&persistence, act.identifier).unwrap();
+        let act_metadata = ActMetadata::load(&persistence, act.identifier).unwrap();
+        assert!(act_metadata.modification_dates().is_empty());
+        assert_eq!(act_metadata.published_act().unwrap(), None);
+    }
+
+    #[test]
+    fn test_act_index() {
+        let _lock = pin_fixup_registry(Vec::new());
+        let dir = tempfile::tempdir().unwrap();
+        let persistence = Persistence::new(dir.path());
+        let first = NaiveDate::from_ymd(2098, 1, 1);
+        let second = NaiveDate::from_ymd(2098, 1, 2);
+        let first_act = test_act(1, "2098-01-01");
+        let first_id = first_act.identifier;
+        // A database from before the index
+        let mut state = ActSet::load(&persistence, first).unwrap();
+        state.store_act(first_act).unwrap();
+        state.save().unwrap();
+        ActSet::copy(&persistence, first, second).unwrap();
+        let index_key = ActIndexSpecifics::persistence_key(ActIndexKey);
+        assert!(!persistence.exists(&index_key).unwrap());
+        assert_eq!(
+            ActSet::stored_acts(&persistence).unwrap(),
+            [(first_id, first)].into_iter().collect()
+        );
+        assert!(persistence.exists(&index_key).unwrap());
+
+        // The index is used instead of the states from now on
+        let second_act = test_act(2, "2098-01-02");
+        let second_id = second_act.identifier;
+        ActSet::add_new_act(&persistence, second_act).unwrap();
+        let mut state = ActSet::load(&persistence, first).unwrap();
+        state.remove_act(first_id).unwrap();
+        state.save().unwrap();
+        let reloaded = Persistence::new(dir.path());
+        assert_eq!(
+            ActSet::stored_acts(&reloaded).unwrap(),
+            [(first_id, first), (second_id, second)]
+                .into_iter()
+                .collect()
+        );
+
+        let mut act_index = ActSet::act_index(&reloaded).unwrap();
+        assert!(act_index.remove_act(first_id).unwrap());
+        assert!(!act_index.remove_act(first_id).unwrap());
+        act_index.save().unwrap();
+        assert_eq!(
+            ActSet::stored_acts(&reloaded).unwrap(),
+            [(second_id, second)].into_iter().collect()
+        );
+    }
+}
@@ -15,7 +15,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
 
-use anyhow::{anyhow, ensure, Result};
+use anyhow::{anyhow, bail, ensure, Result};
 use chrono::{Datelike, NaiveDate};
 use hun_law::{
     identifier::{
@@ -27,14 +27,120 @@ use hun_law::{
     structure::{Act, ActChild, ChildrenCommon, SubArticleElement},
     util::{debug::WithElemContext, walker::SAEVisitor},
 };
-use log::info;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
 
-use crate::{fixups::ActFixups, structural_cut_points::GetCutPoints};
+use crate::{
+    events::{EventEnforcementDate, EventRegistry},
+    fixups::ActFixups,
+    structural_cut_points::GetCutPoints,
+};
+
+/// The date a provision comes into force.
+///
+/// `Undetermined` is later than any actual date, so provisions with such
+/// enforcement dates are never in force.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EffectiveDate {
+    Date(NaiveDate),
+    /// Depends on an event (see `EventRegistry`) whose date is not known yet
+    Undetermined {
+        event: String,
+    },
+}
+
+impl EffectiveDate {
+    pub fn date(&self) -> Option<NaiveDate> {
+        match self {
+            EffectiveDate::Date(date) => Some(*date),
+            EffectiveDate::Undetermined { .. } => None,
+        }
+    }
+}
+
+impl std::fmt::Display for EffectiveDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EffectiveDate::Date(date) => write!(f, "{date}"),
+            EffectiveDate::Undetermined { event } => write!(f, "not yet determined ({event})"),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct ActualEnforcementDate {
     positions: Vec<Reference>,
-    date: NaiveDate,
+    date: EffectiveDate,
+}
+
+/// Scheduled expiry ("hatályát veszti") of elements of the act on a specific date.
+/// Inline repeals of enforcement date provisions are converted to this, but it
+/// can also be added by fixups, for standalone sunset clauses.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledExpiry {
+    /// The provision containing the expiry, used as the cause of the repeal
+    pub cause: Reference,
+    /// The expiring elements, relative to the act. The whole act expires
+    /// if both this and `structural_positions` are empty
+    #[serde(default)]
+    pub positions: Vec<Reference>,
+    #[serde(default)]
+    pub structural_positions: Vec<StructuralReference>,
+    pub date: NaiveDate,
+}
+
+#[derive(Debug)]
+pub struct ActualExpiry {
+    pub cause: Reference,
+    /// Relative to the act. Empty if the whole act expires.
+    pub positions: Vec<Reference>,
+    pub date: NaiveDate,
+}
+
+/// Inconsistencies found while calculating the enforcement dates of an act.
+///
+/// In lenient mode, a best guess is used instead of failing, and these are
+/// stored with the act, so that they can be shown to the user.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EnforcementDateWarning {
+    /// There was no default enforcement date. `assumed` was used instead: the
+    /// earliest specific enforcement date, or the day after publication.
+    NoDefaultDate { assumed: NaiveDate },
+    /// There were multiple default enforcement dates, the earliest was used.
+    MultipleDefaultDates {
+        used: NaiveDate,
+        ignored: Vec<NaiveDate>,
+    },
+    /// A specific enforcement date precedes the default one. It is used as-is.
+    DateBeforeDefault {
+        date: NaiveDate,
+        positions: Vec<Reference>,
+    },
+    /// The enforcement date could not be processed, and was ignored.
+    InvalidEnforcementDate { error: String },
+}
+
+impl std::fmt::Display for EnforcementDateWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnforcementDateWarning::NoDefaultDate { assumed } => write!(
+                f,
+                "Could not find the default enforcement date, assuming {assumed}"
+            ),
+            EnforcementDateWarning::MultipleDefaultDates { used, ignored } => write!(
+                f,
+                "Found too many default enforcement dates (used: {used}, ignored: {ignored:?})"
+            ),
+            EnforcementDateWarning::DateBeforeDefault { date, positions } => write!(
+                f,
+                "Enforcement date {date} is before the act's default date (positions: {positions:?})"
+            ),
+            EnforcementDateWarning::InvalidEnforcementDate { error } => {
+                write!(f, "Ignored invalid enforcement date: {error}")
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -42,73 +148,257 @@ pub struct EnforcementDateSet {
     default_date: NaiveDate,
     // TODO: this needs a faster data structure to prevent two levels of linear searches
     enforcement_dates: Vec<ActualEnforcementDate>,
+    expiries: Vec<ActualExpiry>,
+    warnings: Vec<EnforcementDateWarning>,
+    /// Dates of text overrides from fixups. Nothing comes into force on these,
+    /// but the act has to be processed.
+    fixup_dates: Vec<NaiveDate>,
+    /// The ids of the events the enforcement dates depend on
+    events: Vec<String>,
 }
 
 impl EnforcementDateSet {
+    /// Calculate the enforcement dates of an act, including fixups.
+    ///
+    /// Inconsistent enforcement dates do not fail the calculation, see `warnings()`.
     pub fn from_act(act: &Act) -> Result<Self> {
-        let mut visitor = EnforcementDateAccumulator::default();
-        act.walk_saes(&mut visitor)
-            .with_elem_context("Getting enforcement dates failed", act)?;
-        let additional_eds = ActFixups::load(act.identifier)?.get_additional_enforcement_dates();
+        let (extracted_eds, extracted_expiries) = Self::extract_from_act(act)?;
+        let act_fixups = ActFixups::load(act.identifier)?;
+        let removed_eds = act_fixups.get_removed_enforcement_dates();
+        if !removed_eds.is_empty() {
+            info!("Fixup: Removing {} enforcement dates", removed_eds.len());
+        }
+        let mut raw_enforcement_dates: Vec<_> = extracted_eds
+            .into_iter()
+            .filter(|(position, _)| !removed_eds.contains(position))
+            .map(|(_, ed)| ed)
+            .collect();
+        // Inline repeals of removed enforcement dates are removed too
+        let mut expiries: Vec<_> = extracted_expiries
+            .into_iter()
+            .filter(|expiry| !removed_eds.contains(&expiry.cause))
+            .collect();
+        let additional_eds = act_fixups.get_additional_enforcement_dates();
         if !additional_eds.is_empty() {
             info!(
                 "Fixup: Using {} additional enforcement dates",
                 additional_eds.len()
             );
-            visitor.result.extend(additional_eds);
+            raw_enforcement_dates.extend(additional_eds);
+        }
+        let mut result = Self::from_enforcement_dates_lenient(&raw_enforcement_dates, act)
+            .with_elem_context("Calculating enforcement dates failed", act)?;
+        let event_eds = act_fixups.get_event_enforcement_dates();
+        if !event_eds.is_empty() {
+            info!(
+                "Fixup: Using {} event-based enforcement dates",
+                event_eds.len()
+            );
+            result
+                .add_event_enforcement_dates(&event_eds, &EventRegistry::load()?, act)
+                .with_elem_context("Calculating event-based enforcement dates failed", act)?;
+        }
+        let additional_expiries = act_fixups.get_additional_expiries();
+        if !additional_expiries.is_empty() {
+            info!(
+                "Fixup: Using {} additional scheduled expiries",
+                additional_expiries.len()
+            );
+            expiries.extend(additional_expiries);
+        }
+        result
+            .add_expiries(&expiries, act)
+            .with_elem_context("Calculating scheduled expiries failed", act)?;
+        result.fixup_dates = act_fixups
+            .get_text_overrides()
+            .iter()
+            .map(|text_override| text_override.date)
+            .collect();
+        for warning in &result.warnings {
+            warn!("Enforcement dates of {}: {warning}", act.identifier);
         }
-        Self::from_enforcement_dates(&visitor.result, act)
-            .with_elem_context("Calculating enforcement dates failed", act)
+        Ok(result)
+    }
+
+    /// The enforcement dates (along with the position of the provision they
+    /// are in) and scheduled expiries in the text of the act, without fixups.
+    pub fn extract_from_act(
+        act: &Act,
+    ) -> Result<(Vec<(Reference, EnforcementDate)>, Vec<ScheduledExpiry>)> {
+        let mut visitor = EnforcementDateAccumulator::default();
+        act.walk_saes(&mut visitor)
+            .with_elem_context("Getting enforcement dates failed", act)?;
+        Ok((visitor.result, visitor.expiries))
     }
+
+    /// Strict version of `from_enforcement_dates_lenient`: fails on the first warning.
     pub fn from_enforcement_dates(
         raw_enforcement_dates: &[EnforcementDate],
         act: &Act,
+    ) -> Result<Self> {
+        let result = Self::from_enforcement_dates_lenient(raw_enforcement_dates, act)?;
+        if let Some(warning) = result.warnings.first() {
+            bail!("{warning}");
+        }
+        Ok(result)
+    }
+
+    /// Calculate the enforcement date set, using a best guess for inconsistent
+    /// enforcement dates, and recording a warning for each of them.
+    pub fn from_enforcement_dates_lenient(
+        raw_enforcement_dates: &[EnforcementDate],
+        act: &Act,
     ) -> Result<Self> {
         let mut enforcement_dates = Vec::new();
-        let mut default_date = None;
+        let mut default_dates = Vec::new();
+        let mut warnings = Vec::new();
         for raw_date in raw_enforcement_dates {
-            let converted_date = ActualEnforcementDate::from_enforcement_date(raw_date, act)?;
-            if raw_date.is_default {
-                ensure!(default_date.is_none(), "Found too many default enforcement dates (first: {default_date:?}, second: {raw_date:?})");
-                default_date = Some(converted_date);
-            } else {
-                enforcement_dates.push(converted_date);
+            match ActualEnforcementDate::from_enforcement_date(raw_date, act) {
+                Ok(converted_date) => {
+                    if raw_date.is_default {
+                        default_dates.extend(converted_date.date.date());
+                    } else {
+                        enforcement_dates.push(converted_date);
+                    }
+                }
+                Err(error) => warnings.push(EnforcementDateWarning::InvalidEnforcementDate {
+                    error: format!("{error:#}"),
+                }),
             }
         }
-        let default_date = default_date
-            .ok_or_else(|| {
-                anyhow!(
-                    "Could not find the default enforcement date (out of {})",
-                    raw_enforcement_dates.len()
-                )
-            })?
-            .date;
-
-        // See 61/2009. (XII. 14.) IRM rendelet 81. § (2)
-        ensure!(
-            enforcement_dates.iter().all(|ed| ed.date >= default_date),
-            "Some enforcement dates found after the act's default date ({default_date}): {:?}",
-            enforcement_dates
-                .iter()
-                .filter(|ed| ed.date < default_date)
-                .collect::<Vec<_>>(),
-        );
+        default_dates.sort();
+        let default_date = match default_dates.split_first() {
+            Some((used, [])) => *used,
+            Some((used, ignored)) => {
+                warnings.push(EnforcementDateWarning::MultipleDefaultDates {
+                    used: *used,
+                    ignored: ignored.to_vec(),
+                });
+                *used
+            }
+            None => {
+                let assumed = enforcement_dates
+                    .iter()
+                    .filter_map(|ed| ed.date.date())
+                    .min()
+                    .unwrap_or_else(|| act.publication_date.succ());
+                warnings.push(EnforcementDateWarning::NoDefaultDate { assumed });
+                assumed
+            }
+        };
 
-        Ok(Self {
+        let mut result = Self {
             default_date,
             enforcement_dates,
-        })
+            expiries: Vec::new(),
+            warnings,
+            fixup_dates: Vec::new(),
+            events: Vec::new(),
+        };
+        result.check_dates_after_default();
+        Ok(result)
+    }
+
+    /// Add enforcement dates that depend on events. These take precedence over
+    /// the ones already in the set, for the same positions.
+    ///
+    /// Dates before the default date are recorded as warnings.
+    pub fn add_event_enforcement_dates(
+        &mut self,
+        event_eds: &[EventEnforcementDate],
+        events: &EventRegistry,
+        act: &Act,
+    ) -> Result<()> {
+        for event_ed in event_eds {
+            let event = events.get(&event_ed.event)?;
+            let date = match event.date {
+                Some(date) => EffectiveDate::Date(date),
+                None => EffectiveDate::Undetermined {
+                    event: event.name.clone(),
+                },
+            };
+            self.enforcement_dates.push(ActualEnforcementDate {
+                positions: ActualEnforcementDate::convert_positions(
+                    &event_ed.positions,
+                    &event_ed.structural_positions,
+                    act,
+                )?,
+                date,
+            });
+            if !self.events.contains(&event_ed.event) {
+                self.events.push(event_ed.event.clone());
+            }
+        }
+        self.check_dates_after_default();
+        Ok(())
+    }
+
+    /// Add scheduled expiries. Positions are relative to the act.
+    pub fn add_expiries(&mut self, expiries: &[ScheduledExpiry], act: &Act) -> Result<()> {
+        for expiry in expiries {
+            ensure!(
+                expiry.positions.iter().all(|p| p.act().is_none()),
+                "Reference contained act in scheduled expiry"
+            );
+            ensure!(
+                expiry.structural_positions.iter().all(|p| p.act.is_none()),
+                "Structural reference contained act in scheduled expiry"
+            );
+            self.expiries.push(ActualExpiry {
+                cause: expiry.cause.clone(),
+                positions: ActualEnforcementDate::convert_positions(
+                    &expiry.positions,
+                    &expiry.structural_positions,
+                    act,
+                )?,
+                date: expiry.date,
+            });
+        }
+        Ok(())
+    }
+
+    /// Record a warning for all enforcement dates before the default date.
+    fn check_dates_after_default(&mut self) {
+        // See 61/2009. (XII. 14.) IRM rendelet 81. § (2)
+        for ed in &self.enforcement_dates {
+            if let Some(date) = ed.date.date() {
+                if date < self.default_date {
+                    let warning = EnforcementDateWarning::DateBeforeDefault {
+                        date,
+                        positions: ed.positions.clone(),
+                    };
+                    if !self.warnings.contains(&warning) {
+                        self.warnings.push(warning);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Inconsistencies found during the calculation, where a best guess was used.
+    pub fn warnings(&self) -> &[EnforcementDateWarning] {
+        &self.warnings
     }
 
     /// Check the enforcement date of the reference.
-    pub fn effective_enforcement_date(&self, position: &Reference) -> NaiveDate {
+    pub fn effective_enforcement_date(&self, position: &Reference) -> EffectiveDate {
+        self.effective_enforcement_date_with_reason(position).0
+    }
+
+    /// Same as effective_enforcement_date, but also returns the position in the
+    /// enforcement date provision that determined the date.
+    /// None means that the default date of the act was used.
+    pub fn effective_enforcement_date_with_reason(
+        &self,
+        position: &Reference,
+    ) -> (EffectiveDate, Option<&Reference>) {
         // TODO: Check the act instead
         let position = position.without_act();
-        let mut result = self.default_date;
+        let mut result = (EffectiveDate::Date(self.default_date), None);
         for ed in &self.enforcement_dates {
             for ed_pos in &ed.positions {
                 if ed_pos.contains(&position) {
-                    result = ed.date;
+                    result = (ed.date.clone(), Some(ed_pos));
                 }
             }
         }
@@ -121,7 +411,7 @@ impl EnforcementDateSet {
         &self,
         position: &Reference,
         on_date: NaiveDate,
-    ) -> Option<NaiveDate> {
+    ) -> Option<EffectiveDate> {
         // TODO: Check the act instead
         let position = position.without_act();
         let last_part = position.get_last_part();
@@ -129,50 +419,120 @@ impl EnforcementDateSet {
         self.enforcement_dates
             .iter()
             .find(|ed| {
-                ed.date > on_date
+                ed.date > EffectiveDate::Date(on_date)
                     && ed.positions.iter().any(|p| {
                         // This is needed instead of a simple == to handle ranges.
                         is_same_level(&last_part, &p.get_last_part()) && p.contains(&position)
                     })
             })
-            .map(|ed| ed.date)
+            .map(|ed| ed.date.clone())
     }
 
     pub fn is_in_force(&self, position: &Reference, on_date: NaiveDate) -> bool {
         // TODO: short circuit trivial case when all dates are in the past
-        self.effective_enforcement_date(position) <= on_date
+        self.effective_enforcement_date(position) <= EffectiveDate::Date(on_date)
     }
 
     pub fn came_into_force_today(&self, position: &Reference, on_date: NaiveDate) -> bool {
         // TODO: short circuit trivial cases when no dates are "on_date"
-        self.effective_enforcement_date(position) == on_date
+        self.effective_enforcement_date(position) == EffectiveDate::Date(on_date)
     }
 
     pub fn came_into_force_yesterday(&self, position: &Reference, on_date: NaiveDate) -> bool {
         // TODO: short circuit trivial cases when no dates are "on_date"
-        self.effective_enforcement_date(position) == on_date.pred()
+        self.effective_enforcement_date(position) == EffectiveDate::Date(on_date.pred())
     }
 
+    /// The expiries that should be applied on the specific date
+    pub fn expiries_on(&self, date: NaiveDate) -> impl Iterator<Item = &ActualExpiry> {
+        self.expiries
+            .iter()
+            .filter(move |expiry| expiry.date == date)
+    }
+
+    /// Returns the date of the first expiry after `on_date` that specifically
+    /// mentions the element (see `specific_element_not_in_force`).
+    /// Expiries of the whole act are not returned.
+    pub fn upcoming_expiry(&self, position: &Reference, on_date: NaiveDate) -> Option<NaiveDate> {
+        let position = position.without_act();
+        let last_part = position.get_last_part();
+        self.expiries
+            .iter()
+            .filter(|expiry| {
+                expiry.date > on_date
+                    && expiry.positions.iter().any(|p| {
+                        is_same_level(&last_part, &p.get_last_part()) && p.contains(&position)
+                    })
+            })
+            .map(|expiry| expiry.date)
+            .min()
+    }
+
+    /// All determined enforcement dates, expiry dates and fixup dates, i.e. all
+    /// dates when the act has to be processed.
     pub fn get_all_dates(&self) -> Vec<NaiveDate> {
-        let mut result: Vec<_> = self.enforcement_dates.iter().map(|ed| ed.date).collect();
+        let mut result: Vec<_> = self
+            .enforcement_dates
+            .iter()
+            .filter_map(|ed| ed.date.date())
+            .chain(self.expiries.iter().map(|expiry| expiry.date))
+            .chain(self.fixup_dates.iter().copied())
+            .collect();
         result.push(self.default_date);
         result
     }
+
+    /// The ids of the events (see `EventRegistry`) some enforcement dates
+    /// depend on. Their dates are only in `get_all_dates` if they are known.
+    pub fn events(&self) -> &[String] {
+        &self.events
+    }
+
+    /// The dates in `get_all_dates` that precede the publication of the act,
+    /// i.e. the ones where the act has retroactive effect. Sorted.
+    pub fn retroactive_dates(&self, publication_date: NaiveDate) -> Vec<NaiveDate> {
+        let mut result: Vec<_> = self
+            .get_all_dates()
+            .into_iter()
+            .filter(|date| *date < publication_date)
+            .collect();
+        result.sort();
+        result.dedup();
+        result
+    }
 }
 
 #[derive(Debug, Default)]
 struct EnforcementDateAccumulator {
-    result: Vec<EnforcementDate>,
+    result: Vec<(Reference, EnforcementDate)>,
+    expiries: Vec<ScheduledExpiry>,
 }
 
 impl SAEVisitor for EnforcementDateAccumulator {
     fn on_enter<IT: IdentifierCommon, CT: ChildrenCommon>(
         &mut self,
-        _position: &Reference,
+        position: &Reference,
         element: &SubArticleElement<IT, CT>,
     ) -> Result<()> {
         if let Some(SpecialPhrase::EnforcementDate(ed)) = &element.semantic_info.special_phrase {
-            self.result.push(ed.clone())
+            self.result.push((position.clone(), ed.clone()));
+            // "Ez a törvény 2013. július 1-jén lép hatályba, és 2014. július 1-jén hatályát veszti."
+            // applies to the whole act, but
+            // "A 12. § 2013. július 1-jén lép hatályba, és 2014. július 1-jén hatályát veszti."
+            // only to the elements mentioned.
+            if let Some(inline_repeal) = ed.inline_repeal {
+                let (positions, structural_positions) = if ed.is_default {
+                    (Vec::new(), Vec::new())
+                } else {
+                    (ed.positions.clone(), ed.structural_positions.clone())
+                };
+                self.expiries.push(ScheduledExpiry {
+                    cause: position.clone(),
+                    positions,
+                    structural_positions,
+                    date: inline_repeal,
+                });
+            }
         }
         Ok(())
     }
@@ -188,14 +548,23 @@ impl ActualEnforcementDate {
             ed.structural_positions.iter().all(|p| p.act.is_none()),
             "Structural reference contained act in from_enforcement_date"
         );
-        let mut positions = ed.positions.clone();
-        for structural_ref in &ed.structural_positions {
-            positions.push(Self::convert_structural_ref(structural_ref, act)?)
-        }
-        let date = Self::convert_date(&ed.date, act.publication_date);
+        let positions = Self::convert_positions(&ed.positions, &ed.structural_positions, act)?;
+        let date = EffectiveDate::Date(Self::convert_date(&ed.date, act.publication_date));
         Ok(Self { positions, date })
     }
 
+    fn convert_positions(
+        positions: &[Reference],
+        structural_positions: &[StructuralReference],
+        act: &Act,
+    ) -> Result<Vec<Reference>> {
+        let mut result = positions.to_vec();
+        for structural_ref in structural_positions {
+            result.push(Self::convert_structural_ref(structural_ref, act)?)
+        }
+        Ok(result)
+    }
+
     fn convert_date(date: &EnforcementDateType, publication_date: NaiveDate) -> NaiveDate {
         match date {
             hun_law::semantic_info::EnforcementDateType::Date(d) => *d,
@@ -266,7 +635,7 @@ mod tests {
     #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
     struct TestRef {
         position: Reference,
-        date: NaiveDate,
+        date: EffectiveDate,
     }
 
     const TEST_ED_SET: &str = r#"
@@ -380,6 +749,253 @@ mod tests {
                 singleton_yaml::to_string(&effective).unwrap()
             );
         }
+        assert!(ed_set
+            .retroactive_dates(dummy_act.publication_date)
+            .is_empty());
+        assert_eq!(
+            ed_set.retroactive_dates(NaiveDate::from_ymd(2013, 8, 1)),
+            vec![
+                NaiveDate::from_ymd(2013, 7, 15),
+                NaiveDate::from_ymd(2013, 7, 31)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_event_enforcement_dates() {
+        let enforcement_dates: Vec<EnforcementDate> =
+            singleton_yaml::from_str(TEST_ED_SET).unwrap();
+        let events: EventRegistry = singleton_yaml::from_str(
+            r#"
+            - id: known
+              name: Known event
+              date: 2015-01-01
+            - id: unknown
+              name: Unknown event
+            "#,
+        )
+        .unwrap();
+        let event_eds: Vec<EventEnforcementDate> = singleton_yaml::from_str(
+            r#"
+            - positions:
+                - article: "2"
+              event: known
+            - positions:
+                - article: "3"
+                - article: "70"
+              event: unknown
+            "#,
+        )
+        .unwrap();
+        let dummy_act = Act {
+            identifier: ActIdentifier {
+                year: 2024,
+                number: 420,
+            },
+            subject: "Testing".into(),
+            preamble: "".into(),
+            publication_date: NaiveDate::from_ymd(2013, 7, 1),
+            contained_abbreviations: Default::default(),
+            children: Vec::new(),
+        };
+        let mut ed_set =
+            EnforcementDateSet::from_enforcement_dates(&enforcement_dates, &dummy_act).unwrap();
+        ed_set
+            .add_event_enforcement_dates(&event_eds, &events, &dummy_act)
+            .unwrap();
+
+        let article_2: Reference = singleton_yaml::from_str("article: '2'").unwrap();
+        let article_3: Reference = singleton_yaml::from_str("article: '3'").unwrap();
+        let article_70: Reference = singleton_yaml::from_str("article: '70'").unwrap();
+        let undetermined = EffectiveDate::Undetermined {
+            event: "Unknown event".into(),
+        };
+        assert_eq!(
+            ed_set.effective_enforcement_date(&article_2),
+            EffectiveDate::Date(NaiveDate::from_ymd(2015, 1, 1))
+        );
+        assert_eq!(ed_set.effective_enforcement_date(&article_3), undetermined);
+        // Event-based dates take precedence
+        assert_eq!(ed_set.effective_enforcement_date(&article_70), undetermined);
+
+        let far_future = NaiveDate::from_ymd(2100, 1, 1);
+        assert!(ed_set.is_in_force(&article_2, far_future));
+        assert!(!ed_set.is_in_force(&article_3, far_future));
+        assert_eq!(
+            ed_set.specific_element_not_in_force(&article_3, far_future),
+            Some(undetermined)
+        );
+        assert!(ed_set
+            .get_all_dates()
+            .contains(&NaiveDate::from_ymd(2015, 1, 1)));
+        assert_eq!(ed_set.events(), ["known", "unknown"]);
+
+        let unknown_event: Vec<EventEnforcementDate> =
+            singleton_yaml::from_str("- event: nonexistent").unwrap();
+        assert!(ed_set
+            .add_event_enforcement_dates(&unknown_event, &events, &dummy_act)
+            .is_err());
+    }
+
+    #[test]
+    fn test_expiries() {
+        let enforcement_dates: Vec<EnforcementDate> =
+            singleton_yaml::from_str(TEST_ED_SET).unwrap();
+        let expiries: Vec<ScheduledExpiry> = singleton_yaml::from_str(
+            r#"
+            - cause:
+                act:
+                  year: 2024
+                  number: 420
+                article: '100'
+              positions:
+                - article: '2'
+                  paragraph: '1'
+              date: 2015-01-01
+            - cause:
+                act:
+                  year: 2024
+                  number: 420
+                article: '101'
+              date: 2016-01-01
+            "#,
+        )
+        .unwrap();
+        let dummy_act = Act {
+            identifier: ActIdentifier {
+                year: 2024,
+                number: 420,
+            },
+            subject: "Testing".into(),
+            preamble: "".into(),
+            publication_date: NaiveDate::from_ymd(2013, 7, 1),
+            contained_abbreviations: Default::default(),
+            children: Vec::new(),
+        };
+        let mut ed_set =
+            EnforcementDateSet::from_enforcement_dates(&enforcement_dates, &dummy_act).unwrap();
+        ed_set.add_expiries(&expiries, &dummy_act).unwrap();
+
+        let expiry_date = NaiveDate::from_ymd(2015, 1, 1);
+        let paragraph: Reference =
+            singleton_yaml::from_str("{article: '2', paragraph: '1'}").unwrap();
+        let point: Reference =
+            singleton_yaml::from_str("{article: '2', paragraph: '1', point: a}").unwrap();
+        let article: Reference = singleton_yaml::from_str("article: '2'").unwrap();
+        assert_eq!(
+            ed_set.upcoming_expiry(&paragraph, NaiveDate::from_ymd(2014, 1, 1)),
+            Some(expiry_date)
+        );
+        // Only specifically mentioned elements are marked
+        assert_eq!(
+            ed_set.upcoming_expiry(&point, NaiveDate::from_ymd(2014, 1, 1)),
+            None
+        );
+        assert_eq!(
+            ed_set.upcoming_expiry(&article, NaiveDate::from_ymd(2014, 1, 1)),
+            None
+        );
+        assert_eq!(ed_set.upcoming_expiry(&paragraph, expiry_date), None);
+
+        assert_eq!(ed_set.expiries_on(expiry_date).count(), 1);
+        assert!(ed_set
+            .expiries_on(NaiveDate::from_ymd(2016, 1, 1))
+            .all(|expiry| expiry.positions.is_empty()));
+        let all_dates = ed_set.get_all_dates();
+        assert!(all_dates.contains(&expiry_date));
+        assert!(all_dates.contains(&NaiveDate::from_ymd(2016, 1, 1)));
+    }
+
+    #[test]
+    fn test_lenient_enforcement_dates() {
+        let dummy_act = Act {
+            identifier: ActIdentifier {
+                year: 2024,
+                number: 420,
+            },
+            subject: "Testing".into(),
+            preamble: "".into(),
+            publication_date: NaiveDate::from_ymd(2013, 7, 1),
+            contained_abbreviations: Default::default(),
+            children: Vec::new(),
+        };
+        let article_1: Reference = singleton_yaml::from_str("article: '1'").unwrap();
+        let article_2: Reference = singleton_yaml::from_str("article: '2'").unwrap();
+
+        // No default date: the earliest specific date is assumed
+        let enforcement_dates: Vec<EnforcementDate> = singleton_yaml::from_str(
+            r#"
+            - date:
+                Date: 2013-09-01
+              positions:
+                - article: "2"
+            - date:
+                Date: 2013-08-01
+              positions:
+                - article: "3"
+            "#,
+        )
+        .unwrap();
+        assert!(
+            EnforcementDateSet::from_enforcement_dates(&enforcement_dates, &dummy_act).is_err()
+        );
+        let ed_set =
+            EnforcementDateSet::from_enforcement_dates_lenient(&enforcement_dates, &dummy_act)
+                .unwrap();
+        assert_eq!(
+            ed_set.warnings(),
+            &[EnforcementDateWarning::NoDefaultDate {
+                assumed: NaiveDate::from_ymd(2013, 8, 1)
+            }]
+        );
+        assert_eq!(
+            ed_set.effective_enforcement_date(&article_1),
+            EffectiveDate::Date(NaiveDate::from_ymd(2013, 8, 1))
+        );
+
+        // Multiple default dates, and a date before the default one
+        let enforcement_dates: Vec<EnforcementDate> = singleton_yaml::from_str(
+            r#"
+            - date:
+                Date: 2013-10-01
+              is_default: true
+            - date:
+                Date: 2013-09-01
+              is_default: true
+            - date:
+                Date: 2013-08-01
+              positions:
+                - article: "2"
+            "#,
+        )
+        .unwrap();
+        assert!(
+            EnforcementDateSet::from_enforcement_dates(&enforcement_dates, &dummy_act).is_err()
+        );
+        let ed_set =
+            EnforcementDateSet::from_enforcement_dates_lenient(&enforcement_dates, &dummy_act)
+                .unwrap();
+        assert_eq!(
+            ed_set.warnings(),
+            &[
+                EnforcementDateWarning::MultipleDefaultDates {
+                    used: NaiveDate::from_ymd(2013, 9, 1),
+                    ignored: vec![NaiveDate::from_ymd(2013, 10, 1)],
+                },
+                EnforcementDateWarning::DateBeforeDefault {
+                    date: NaiveDate::from_ymd(2013, 8, 1),
+                    positions: vec![article_2.clone()],
+                },
+            ]
+        );
+        assert_eq!(
+            ed_set.effective_enforcement_date(&article_1),
+            EffectiveDate::Date(NaiveDate::from_ymd(2013, 9, 1))
+        );
+        assert_eq!(
+            ed_set.effective_enforcement_date(&article_2),
+            EffectiveDate::Date(NaiveDate::from_ymd(2013, 8, 1))
+        );
     }
 
     const TEST_ACT: &str = r#"
@@ -0,0 +1,96 @@
+// Copyright (c) 2022-2023, Alex Badics
+//
+// This file is part of AJDB
+//
+// AJDB is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// AJDB is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
+
+//! External events that enforcement dates may depend on
+//!
+//! Some provisions come into force on the date of an event, instead of a fixed
+//! date, e.g. "az Európai Unióhoz való csatlakozás napján", or "a külön
+//! törvényben meghatározott napon". These events are maintained by hand in
+//! `data/events.yml`, and enforcement dates refer to them by id (see
+//! `EventEnforcementDate`). Events without a date are "not yet determined":
+//! provisions depending on them are not in force.
+//!
+//! The stored states only cache which events an act depends on, not their
+//! dates (see `CachedEnforcementDates`). After setting or changing the date of
+//! an event, run `ajdb recalculate` from the earliest of its old and new dates.
+
+use std::{collections::BTreeMap, fs::File, path::PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::NaiveDate;
+use hun_law::{
+    reference::{structural::StructuralReference, Reference},
+    util::singleton_yaml,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    pub id: String,
+    /// Human readable description, e.g. "Az Európai Unióhoz való csatlakozás napja"
+    pub name: String,
+    /// None if the date of the event is not yet determined
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(from = "Vec<Event>")]
+pub struct EventRegistry {
+    events: BTreeMap<String, Event>,
+}
+
+impl EventRegistry {
+    pub fn load() -> Result<Self> {
+        Self::load_from("./data/events.yml".into())
+    }
+
+    pub fn load_from(path: PathBuf) -> Result<Self> {
+        if path.exists() {
+            singleton_yaml::from_reader(File::open(&path)?)
+                .with_context(|| format!("Error loading events from {}", path.display()))
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Result<&Event> {
+        self.events
+            .get(id)
+            .ok_or_else(|| anyhow!("Unknown event '{id}'"))
+    }
+}
+
+impl From<Vec<Event>> for EventRegistry {
+    fn from(events: Vec<Event>) -> Self {
+        Self {
+            events: events.into_iter().map(|e| (e.id.clone(), e)).collect(),
+        }
+    }
+}
+
+/// Enforcement date that depends on an event, instead of the publication date
+/// of the act. Added with fixups, as `hun_law` does not recognize these.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventEnforcementDate {
+    #[serde(default)]
+    pub positions: Vec<Reference>,
+    #[serde(default)]
+    pub structural_positions: Vec<StructuralReference>,
+    /// The id of the event in the `EventRegistry`
+    pub event: String,
+}
@@ -15,49 +15,205 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{fs::File, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs::File,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
 use chrono::NaiveDate;
-use hun_law::{identifier::ActIdentifier, semantic_info::EnforcementDate, util::singleton_yaml};
-use serde::{Deserialize, Serialize};
+use hun_law::{
+    identifier::ActIdentifier, reference::Reference, semantic_info::EnforcementDate,
+    structure::ChangeCause, util::singleton_yaml,
+};
+use log::{info, warn};
+use seahash::SeaHasher;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use crate::amender::AppliableModification;
+use crate::{
+    amender::{
+        fix_order::affected_reference, text_amendment::text_replace::MatchLevel,
+        text_override::TextOverride, AppliableModification, AppliableModificationType,
+    },
+    enforcement_date_set::ScheduledExpiry,
+    events::EventEnforcementDate,
+};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+pub const FIXUPS_DIR: &str = "./data/fixups/";
+/// Environment variable with the fixup directories to use instead of
+/// `FIXUPS_DIR`, separated like PATH, in increasing order of priority.
+pub const FIXUP_DIRS_ENV: &str = "AJDB_FIXUP_DIRS";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ActFixup {
     AddModification(AppliableModification),
     AddEnforcementDate(EnforcementDate),
+    AddEventEnforcementDate(EventEnforcementDate),
+    AddExpiry(ScheduledExpiry),
+    /// Do not apply the selected modifications extracted from the act
+    RemoveModification(ModificationSelector),
+    /// Apply a different modification instead of the selected extracted ones,
+    /// with the same cause. E.g. for fixing the from/to of text amendments.
+    ReplaceModification {
+        selector: ModificationSelector,
+        modification: AppliableModificationType,
+    },
+    /// Match the replaced text of the selected extracted text amendments
+    /// tolerantly, up to `max_match_level`. E.g. for amendments that do not
+    /// follow the typographic changes of the amended text, or that refer to
+    /// a suffixed form by its base form.
+    AllowTolerantMatch {
+        selector: ModificationSelector,
+        max_match_level: MatchLevel,
+    },
+    /// Ignore the enforcement date provision at the position (and its inline repeal)
+    RemoveEnforcementDate(Reference),
+    /// Force the text of an element of the act on a specific date
+    OverrideElementText(ElementTextOverride),
 }
 
-#[derive(Debug, Clone)]
+/// Selects modifications extracted from an act.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModificationSelector {
+    pub cause: ChangeCause,
+    /// The element changed by the modification. If not specified, all
+    /// modifications with the cause are selected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<Reference>,
+}
+
+impl ModificationSelector {
+    pub fn matches(&self, modification: &AppliableModification) -> bool {
+        modification.cause == self.cause
+            && self.position.as_ref().map_or(true, |position| {
+                affected_reference(&modification.modification).as_ref() == Some(position)
+            })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ElementTextOverride {
+    /// The overridden SAE, including the act
+    pub position: Reference,
+    pub date: NaiveDate,
+    pub text: String,
+    /// Shown as the cause of the change
+    pub cause: ChangeCause,
+}
+
+impl ElementTextOverride {
+    pub fn to_modification(&self) -> AppliableModification {
+        AppliableModification::new(
+            self.cause.clone(),
+            TextOverride {
+                position: self.position.clone(),
+                text: self.text.clone(),
+            },
+        )
+    }
+}
+
+impl ActFixup {
+    /// Whether the two fixups cannot be used together, because they set the
+    /// same thing differently (or are duplicates)
+    fn conflicts_with(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ActFixup::AddEnforcementDate(a), ActFixup::AddEnforcementDate(b)) => {
+                a.positions == b.positions && a.structural_positions == b.structural_positions
+            }
+            (ActFixup::AddEventEnforcementDate(a), ActFixup::AddEventEnforcementDate(b)) => {
+                a.positions == b.positions && a.structural_positions == b.structural_positions
+            }
+            (ActFixup::AddExpiry(a), ActFixup::AddExpiry(b)) => a.cause == b.cause,
+            (
+                ActFixup::RemoveModification(a) | ActFixup::ReplaceModification { selector: a, .. },
+                ActFixup::RemoveModification(b) | ActFixup::ReplaceModification { selector: b, .. },
+            ) => a == b,
+            (
+                ActFixup::AllowTolerantMatch { selector: a, .. },
+                ActFixup::AllowTolerantMatch { selector: b, .. },
+            ) => a == b,
+            (ActFixup::OverrideElementText(a), ActFixup::OverrideElementText(b)) => {
+                a.position == b.position && a.date == b.date
+            }
+            _ => self == other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct ActFixups {
-    fixups: Vec<ActFixup>,
+    entries: Vec<FixupEntry<ActFixup>>,
+    conflicts: Vec<FixupConflict>,
 }
 
 impl ActFixups {
-    pub fn load(act_id: ActIdentifier) -> Result<Self> {
-        Self::load_from(act_id, "./data/fixups/act/".into())
+    /// The fixups of the act from all layers, cached in the `FixupRegistry`
+    pub fn load(act_id: ActIdentifier) -> Result<Arc<Self>> {
+        FixupRegistry::get()?.act_fixups(act_id)
     }
 
-    pub fn load_from(act_id: ActIdentifier, base_dir: PathBuf) -> Result<Self> {
-        let fixup_path = base_dir
+    /// Load and merge the fixup files of the act from the layers, without caching
+    pub fn load_from(act_id: ActIdentifier, layers: &[FixupLayer]) -> Result<Self> {
+        let mut result = Self::default();
+        for layer in layers {
+            let path = layer.act_path(act_id);
+            if path.exists() {
+                merge_layer(&mut result.entries, &mut result.conflicts, &path)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// The path of the fixup file of the act in a layer's `act` directory,
+    /// whether it exists or not
+    pub fn path(act_id: ActIdentifier, act_dir: &Path) -> PathBuf {
+        act_dir
             .join(act_id.year.to_string())
-            .join(format!("{}.yml", act_id));
-        let fixups = if fixup_path.exists() {
-            singleton_yaml::from_reader(File::open(&fixup_path)?)?
-        } else {
-            Vec::new()
-        };
-        Ok(Self { fixups })
+            .join(format!("{}.yml", act_id))
+    }
+
+    /// All fixups, in the order of the layers, and then the order in the files
+    pub fn fixups(&self) -> impl Iterator<Item = &ActFixup> {
+        self.entries.iter().map(|e| &e.fixup)
+    }
+
+    /// All fixups along with the file they come from
+    pub fn entries(&self) -> &[FixupEntry<ActFixup>] {
+        &self.entries
+    }
+
+    /// Fixups that were dropped because a higher priority layer overrides them
+    pub fn conflicts(&self) -> &[FixupConflict] {
+        &self.conflicts
+    }
+
+    /// Add a fixup to the end of the act's fixup file in the highest priority
+    /// layer, keeping the existing entries and comments intact. Returns the
+    /// path of the file.
+    pub fn append(act_id: ActIdentifier, fixup: &ActFixup, comment: &str) -> Result<PathBuf> {
+        let path = top_layer()?.act_path(act_id);
+        append_to_file(&path, fixup, comment)?;
+        Ok(path)
     }
 
     pub fn get_additional_modifications(&self) -> Vec<AppliableModification> {
-        self.fixups
+        self.entries
             .iter()
-            .filter_map(|f| {
-                if let ActFixup::AddModification(m) = f {
-                    Some(m.clone())
+            .filter_map(|e| {
+                if let ActFixup::AddModification(m) = &e.fixup {
+                    Some(AppliableModification {
+                        fixup: Some(e.provenance()),
+                        ..m.clone()
+                    })
                 } else {
                     None
                 }
@@ -66,8 +222,7 @@ impl ActFixups {
     }
 
     pub fn get_additional_enforcement_dates(&self) -> Vec<EnforcementDate> {
-        self.fixups
-            .iter()
+        self.fixups()
             .filter_map(|f| {
                 if let ActFixup::AddEnforcementDate(m) = f {
                     Some(m.clone())
@@ -77,39 +232,869 @@ impl ActFixups {
             })
             .collect()
     }
+
+    pub fn get_event_enforcement_dates(&self) -> Vec<EventEnforcementDate> {
+        self.fixups()
+            .filter_map(|f| {
+                if let ActFixup::AddEventEnforcementDate(m) = f {
+                    Some(m.clone())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    pub fn get_additional_expiries(&self) -> Vec<ScheduledExpiry> {
+        self.fixups()
+            .filter_map(|f| {
+                if let ActFixup::AddExpiry(m) = f {
+                    Some(m.clone())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    pub fn get_removed_enforcement_dates(&self) -> Vec<Reference> {
+        self.fixups()
+            .filter_map(|f| {
+                if let ActFixup::RemoveEnforcementDate(position) = f {
+                    Some(position.clone())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    pub fn get_text_overrides(&self) -> Vec<ElementTextOverride> {
+        self.fixups()
+            .filter_map(|f| {
+                if let ActFixup::OverrideElementText(m) = f {
+                    Some(m.clone())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// The text overrides of the date, as modifications
+    pub fn get_text_override_modifications(&self, date: NaiveDate) -> Vec<AppliableModification> {
+        self.entries
+            .iter()
+            .filter_map(|e| match &e.fixup {
+                ActFixup::OverrideElementText(text_override) if text_override.date == date => {
+                    Some(AppliableModification {
+                        fixup: Some(e.provenance()),
+                        ..text_override.to_modification()
+                    })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Remove and replace extracted modifications according to the
+    /// `RemoveModification` and `ReplaceModification` fixups. Modifications
+    /// that already come from fixups are kept as-is.
+    pub fn fix_modifications(&self, modifications: &mut Vec<AppliableModification>) {
+        let mut removed = 0;
+        let mut replaced = 0;
+        let mut tolerant = 0;
+        modifications.retain(|modification| {
+            let remove = modification.fixup.is_none()
+                && self.fixups().any(|f| {
+                    matches!(f, ActFixup::RemoveModification(selector) if selector.matches(modification))
+                });
+            if remove {
+                removed += 1;
+            }
+            !remove
+        });
+        for modification in modifications.iter_mut() {
+            if modification.fixup.is_some() {
+                continue;
+            }
+            for entry in &self.entries {
+                if let ActFixup::ReplaceModification {
+                    selector,
+                    modification: replacement,
+                } = &entry.fixup
+                {
+                    if selector.matches(modification) {
+                        modification.modification = replacement.clone();
+                        modification.fixup = Some(entry.provenance());
+                        replaced += 1;
+                        break;
+                    }
+                }
+            }
+            if modification.fixup.is_some() {
+                continue;
+            }
+            for entry in &self.entries {
+                if let ActFixup::AllowTolerantMatch {
+                    selector,
+                    max_match_level,
+                } = &entry.fixup
+                {
+                    if selector.matches(modification) {
+                        modification.max_match_level = *max_match_level;
+                        modification.fixup = Some(entry.provenance());
+                        tolerant += 1;
+                        break;
+                    }
+                }
+            }
+        }
+        if removed > 0 || replaced > 0 || tolerant > 0 {
+            info!(
+                "Fixup: Removed {removed}, replaced {replaced} and allowed tolerant matching for {tolerant} modifications"
+            );
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum GlobalFixup {
     AddModification(AppliableModification),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct GlobalFixups {
-    fixups: Vec<GlobalFixup>,
+    entries: Vec<FixupEntry<GlobalFixup>>,
+    conflicts: Vec<FixupConflict>,
 }
 
 impl GlobalFixups {
-    pub fn load(date: NaiveDate) -> Result<Self> {
-        Self::load_from(date, "./data/fixups/date/".into())
+    /// The fixups of the date from all layers, cached in the `FixupRegistry`
+    pub fn load(date: NaiveDate) -> Result<Arc<Self>> {
+        FixupRegistry::get()?.date_fixups(date)
     }
 
-    pub fn load_from(date: NaiveDate, base_dir: PathBuf) -> Result<Self> {
-        let fixup_path = base_dir.join(date.format("%Y-%m-%d.yml").to_string());
-        let fixups = if fixup_path.exists() {
-            singleton_yaml::from_reader(File::open(&fixup_path)?)?
-        } else {
-            Vec::new()
-        };
-        Ok(Self { fixups })
+    /// Load and merge the fixup files of the date from the layers, without caching
+    pub fn load_from(date: NaiveDate, layers: &[FixupLayer]) -> Result<Self> {
+        let mut result = Self::default();
+        for layer in layers {
+            let path = layer.date_path(date);
+            if path.exists() {
+                merge_layer(&mut result.entries, &mut result.conflicts, &path)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// The path of the fixup file of the date in a layer's `date` directory,
+    /// whether it exists or not
+    pub fn path(date: NaiveDate, date_dir: &Path) -> PathBuf {
+        date_dir.join(date.format("%Y-%m-%d.yml").to_string())
+    }
+
+    /// All fixups, in the order of the layers, and then the order in the files
+    pub fn fixups(&self) -> impl Iterator<Item = &GlobalFixup> {
+        self.entries.iter().map(|e| &e.fixup)
+    }
+
+    /// All fixups along with the file they come from
+    pub fn entries(&self) -> &[FixupEntry<GlobalFixup>] {
+        &self.entries
+    }
+
+    /// Fixups that were dropped because a higher priority layer overrides them
+    pub fn conflicts(&self) -> &[FixupConflict] {
+        &self.conflicts
+    }
+
+    /// Add a fixup to the end of the date's fixup file in the highest priority
+    /// layer, keeping the existing entries and comments intact. Returns the
+    /// path of the file.
+    pub fn append(date: NaiveDate, fixup: &GlobalFixup, comment: &str) -> Result<PathBuf> {
+        let path = top_layer()?.date_path(date);
+        append_to_file(&path, fixup, comment)?;
+        Ok(path)
     }
 
     pub fn get_additional_modifications(&self) -> Vec<AppliableModification> {
-        self.fixups
+        self.entries
             .iter()
-            .map(|f| match f {
-                GlobalFixup::AddModification(modification) => modification.clone(),
+            .map(|e| match &e.fixup {
+                GlobalFixup::AddModification(modification) => AppliableModification {
+                    fixup: Some(e.provenance()),
+                    ..modification.clone()
+                },
             })
             .collect()
     }
 }
+
+/// A directory with fixup files: act fixups in `act/<year>/<act>.yml`, date
+/// fixups in `date/<date>.yml`, like in `data/fixups`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixupLayer {
+    pub base_dir: PathBuf,
+}
+
+impl FixupLayer {
+    pub fn new(base_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            base_dir: base_dir.into(),
+        }
+    }
+
+    pub fn act_dir(&self) -> PathBuf {
+        self.base_dir.join("act")
+    }
+
+    pub fn date_dir(&self) -> PathBuf {
+        self.base_dir.join("date")
+    }
+
+    pub fn act_path(&self, act_id: ActIdentifier) -> PathBuf {
+        ActFixups::path(act_id, &self.act_dir())
+    }
+
+    pub fn date_path(&self, date: NaiveDate) -> PathBuf {
+        GlobalFixups::path(date, &self.date_dir())
+    }
+}
+
+/// The configured fixup layers, in increasing order of priority. Fixups in a
+/// later layer override conflicting fixups of earlier layers, e.g. a
+/// private set of overrides on top of the upstream `data/fixups`.
+pub fn fixup_layers() -> Vec<FixupLayer> {
+    match std::env::var_os(FIXUP_DIRS_ENV) {
+        Some(dirs) if !dirs.is_empty() => {
+            std::env::split_paths(&dirs).map(FixupLayer::new).collect()
+        }
+        _ => vec![FixupLayer::new(FIXUPS_DIR)],
+    }
+}
+
+/// The layer of the shared registry with the highest priority
+fn top_layer() -> Result<FixupLayer> {
+    Ok(FixupRegistry::get()?
+        .layers()
+        .last()
+        .cloned()
+        .unwrap_or_else(|| FixupLayer::new(FIXUPS_DIR)))
+}
+
+/// The location of a fixup entry
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FixupOrigin {
+    pub path: PathBuf,
+    /// Index of the entry in the file
+    pub index: usize,
+}
+
+impl Display for FixupOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} #{}", self.path.display(), self.index)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FixupEntry<T> {
+    pub origin: FixupOrigin,
+    pub fixup: T,
+    pub note: Option<String>,
+}
+
+impl<T> FixupEntry<T> {
+    pub fn provenance(&self) -> FixupProvenance {
+        FixupProvenance {
+            file: self.origin.path.to_string_lossy().into_owned(),
+            index: self.origin.index,
+            note: self.note.clone(),
+        }
+    }
+}
+
+/// An entry in a fixup file: the fixup itself, and optionally a `note` key
+/// justifying it, e.g.
+/// ```yaml
+/// - AddModification:
+///     ...
+///   note: A módosító rendelkezés hivatkozása hibás
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+struct FixupFileEntry<T> {
+    #[serde(flatten)]
+    fixup: T,
+    #[serde(default)]
+    note: Option<String>,
+}
+
+/// The fixup a modification was created or changed by, so that the
+/// consolidated text can be marked as depending on a manual correction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FixupProvenance {
+    /// Path of the fixup file
+    pub file: String,
+    /// Index of the entry in the file
+    pub index: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// A fixup dropped in favor of a conflicting one in a higher priority layer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixupConflict {
+    pub overridden: FixupOrigin,
+    pub by: FixupOrigin,
+}
+
+impl Display for FixupConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is overridden by {}", self.overridden, self.by)
+    }
+}
+
+trait LayeredFixup: DeserializeOwned {
+    fn conflicts_with(&self, other: &Self) -> bool;
+}
+
+impl LayeredFixup for ActFixup {
+    fn conflicts_with(&self, other: &Self) -> bool {
+        ActFixup::conflicts_with(self, other)
+    }
+}
+
+impl LayeredFixup for GlobalFixup {
+    fn conflicts_with(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+/// Add the entries of a fixup file on top of the entries of the previous
+/// layers. Entries of previous layers that conflict with the new ones are
+/// dropped. Conflicts within the same file are not checked.
+fn merge_layer<T: LayeredFixup>(
+    entries: &mut Vec<FixupEntry<T>>,
+    conflicts: &mut Vec<FixupConflict>,
+    path: &Path,
+) -> Result<()> {
+    let fixups: Vec<FixupFileEntry<T>> = singleton_yaml::from_reader(File::open(path)?)
+        .with_context(|| format!("Error loading fixups from {}", path.display()))?;
+    for (index, FixupFileEntry { fixup, note }) in fixups.into_iter().enumerate() {
+        let origin = FixupOrigin {
+            path: path.to_owned(),
+            index,
+        };
+        entries.retain(|entry| {
+            if entry.origin.path == path || !entry.fixup.conflicts_with(&fixup) {
+                return true;
+            }
+            let conflict = FixupConflict {
+                overridden: entry.origin.clone(),
+                by: origin.clone(),
+            };
+            warn!("Fixup conflict: {conflict}");
+            conflicts.push(conflict);
+            false
+        });
+        entries.push(FixupEntry {
+            origin,
+            fixup,
+            note,
+        });
+    }
+    Ok(())
+}
+
+/// How often the fixup directories are checked for changes, if watched
+const FINGERPRINT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+static REGISTRY: Mutex<Option<CachedRegistry>> = Mutex::new(None);
+/// See `FixupRegistry::watch_changes`
+static WATCH_CHANGES: AtomicBool = AtomicBool::new(false);
+
+struct CachedRegistry {
+    registry: Arc<FixupRegistry>,
+    checked_at: Instant,
+    /// Reload on the next `get()`, see `FixupRegistry::invalidate`
+    stale: bool,
+}
+
+/// In-memory cache of the fixup files of all layers. Files are loaded when
+/// first needed. If the shared registry is watched, the whole cache is thrown
+/// away when any fixup file is changed, added or removed.
+#[derive(Debug)]
+pub struct FixupRegistry {
+    layers: Vec<FixupLayer>,
+    fingerprint: u64,
+    act_fixups: Mutex<BTreeMap<ActIdentifier, Arc<ActFixups>>>,
+    date_fixups: Mutex<BTreeMap<NaiveDate, Arc<GlobalFixups>>>,
+}
+
+impl FixupRegistry {
+    pub fn new(layers: Vec<FixupLayer>) -> Result<Self> {
+        Ok(Self {
+            fingerprint: fingerprint(&layers)?,
+            layers,
+            act_fixups: Default::default(),
+            date_fixups: Default::default(),
+        })
+    }
+
+    /// The shared registry of the configured layers (see `fixup_layers`).
+    ///
+    /// It is loaded once and then kept for the whole process, so that a CLI
+    /// run uses the same fixups from start to end. Long running processes
+    /// should call `watch_changes`.
+    pub fn get() -> Result<Arc<Self>> {
+        let mut cached = REGISTRY
+            .lock()
+            .map_err(|_| anyhow!("Fixup registry lock poisoned"))?;
+        let layers = match cached.as_mut() {
+            Some(cached) => {
+                let check_due = WATCH_CHANGES.load(Ordering::Relaxed)
+                    && cached.checked_at.elapsed() >= FINGERPRINT_CHECK_INTERVAL;
+                if !cached.stale && !check_due {
+                    return Ok(cached.registry.clone());
+                }
+                if !cached.stale
+                    && fingerprint(&cached.registry.layers)? == cached.registry.fingerprint
+                {
+                    cached.checked_at = Instant::now();
+                    return Ok(cached.registry.clone());
+                }
+                info!("Fixup files changed, reloading");
+                cached.registry.layers.clone()
+            }
+            None => fixup_layers(),
+        };
+        let registry = Arc::new(Self::new(layers)?);
+        *cached = Some(CachedRegistry {
+            registry: registry.clone(),
+            checked_at: Instant::now(),
+            stale: false,
+        });
+        Ok(registry)
+    }
+
+    /// Use `layers` for the shared registry instead of the configured ones.
+    pub fn pin(layers: Vec<FixupLayer>) -> Result<()> {
+        let registry = Arc::new(Self::new(layers)?);
+        let mut cached = REGISTRY
+            .lock()
+            .map_err(|_| anyhow!("Fixup registry lock poisoned"))?;
+        *cached = Some(CachedRegistry {
+            registry,
+            checked_at: Instant::now(),
+            stale: false,
+        });
+        Ok(())
+    }
+
+    /// Reload the shared registry when the fixup files change, checking them
+    /// at most every `FINGERPRINT_CHECK_INTERVAL`. Used by the web server, so
+    /// that fixups can be edited without restarting it.
+    pub fn watch_changes() {
+        WATCH_CHANGES.store(true, Ordering::Relaxed);
+    }
+
+    /// Force reloading the fixup files on the next `get()`, e.g. after
+    /// changing them in the same process.
+    pub fn invalidate() {
+        if let Ok(mut cached) = REGISTRY.lock() {
+            if let Some(cached) = cached.as_mut() {
+                cached.stale = true;
+            }
+        }
+    }
+
+    pub fn layers(&self) -> &[FixupLayer] {
+        &self.layers
+    }
+
+    pub fn act_fixups(&self, act_id: ActIdentifier) -> Result<Arc<ActFixups>> {
+        let mut cache = self
+            .act_fixups
+            .lock()
+            .map_err(|_| anyhow!("Fixup registry lock poisoned"))?;
+        if let Some(fixups) = cache.get(&act_id) {
+            return Ok(fixups.clone());
+        }
+        let fixups = Arc::new(ActFixups::load_from(act_id, &self.layers)?);
+        cache.insert(act_id, fixups.clone());
+        Ok(fixups)
+    }
+
+    pub fn date_fixups(&self, date: NaiveDate) -> Result<Arc<GlobalFixups>> {
+        let mut cache = self
+            .date_fixups
+            .lock()
+            .map_err(|_| anyhow!("Fixup registry lock poisoned"))?;
+        if let Some(fixups) = cache.get(&date) {
+            return Ok(fixups.clone());
+        }
+        let fixups = Arc::new(GlobalFixups::load_from(date, &self.layers)?);
+        cache.insert(date, fixups.clone());
+        Ok(fixups)
+    }
+}
+
+/// Hash of the paths, sizes and modification times of all fixup files
+fn fingerprint(layers: &[FixupLayer]) -> Result<u64> {
+    let mut hasher = SeaHasher::new();
+    for layer in layers {
+        layer.base_dir.hash(&mut hasher);
+        for path in list_fixup_files(&layer.base_dir)? {
+            let metadata = std::fs::metadata(&path)?;
+            path.hash(&mut hasher);
+            metadata.len().hash(&mut hasher);
+            metadata.modified()?.hash(&mut hasher);
+        }
+    }
+    Ok(hasher.finish())
+}
+
+/// All .yml files in the directory, recursively, sorted by path
+pub fn list_fixup_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut result = Vec::new();
+    if !dir.exists() {
+        return Ok(result);
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            result.extend(list_fixup_files(&path)?);
+        } else if path.extension().map_or(false, |e| e == "yml") {
+            result.push(path);
+        }
+    }
+    result.sort();
+    Ok(result)
+}
+
+/// Append a single entry to a fixup file as a one-element YAML list, preceded
+/// by `comment` as YAML comment lines.
+fn append_to_file<T: Serialize>(path: &Path, fixup: &T, comment: &str) -> Result<()> {
+    let mut content = if path.exists() {
+        std::fs::read_to_string(path)?
+    } else {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        String::new()
+    };
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    for line in comment.lines() {
+        content.push_str("# ");
+        content.push_str(line);
+        content.push('\n');
+    }
+    let serialized = singleton_yaml::to_string(&[fixup])?;
+    content.push_str(serialized.trim_start_matches("---\n"));
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+    std::fs::write(path, content)?;
+    FixupRegistry::invalidate();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::pin_fixup_registry;
+
+    fn write_act_fixups(layer: &FixupLayer, act_id: ActIdentifier, content: &str) {
+        let path = layer.act_path(act_id);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_layered_act_fixups() {
+        let act_id = ActIdentifier {
+            year: 2012,
+            number: 1,
+        };
+        let upstream_dir = tempfile::tempdir().unwrap();
+        let private_dir = tempfile::tempdir().unwrap();
+        let upstream = FixupLayer::new(upstream_dir.path());
+        let private = FixupLayer::new(private_dir.path());
+        write_act_fixups(
+            &upstream,
+            act_id,
+            r#"
+            - AddExpiry:
+                cause:
+                  act:
+                    year: 2012
+                    number: 1
+                  article: '5'
+                date: 2013-01-01
+            - RemoveEnforcementDate:
+                act:
+                  year: 2012
+                  number: 1
+                article: '6'
+            "#,
+        );
+        write_act_fixups(
+            &private,
+            act_id,
+            r#"
+            - AddExpiry:
+                cause:
+                  act:
+                    year: 2012
+                    number: 1
+                  article: '5'
+                date: 2014-01-01
+              note: Meghosszabbított hatály
+            "#,
+        );
+        let fixups = ActFixups::load_from(act_id, &[upstream.clone(), private.clone()]).unwrap();
+        let origins: Vec<_> = fixups.entries().iter().map(|e| &e.origin).collect();
+        assert_eq!(
+            origins,
+            vec![
+                &FixupOrigin {
+                    path: upstream.act_path(act_id),
+                    index: 1
+                },
+                &FixupOrigin {
+                    path: private.act_path(act_id),
+                    index: 0
+                },
+            ]
+        );
+        assert_eq!(
+            fixups.get_additional_expiries()[0].date,
+            NaiveDate::from_ymd(2014, 1, 1)
+        );
+        assert_eq!(
+            fixups.entries()[1].provenance().note.as_deref(),
+            Some("Meghosszabbított hatály")
+        );
+        assert_eq!(
+            fixups.conflicts(),
+            &[FixupConflict {
+                overridden: FixupOrigin {
+                    path: upstream.act_path(act_id),
+                    index: 0
+                },
+                by: FixupOrigin {
+                    path: private.act_path(act_id),
+                    index: 0
+                },
+            }]
+        );
+
+        // Without the private layer, there are no conflicts
+        let fixups = ActFixups::load_from(act_id, &[upstream]).unwrap();
+        assert_eq!(fixups.entries().len(), 2);
+        assert!(fixups.conflicts().is_empty());
+    }
+
+    #[test]
+    fn test_merge_layer_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let upstream = dir.path().join("upstream.yml");
+        let private = dir.path().join("private.yml");
+        std::fs::write(
+            &upstream,
+            r#"
+            - RemoveModification:
+                cause:
+                  Other: removed
+            - RemoveModification:
+                cause:
+                  Other: kept
+            - RemoveEnforcementDate:
+                article: '6'
+            - RemoveEnforcementDate:
+                article: '6'
+            "#,
+        )
+        .unwrap();
+        std::fs::write(
+            &private,
+            r#"
+            - ReplaceModification:
+                selector:
+                  cause:
+                    Other: removed
+                modification:
+                  TextOverride:
+                    position:
+                      act:
+                        year: 2013
+                        number: 1
+                      article: '1'
+                    text: aaa
+            - RemoveEnforcementDate:
+                article: '6'
+            "#,
+        )
+        .unwrap();
+        let mut entries: Vec<FixupEntry<ActFixup>> = Vec::new();
+        let mut conflicts = Vec::new();
+        merge_layer(&mut entries, &mut conflicts, &upstream).unwrap();
+        // Conflicts within the same file are not checked
+        assert_eq!(entries.len(), 4);
+        assert!(conflicts.is_empty());
+
+        merge_layer(&mut entries, &mut conflicts, &private).unwrap();
+        let origins: Vec<_> = entries
+            .iter()
+            .map(|e| (e.origin.path.as_path(), e.origin.index))
+            .collect();
+        assert_eq!(
+            origins,
+            vec![
+                (upstream.as_path(), 1),
+                (private.as_path(), 0),
+                (private.as_path(), 1)
+            ]
+        );
+        let conflict = |overridden: usize, by: usize| FixupConflict {
+            overridden: FixupOrigin {
+                path: upstream.clone(),
+                index: overridden,
+            },
+            by: FixupOrigin {
+                path: private.clone(),
+                index: by,
+            },
+        };
+        // A selector can only be removed or replaced once, and duplicates
+        // are conflicts too
+        assert_eq!(
+            conflicts,
+            vec![conflict(0, 0), conflict(2, 1), conflict(3, 1)]
+        );
+    }
+
+    #[test]
+    fn test_merge_layer_global_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let modification = r#"
+            - AddModification:
+                cause:
+                  Other: added
+                modification:
+                  TextOverride:
+                    position:
+                      act:
+                        year: 2013
+                        number: 1
+                      article: '1'
+                    text: aaa
+            "#;
+        let upstream = dir.path().join("upstream.yml");
+        let private = dir.path().join("private.yml");
+        std::fs::write(&upstream, modification).unwrap();
+        std::fs::write(&private, modification).unwrap();
+        let mut entries: Vec<FixupEntry<GlobalFixup>> = Vec::new();
+        let mut conflicts = Vec::new();
+        merge_layer(&mut entries, &mut conflicts, &upstream).unwrap();
+        merge_layer(&mut entries, &mut conflicts, &private).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].origin.path, private);
+        assert_eq!(conflicts.len(), 1);
+    }
+
+    #[test]
+    fn test_shared_registry_is_pinned() {
+        let act_id = ActIdentifier {
+            year: 2012,
+            number: 1,
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let layer = FixupLayer::new(dir.path());
+        let expiry = |date: &str| {
+            format!(
+                r#"
+                - AddExpiry:
+                    cause:
+                      act:
+                        year: 2012
+                        number: 1
+                      article: '5'
+                    date: {date}
+                "#
+            )
+        };
+        write_act_fixups(&layer, act_id, &expiry("2013-01-01"));
+        let _lock = pin_fixup_registry(vec![layer.clone()]);
+        let loaded_date = || ActFixups::load(act_id).unwrap().get_additional_expiries()[0].date;
+        assert_eq!(loaded_date(), NaiveDate::from_ymd(2013, 1, 1));
+
+        // Changes are not picked up automatically without watch_changes()
+        write_act_fixups(&layer, act_id, &expiry("2014-01-01"));
+        assert_eq!(loaded_date(), NaiveDate::from_ymd(2013, 1, 1));
+
+        // Invalidating keeps the pinned layers
+        FixupRegistry::invalidate();
+        assert_eq!(loaded_date(), NaiveDate::from_ymd(2014, 1, 1));
+        assert_eq!(FixupRegistry::get().unwrap().layers(), &[layer]);
+    }
+
+    #[test]
+    fn test_allow_tolerant_match() {
+        let act_id = ActIdentifier {
+            year: 2012,
+            number: 1,
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let layer = FixupLayer::new(dir.path());
+        write_act_fixups(
+            &layer,
+            act_id,
+            r#"
+            - AllowTolerantMatch:
+                selector:
+                  cause:
+                    Other: tolerant
+                max_match_level: Whitespace
+            - RemoveModification:
+                cause:
+                  Other: removed
+            "#,
+        );
+        let fixups = ActFixups::load_from(act_id, &[layer]).unwrap();
+        let mut modifications: Vec<AppliableModification> = singleton_yaml::from_str(
+            r#"
+            - cause:
+                Other: tolerant
+              modification:
+                TextOverride:
+                  position:
+                    act:
+                      year: 2013
+                      number: 1
+                    article: '1'
+                  text: aaa
+            - cause:
+                Other: other
+              modification:
+                TextOverride:
+                  position:
+                    act:
+                      year: 2013
+                      number: 1
+                    article: '2'
+                  text: bbb
+            "#,
+        )
+        .unwrap();
+        fixups.fix_modifications(&mut modifications);
+        assert_eq!(modifications[0].max_match_level, MatchLevel::Whitespace);
+        assert_eq!(
+            modifications[0].fixup,
+            Some(fixups.entries()[0].provenance())
+        );
+        assert_eq!(modifications[1].max_match_level, MatchLevel::Exact);
+        assert_eq!(modifications[1].fixup, None);
+    }
+}
@@ -16,11 +16,19 @@
 // along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
 
 pub mod amender;
+pub mod annulment;
+pub mod bitemporal;
 pub mod cache_backend;
 pub mod database;
 pub mod enforcement_date_set;
+pub mod events;
 pub mod fixups;
 pub mod persistence;
+pub mod recalculation;
+pub mod simulation;
 mod structural_cut_points;
+#[doc(hidden)]
+pub mod testing;
 pub mod util;
+pub mod validation;
 pub mod web;
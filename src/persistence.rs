@@ -160,7 +160,7 @@ impl Persistence {
     }
 
     pub fn is_link(&self, key: &PersistenceKey) -> Result<bool> {
-        Ok(self.cache.contains(key) || self.path_for(key).is_symlink())
+        Ok(self.path_for(key).is_symlink())
     }
 
     pub fn link(&self, from: &PersistenceKey, to: &PersistenceKey) -> Result<()> {
@@ -188,10 +188,77 @@ impl Persistence {
             })?,
             to_path,
         )?;
-        // TODO: cache
+        // The cached data may be the one stored at `to` before linking
+        self.cache.remove(to);
         Ok(())
     }
 
+    /// Remove the data stored at key. Does nothing if there is no data.
+    pub fn remove(&self, key: &PersistenceKey) -> Result<()> {
+        self.cache.remove(key);
+        let path = self.path_for(key);
+        if path.exists() || path.is_symlink() {
+            fs::remove_file(&path).with_context(|| anyhow!("Removing {key} failed"))?;
+        }
+        Ok(())
+    }
+
+    /// All stored keys starting with `prefix` (without trailing slash), sorted.
+    pub fn list_keys(&self, prefix: &str) -> Result<Vec<PersistenceKey>> {
+        let mut result = Vec::new();
+        let mut dirs = vec![self.persistence_dir.join(prefix)];
+        while let Some(dir) = dirs.pop() {
+            if !dir.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if let Some(key) = self.key_for(&path)? {
+                    result.push(key);
+                }
+            }
+        }
+        result.sort();
+        Ok(result)
+    }
+
+    /// The last key of `list_keys(prefix)`, without listing all of them.
+    pub fn last_key(&self, prefix: &str) -> Result<Option<PersistenceKey>> {
+        self.last_key_in(&self.persistence_dir.join(prefix))
+    }
+
+    fn last_key_in(&self, dir: &Path) -> Result<Option<PersistenceKey>> {
+        if !dir.is_dir() {
+            return Ok(None);
+        }
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>>>()?;
+        paths.sort();
+        for path in paths.into_iter().rev() {
+            let key = if path.is_dir() {
+                self.last_key_in(&path)?
+            } else {
+                self.key_for(&path)?
+            };
+            if key.is_some() {
+                return Ok(key);
+            }
+        }
+        Ok(None)
+    }
+
+    /// The key of a stored file, None for other files (e.g. temporary ones)
+    fn key_for(&self, path: &Path) -> Result<Option<PersistenceKey>> {
+        Ok(path
+            .strip_prefix(&self.persistence_dir)?
+            .to_str()
+            .and_then(|p| p.strip_suffix(".json.gz"))
+            .map(|key| key.to_owned()))
+    }
+
     fn path_for(&self, key: &str) -> PathBuf {
         self.persistence_dir.join(format!("{}.json.gz", key))
     }
@@ -225,3 +292,68 @@ impl Persistence {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forced(key: &str) -> KeyType {
+        KeyType::Forced(key.to_owned())
+    }
+
+    #[test]
+    fn test_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let persistence = Persistence::new(dir.path());
+        let from = "state/2098/01/01".to_owned();
+        let to = "state/2098/01/02".to_owned();
+        persistence.store(forced(&from), &1).unwrap();
+        persistence.store(forced(&to), &2).unwrap();
+        // Stored in this process, so it is in the cache
+        assert!(!persistence.is_link(&to).unwrap());
+
+        persistence.link(&from, &to).unwrap();
+        assert!(persistence.is_link(&to).unwrap());
+        assert!(!persistence.is_link(&from).unwrap());
+        assert_eq!(persistence.load::<i32>(&to).unwrap(), 1);
+
+        persistence.store(forced(&to), &3).unwrap();
+        assert!(!persistence.is_link(&to).unwrap());
+        assert_eq!(persistence.load::<i32>(&from).unwrap(), 1);
+        assert_eq!(Persistence::new(dir.path()).load::<i32>(&to).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_last_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let persistence = Persistence::new(dir.path());
+        assert_eq!(persistence.last_key("state").unwrap(), None);
+        for key in ["state/2097/12/31", "state/2098/02/01", "state/2098/01/05"] {
+            persistence.store(forced(key), &0).unwrap();
+        }
+        // Empty directories and other files are skipped
+        fs::create_dir_all(dir.path().join("state/2098/03")).unwrap();
+        fs::write(dir.path().join("state/2098/02/02.json.gz.tmp"), "").unwrap();
+        assert_eq!(
+            persistence.last_key("state").unwrap(),
+            Some("state/2098/02/01".to_owned())
+        );
+        assert_eq!(
+            persistence.last_key("state").unwrap().as_ref(),
+            persistence.list_keys("state").unwrap().last()
+        );
+    }
+
+    #[test]
+    fn test_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let persistence = Persistence::new(dir.path());
+        let key = "act_metadata/2098/1".to_owned();
+        persistence.store(forced(&key), &1).unwrap();
+        persistence.remove(&key).unwrap();
+        assert!(!persistence.exists(&key).unwrap());
+        assert!(persistence.load::<i32>(&key).is_err());
+        // Removing it again is not an error
+        persistence.remove(&key).unwrap();
+    }
+}
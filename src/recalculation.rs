@@ -0,0 +1,172 @@
+// Copyright (c) 2022-2023, Alex Badics
+//
+// This file is part of AJDB
+//
+// AJDB is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// AJDB is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Calculating the state of a date from the state of the day before, by
+//! applying everything that comes into force on that date.
+//!
+//! The same routine is used for the stored states (`ajdb recalculate`), and
+//! for the in-memory replays of the bitemporal queries (`KnownState`).
+
+use anyhow::Result;
+use chrono::NaiveDate;
+use hun_law::{identifier::ActIdentifier, structure::Act};
+use log::info;
+
+use crate::{
+    amender::{AppliableModificationSet, OnError},
+    annulment::AnnulmentSet,
+    database::{ActMetadata, ActSet},
+    events::EventRegistry,
+    persistence::Persistence,
+};
+
+/// A state that can be recalculated with `recalculate_date`.
+pub trait RecalculatedState {
+    fn act_ids(&self) -> Result<Vec<ActIdentifier>>;
+    fn has_act(&self, id: ActIdentifier) -> bool;
+    /// Returns true if anything comes into force in the act on the date or
+    /// the day before it.
+    fn is_date_interesting(
+        &self,
+        id: ActIdentifier,
+        date: NaiveDate,
+        events: &EventRegistry,
+    ) -> Result<bool>;
+    fn act(&self, id: ActIdentifier) -> Result<Act>;
+    /// Apply the modifications of the act in `modifications` (if any), and
+    /// record them in its change history.
+    fn apply_modifications(
+        &mut self,
+        id: ActIdentifier,
+        date: NaiveDate,
+        modifications: &AppliableModificationSet,
+    ) -> Result<()>;
+}
+
+impl RecalculatedState for ActSet<'_> {
+    fn act_ids(&self) -> Result<Vec<ActIdentifier>> {
+        Ok(self.get_acts()?.iter().map(|ae| ae.identifier()).collect())
+    }
+
+    fn has_act(&self, id: ActIdentifier) -> bool {
+        ActSet::has_act(self, id)
+    }
+
+    fn is_date_interesting(
+        &self,
+        id: ActIdentifier,
+        date: NaiveDate,
+        events: &EventRegistry,
+    ) -> Result<bool> {
+        Ok(self.get_act(id)?.is_date_interesting(date, events))
+    }
+
+    fn act(&self, id: ActIdentifier) -> Result<Act> {
+        self.get_act(id)?.act()
+    }
+
+    fn apply_modifications(
+        &mut self,
+        id: ActIdentifier,
+        date: NaiveDate,
+        modifications: &AppliableModificationSet,
+    ) -> Result<()> {
+        modifications.apply_to_act_in_state(id, date, self, OnError::Warn)
+    }
+}
+
+/// Apply everything that comes into force on `date` to `state`, which should
+/// be the state of the day before, plus the acts published on `date`.
+///
+/// Only the retroactive acts published up to `known_on` are considered, or
+/// all of them if it is None.
+///
+/// Returns the acts that were modified, or had something come into force.
+pub fn recalculate_date(
+    state: &mut impl RecalculatedState,
+    persistence: &Persistence,
+    date: NaiveDate,
+    annulments: &AnnulmentSet,
+    events: &EventRegistry,
+    known_on: Option<NaiveDate>,
+) -> Result<Vec<ActIdentifier>> {
+    let mut act_ids = Vec::new();
+    for act_id in state.act_ids()? {
+        if state.is_date_interesting(act_id, date, events)? {
+            act_ids.push(act_id);
+        }
+    }
+    let mut modifications = AppliableModificationSet::default();
+    modifications.add_fixups(date)?;
+    modifications.add_annulments(annulments, date)?;
+    // Acts already in the state are processed normally
+    modifications.add_retroactive(persistence, date, |act_id, publication_date| {
+        !state.has_act(act_id) && known_on.map_or(true, |known_on| publication_date <= known_on)
+    })?;
+    if act_ids.is_empty() && modifications.affected_acts().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // NOTE: It's important to go in reverse, since there may be later acts
+    //       that modify earlier acts on the same enforcement day.
+    //       E.g. 2020. évi LXXIV. törvény.yml modifies 2020. évi XLIII. törvény.yml,
+    //       both with enforcement dates 2021-01-01, leading to a conflict in Btk.
+    act_ids.sort();
+    act_ids.reverse();
+
+    for act_id in &act_ids {
+        // NOTE: And then there's the case where an Act is modified by one Act, and then another,
+        //       Both coming into force at the same time. This is resolved by the internal
+        //       ordering fix in modifications.apply_to_act(...)
+        state.apply_modifications(*act_id, date, &modifications)?;
+        modifications.remove_affecting(*act_id);
+        modifications.add(&state.act(*act_id)?, date)?;
+    }
+
+    let mut modified_acts = act_ids; //no clone necessary
+    for act_id in modifications.affected_acts() {
+        if state.has_act(act_id) {
+            state.apply_modifications(act_id, date, &modifications)?;
+            modified_acts.push(act_id);
+        }
+    }
+    Ok(modified_acts)
+}
+
+/// Recalculate the stored state of `date` from the stored state of the day
+/// before, and record the date in the metadata of the modified acts.
+pub fn recalculate_stored_date(
+    persistence: &Persistence,
+    annulments: &AnnulmentSet,
+    events: &EventRegistry,
+    date: NaiveDate,
+) -> Result<()> {
+    info!("Recalculating {}", date);
+    ActSet::copy(persistence, date.pred(), date)?;
+    let mut state = ActSet::load(persistence, date)?;
+    let modified_acts = recalculate_date(&mut state, persistence, date, annulments, events, None)?;
+    if modified_acts.is_empty() {
+        return Ok(());
+    }
+    for act_id in modified_acts {
+        let mut act_metadata = ActMetadata::load(persistence, act_id)?;
+        act_metadata.add_modification_date(date)?;
+        act_metadata.save()?;
+    }
+    state.save()?;
+    Ok(())
+}
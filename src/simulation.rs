@@ -0,0 +1,336 @@
+// Copyright (c) 2022-2023, Alex Badics
+//
+// This file is part of AJDB
+//
+// AJDB is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// AJDB is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
+
+//! What-if simulation of draft acts (e.g. "törvényjavaslat") that are not
+//! part of the database.
+//!
+//! The modifications of the draft are applied to the acts as they are stored
+//! in the database, and the results are kept in memory only.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use chrono::NaiveDate;
+use hun_law::{identifier::ActIdentifier, structure::Act, util::debug::WithElemContext};
+
+use crate::{
+    amender::{
+        extract::extract_modifications_with_fixups, AppliableModification,
+        AppliableModificationSet, ModificationObserver, NeedsFullReparse, OnError,
+    },
+    database::ActSet,
+    enforcement_date_set::EnforcementDateSet,
+    fixups::ActFixups,
+    util::read_all,
+};
+
+/// Drafts available in the web interface, in `<name>.yml` files
+pub const DRAFTS_DIR: &str = "./data/drafts/";
+
+pub fn load_draft(path: &Path) -> Result<Act> {
+    hun_law::util::singleton_yaml::from_slice(
+        &read_all(path).with_context(|| anyhow!("Error reading {path:?}"))?,
+    )
+    .with_context(|| anyhow!("Error deserializing {path:?}"))
+}
+
+/// Load a draft from `DRAFTS_DIR` by name (file name without the extension)
+pub fn load_named_draft(name: &str) -> Result<Act> {
+    ensure!(
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || "_-".contains(c)),
+        "Invalid draft name: {name:?}"
+    );
+    load_draft(&PathBuf::from(DRAFTS_DIR).join(format!("{name}.yml")))
+}
+
+/// Names of the drafts in `DRAFTS_DIR`, sorted.
+pub fn list_drafts() -> Result<Vec<String>> {
+    let dir = Path::new(DRAFTS_DIR);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut result = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().map_or(false, |ext| ext == "yml") {
+            if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                result.push(name.to_owned());
+            }
+        }
+    }
+    result.sort();
+    Ok(result)
+}
+
+/// The earliest date any part of the draft comes into force, used when no
+/// date was explicitly specified.
+pub fn first_enforcement_date(draft: &Act) -> Result<NaiveDate> {
+    EnforcementDateSet::from_act(draft)?
+        .get_all_dates()
+        .into_iter()
+        .min()
+        .ok_or_else(|| anyhow!("No enforcement dates in {}", draft.identifier))
+}
+
+/// The result of applying a draft to the database state of a single date.
+///
+/// Nothing is stored in the database: the modified acts are only kept in
+/// this overlay.
+#[derive(Debug)]
+pub struct Simulation {
+    pub draft_id: ActIdentifier,
+    pub date: NaiveDate,
+    pub acts: BTreeMap<ActIdentifier, SimulatedAct>,
+    /// Acts that would be modified by the draft, but are not in the database
+    pub missing_acts: Vec<ActIdentifier>,
+}
+
+#[derive(Debug)]
+pub struct SimulatedAct {
+    pub before: Act,
+    pub after: Act,
+    pub modifications: Vec<AppliableModification>,
+    /// Modifications that could not be applied, with the error message
+    pub failures: Vec<(AppliableModification, String)>,
+}
+
+impl Simulation {
+    /// Apply the modifications of `draft` that come into force on `date` to
+    /// the acts in `state`, which should be the stored state at `date`, i.e.
+    /// the real modifications of the day are already applied.
+    pub fn run(state: &ActSet, draft: &Act, date: NaiveDate) -> Result<Self> {
+        // Drafts do not have fixups, they are not in data/fixups
+        let modifications = extract_modifications_with_fixups(draft, date, &ActFixups::default())
+            .with_elem_context("Error extracting modifications", draft)?;
+        if modifications.is_empty() {
+            let mut dates = EnforcementDateSet::from_act(draft)?.get_all_dates();
+            dates.sort();
+            dates.dedup();
+            bail!(
+                "{} has no modifications coming into force on {date}. Its enforcement dates are: {}",
+                draft.identifier,
+                dates
+                    .iter()
+                    .map(|d| d.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        let mut modification_set = AppliableModificationSet::default();
+        modification_set.add_modifications(modifications)?;
+
+        let mut result = Self {
+            draft_id: draft.identifier,
+            date,
+            acts: BTreeMap::new(),
+            missing_acts: Vec::new(),
+        };
+        for (act_id, modifications) in modification_set.get_modifications() {
+            if act_id == draft.identifier {
+                // Automatic repeals of the draft's own amending provisions
+                continue;
+            }
+            if !state.has_act(act_id) {
+                result.missing_acts.push(act_id);
+                continue;
+            }
+            let before = state.get_act(act_id)?.act()?;
+            let mut after = before.clone();
+            let mut observer = FailureCollector::default();
+            AppliableModificationSet::apply_to_act_observed(
+                &mut after,
+                date,
+                modifications.clone(),
+                OnError::Warn,
+                Some(&mut observer),
+            )?;
+            result.acts.insert(
+                act_id,
+                SimulatedAct {
+                    before,
+                    after,
+                    modifications,
+                    failures: observer.failures,
+                },
+            );
+        }
+        result.missing_acts.sort();
+        Ok(result)
+    }
+
+    pub fn has_failures(&self) -> bool {
+        !self.missing_acts.is_empty() || self.acts.values().any(|a| !a.failures.is_empty())
+    }
+}
+
+#[derive(Debug, Default)]
+struct FailureCollector {
+    failures: Vec<(AppliableModification, String)>,
+}
+
+impl ModificationObserver for FailureCollector {
+    fn on_applied(
+        &mut self,
+        modification: &AppliableModification,
+        _act_before: &Act,
+        _act_after: &Act,
+        result: &Result<NeedsFullReparse>,
+    ) {
+        if let Err(err) = result {
+            self.failures
+                .push((modification.clone(), format!("{err:#}")));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hun_law::{
+        structure::{ActChild, SAEBody},
+        util::singleton_yaml,
+    };
+
+    use super::*;
+    use crate::persistence::Persistence;
+    use crate::testing::pin_fixup_registry;
+
+    fn parse_act(yaml: &str) -> Act {
+        let mut act: Act = singleton_yaml::from_str(yaml).unwrap();
+        act.add_semantic_info().unwrap();
+        act
+    }
+
+    fn paragraph_texts(act: &Act) -> Vec<String> {
+        let mut result = Vec::new();
+        for child in &act.children {
+            if let ActChild::Article(article) = child {
+                for paragraph in &article.children {
+                    if let SAEBody::Text(text) = &paragraph.body {
+                        result.push(text.clone());
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn test_failing_draft() {
+        let _lock = pin_fixup_registry(Vec::new());
+        let dir = tempfile::tempdir().unwrap();
+        let persistence = Persistence::new(dir.path());
+        let date = NaiveDate::from_ymd(2099, 3, 1);
+        let amended = parse_act(
+            r#"
+            identifier:
+              year: 2098
+              number: 1
+            subject: Az AJDB teszteléséről
+            preamble: ''
+            publication_date: 2098-01-01
+            children:
+            - Article:
+                identifier: '1'
+                children:
+                - identifier: '1'
+                  body: A miniszter rendeletben állapítja meg.
+                - identifier: '2'
+                  body: A részleteket a miniszter határozza meg.
+            "#,
+        );
+        let original_texts = paragraph_texts(&amended);
+        let mut state = ActSet::load(&persistence, date).unwrap();
+        state.store_act(amended).unwrap();
+        state.save().unwrap();
+
+        let draft = parse_act(
+            r#"
+            identifier:
+              year: 2099
+              number: 1
+            subject: Az AJDB teszteléséről szóló törvény módosításáról
+            preamble: ''
+            publication_date: 2099-01-15
+            children:
+            - Article:
+                identifier: '1'
+                children:
+                - identifier: '1'
+                  body: Az AJDB teszteléséről szóló 2098. évi I. törvény 1. § (1) bekezdésében a „miniszter” szövegrész helyébe a „Kormány” szöveg lép.
+            - Article:
+                identifier: '2'
+                children:
+                - identifier: '1'
+                  body: Az AJDB teszteléséről szóló 2098. évi I. törvény 1. § (2) bekezdésében a „nem létező” szövegrész helyébe a „más” szöveg lép.
+            - Article:
+                identifier: '3'
+                children:
+                - identifier: '1'
+                  body: Az AJDB módosításáról szóló 2098. évi II. törvény 1. § (1) bekezdésében a „miniszter” szövegrész helyébe a „Kormány” szöveg lép.
+            - Article:
+                identifier: '4'
+                children:
+                - identifier: '1'
+                  body: Ez a törvény 2099. március 1-jén lép hatályba.
+            "#,
+        );
+        assert_eq!(first_enforcement_date(&draft).unwrap(), date);
+
+        let state = ActSet::load(&persistence, date).unwrap();
+        let simulation = Simulation::run(&state, &draft, date).unwrap();
+        assert!(simulation.has_failures());
+        assert_eq!(
+            simulation.missing_acts,
+            vec![ActIdentifier {
+                year: 2098,
+                number: 2
+            }]
+        );
+        let simulated = &simulation.acts[&ActIdentifier {
+            year: 2098,
+            number: 1,
+        }];
+        assert_eq!(simulated.modifications.len(), 2);
+        assert_eq!(simulated.failures.len(), 1);
+        assert!(simulated.failures[0].1.contains("nem létező"));
+        // The modifications that do apply are still applied
+        assert_eq!(
+            paragraph_texts(&simulated.after),
+            vec![
+                "A Kormány rendeletben állapítja meg.".to_string(),
+                original_texts[1].clone(),
+            ]
+        );
+        assert_eq!(paragraph_texts(&simulated.before), original_texts);
+
+        // Nothing is written to the database
+        let stored = ActSet::load(&persistence, date)
+            .unwrap()
+            .get_act(simulated.after.identifier)
+            .unwrap()
+            .act()
+            .unwrap();
+        assert_eq!(paragraph_texts(&stored), original_texts);
+    }
+}
@@ -330,6 +330,19 @@ fn handle_subtitle_title(
     })
 }
 
+/// Cut points of an article range.
+///
+/// The range may cross subtitle and structural element boundaries, e.g.
+/// "a 12–20. § helyébe a következő rendelkezések lépnek", where a new subtitle
+/// or chapter starts at 15. §. The policy for headings is:
+/// * Headings before the first article of the range are kept. They are part of
+///   the range only if explicitly referenced (e.g. `SubtitleBeforeArticleInclusive`)
+/// * Headings after the last article of the range are kept, as they belong to
+///   the articles following the range.
+/// * Headings between the first and last articles of the range are cut out,
+///   i.e. replaced by the content of the amendment, which is expected to
+///   restate them. In case of repeals, see `heading_section_end` for which of
+///   these are kept.
 fn handle_article_range(
     children: &[ActChild],
     range: &IdentifierRange<ArticleIdentifier>,
@@ -342,11 +355,7 @@ fn handle_article_range(
             |_child| true,
         )
     } else {
-        get_cut_points(
-            children,
-            |child| get_article_id(child).map_or(false, |id| range.contains(id)),
-            |child| get_article_id(child).map_or(true, |id| !range.contains(id)),
-        )
+        find_article_range_offsets(children, range)
     }
     .with_context(|| {
         anyhow!(
@@ -357,6 +366,37 @@ fn handle_article_range(
     })
 }
 
+fn find_article_range_offsets(
+    children: &[ActChild],
+    range: &IdentifierRange<ArticleIdentifier>,
+) -> Result<Range<usize>> {
+    let in_range = |child: &ActChild| get_article_id(child).map_or(false, |id| range.contains(id));
+    let cut_start = children
+        .iter()
+        .position(in_range)
+        .ok_or_else(|| anyhow!("Could not find starting cut point"))?;
+    let cut_end = children
+        .iter()
+        .rposition(in_range)
+        .ok_or_else(|| anyhow!("Could not find ending cut point"))?
+        + 1;
+    if let Some(id) = children[cut_start..cut_end]
+        .iter()
+        .filter_map(get_article_id)
+        .find(|id| !range.contains(*id))
+    {
+        bail!("Article {} is out of order, it is inside the range", id);
+    }
+    Ok(cut_start..cut_end)
+}
+
+/// Cut points of a subtitle relative to an article.
+///
+/// In case of `AfterArticle`, headings of structural elements between the
+/// article and the subtitle are skipped (and kept), e.g. if the article is the
+/// last one of its chapter, "a 12. §-t követő alcím" is the first subtitle of
+/// the next chapter. In the other cases, the subtitle must directly precede the
+/// article, as a subtitle followed by a structural element has no articles.
 fn handle_article_relative(
     children: &[ActChild],
     article_id: ArticleIdentifier,
@@ -392,7 +432,14 @@ fn handle_article_relative(
             .position(|child| get_article_id(child) == Some(article_id))
             .ok_or_else(|| anyhow!("Could not find Article {}", article_id))?;
         let (cut_start, cut_end) = match subtitle_position {
-            SubtitlePosition::AfterArticle => (article_position + 1, article_position + 2),
+            SubtitlePosition::AfterArticle => {
+                let subtitle_position = children
+                    .iter()
+                    .skip(article_position + 1)
+                    .position(|child| !matches!(child, ActChild::StructuralElement(_)))
+                    .map_or(children.len(), |p| p + article_position + 1);
+                (subtitle_position, subtitle_position + 1)
+            }
             // "A Btk. 83. §-t megelőző alcím helyébe a következő alcím lép:"
             SubtitlePosition::BeforeArticle => {
                 (article_position.saturating_sub(1), article_position)
@@ -419,6 +466,34 @@ fn handle_article_relative(
     })
 }
 
+/// The end of the section introduced by the heading at `index`: the index of
+/// the next subtitle or structural element of the same or higher level.
+/// Returns `None` if the element at `index` is not a heading.
+///
+/// Used for repealing article ranges: headings inside the range are only
+/// removed if all of their section is repealed.
+pub fn heading_section_end(children: &[ActChild], index: usize) -> Option<usize> {
+    let heading = children.get(index)?;
+    if let ActChild::Article(_) = heading {
+        return None;
+    }
+    let end_fn = |child: &ActChild| match (heading, child) {
+        (_, ActChild::Article(_)) => false,
+        (ActChild::StructuralElement(heading), ActChild::StructuralElement(se)) => {
+            se.element_type <= heading.element_type
+        }
+        (ActChild::StructuralElement(_), ActChild::Subtitle(_)) => false,
+        _ => true,
+    };
+    Some(
+        children
+            .iter()
+            .skip(index + 1)
+            .position(end_fn)
+            .map_or(children.len(), |p| p + index + 1),
+    )
+}
+
 fn get_subtitle_id(child: &ActChild) -> Option<NumericIdentifier> {
     if let ActChild::Subtitle(Subtitle {
         identifier: Some(identifier),
@@ -781,7 +856,7 @@ mod tests {
             11..12
         );
 
-        // Known limitation: Amendment stops at subtitles and structural elements
+        // Headings after the range are kept
         assert_eq!(
             handle_article_range(
                 children,
@@ -791,6 +866,7 @@ mod tests {
             .unwrap(),
             3..7
         );
+        // Subtitles inside the range are replaced, the one before it is kept
         assert_eq!(
             handle_article_range(
                 children,
@@ -798,7 +874,26 @@ mod tests {
                 false
             )
             .unwrap(),
-            9..10
+            9..12
+        );
+        // Crossing a structural element
+        assert_eq!(
+            handle_article_range(
+                children,
+                &IdentifierRange::from_range("2".parse().unwrap(), "3".parse().unwrap()),
+                false
+            )
+            .unwrap(),
+            5..10
+        );
+        assert_eq!(
+            handle_article_range(
+                children,
+                &IdentifierRange::from_range("1".parse().unwrap(), "4".parse().unwrap()),
+                false
+            )
+            .unwrap(),
+            2..12
         );
 
         // --- Insertions ---
@@ -877,6 +972,31 @@ mod tests {
             .unwrap(),
             3..5
         );
+        // Structural element headings between the article and the subtitle are skipped
+        assert_eq!(
+            handle_article_relative(
+                children,
+                "2".parse().unwrap(),
+                SubtitlePosition::AfterArticle,
+                false
+            )
+            .unwrap(),
+            6..7
+        );
+        assert!(handle_article_relative(
+            children,
+            "4".parse().unwrap(),
+            SubtitlePosition::AfterArticle,
+            false
+        )
+        .is_err());
+        assert!(handle_article_relative(
+            children,
+            "3".parse().unwrap(),
+            SubtitlePosition::BeforeArticleInclusive,
+            false
+        )
+        .is_ok());
 
         // --- Insertions ---
         assert_eq!(
@@ -931,6 +1051,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_heading_section_end() {
+        let children: &[ActChild] = &[
+            quick_structural_element(1, StructuralElementType::Title),
+            quick_structural_element(1, StructuralElementType::Chapter),
+            quick_subtitle(1, "ST 1"),
+            quick_article("1"),
+            quick_subtitle(2, "ST 2"),
+            quick_article("2"),
+            quick_structural_element(2, StructuralElementType::Chapter),
+            quick_article("3"),
+            quick_structural_element(2, StructuralElementType::Title),
+            quick_article("4"),
+        ];
+        assert_eq!(heading_section_end(children, 0), Some(8));
+        assert_eq!(heading_section_end(children, 1), Some(6));
+        assert_eq!(heading_section_end(children, 2), Some(4));
+        assert_eq!(heading_section_end(children, 4), Some(6));
+        assert_eq!(heading_section_end(children, 6), Some(8));
+        assert_eq!(heading_section_end(children, 8), Some(10));
+        assert_eq!(heading_section_end(children, 3), None);
+        assert_eq!(heading_section_end(children, 10), None);
+    }
+
     fn quick_structural_element(id: u16, element_type: StructuralElementType) -> ActChild {
         StructuralElement {
             identifier: id.into(),
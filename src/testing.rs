@@ -0,0 +1,107 @@
+// Copyright (c) 2022-2023, Alex Badics
+//
+// This file is part of AJDB
+//
+// AJDB is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// AJDB is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Fixtures shared by the tests of the library and the `ajdb` binary. Not
+//! part of the API.
+
+use std::sync::{Mutex, MutexGuard};
+
+use hun_law::{identifier::ActIdentifier, structure::Act, util::singleton_yaml};
+
+use crate::fixups::{FixupLayer, FixupRegistry};
+
+static FIXUP_REGISTRY_LOCK: Mutex<()> = Mutex::new(());
+
+/// Lock the shared `FixupRegistry` for the rest of the test, and pin it to
+/// `layers`.
+///
+/// Every test that uses the shared registry has to do this, including the
+/// ones that only read it by extracting modifications or enforcement dates,
+/// as tests run in parallel.
+pub fn pin_fixup_registry(layers: Vec<FixupLayer>) -> MutexGuard<'static, ()> {
+    let guard = FIXUP_REGISTRY_LOCK
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    FixupRegistry::pin(layers).unwrap();
+    guard
+}
+
+pub const AMENDED: ActIdentifier = ActIdentifier {
+    year: 2098,
+    number: 1,
+};
+pub const AMENDING: ActIdentifier = ActIdentifier {
+    year: 2098,
+    number: 2,
+};
+
+/// Published on 2098-01-01, in force from 2098-01-02.
+pub fn amended_act() -> Act {
+    parse_act(
+        r#"
+        identifier:
+          year: 2098
+          number: 1
+        subject: Az AJDB teszteléséről
+        preamble: ''
+        publication_date: 2098-01-01
+        children:
+        - Article:
+            identifier: '1'
+            children:
+            - identifier: '1'
+              body: A miniszter rendeletben állapítja meg.
+        - Article:
+            identifier: '2'
+            children:
+            - identifier: '1'
+              body: Ez a törvény 2098. január 2-án lép hatályba.
+        "#,
+    )
+}
+
+/// Published on 2098-02-01, replaces "miniszter" with "Kormány" in the first
+/// paragraph of `amended_act` from 2098-02-02.
+pub fn amending_act() -> Act {
+    parse_act(
+        r#"
+        identifier:
+          year: 2098
+          number: 2
+        subject: Az AJDB teszteléséről szóló törvény módosításáról
+        preamble: ''
+        publication_date: 2098-02-01
+        children:
+        - Article:
+            identifier: '1'
+            children:
+            - identifier: '1'
+              body: Az AJDB teszteléséről szóló 2098. évi I. törvény 1. § (1) bekezdésében a „miniszter” szövegrész helyébe a „Kormány” szöveg lép.
+        - Article:
+            identifier: '2'
+            children:
+            - identifier: '1'
+              body: Ez a törvény 2098. február 2-án lép hatályba.
+        "#,
+    )
+}
+
+fn parse_act(yaml: &str) -> Act {
+    let mut act: Act = singleton_yaml::from_str(yaml).unwrap();
+    act.add_semantic_info().unwrap();
+    act
+}
@@ -0,0 +1,185 @@
+// Copyright (c) 2022-2023, Alex Badics
+//
+// This file is part of AJDB
+//
+// AJDB is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// AJDB is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Checks done on raw acts before they are added to the database.
+//!
+//! Problems found here would otherwise only show up during recalculation,
+//! or when the act is rendered in the web interface.
+
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use hun_law::{
+    identifier::{ActIdentifier, IdentifierCommon},
+    reference::Reference,
+    structure::{Act, ChildrenCommon, SAEBody, SubArticleElement},
+    util::walker::{SAEVisitor, WalkSAE},
+};
+
+use crate::{
+    amender::{extract::extract_modifications_from_act, repeal::get_act_repeal, AffectedAct},
+    enforcement_date_set::EnforcementDateSet,
+};
+
+#[derive(Debug, Default)]
+pub struct ActValidation {
+    /// Problems that would make the recalculation or the rendering of the act fail
+    pub errors: Vec<String>,
+    /// Problems that are worked around, but the result may be inaccurate.
+    /// Does not include the warnings of the enforcement dates.
+    pub warnings: Vec<String>,
+    /// Acts modified by the act that are not in the database. Their
+    /// modifications are skipped during recalculation.
+    pub unknown_targets: BTreeSet<ActIdentifier>,
+}
+
+impl ActValidation {
+    /// `is_known` should return true for every act in the database.
+    pub fn validate(act: &Act, is_known: impl Fn(ActIdentifier) -> bool) -> Result<Self> {
+        let mut result = Self::default();
+        // Same condition as in CachedEnforcementDates::from_act: there is nothing to
+        // come into force in these acts.
+        if !act.children.is_empty() && get_act_repeal(act).is_none() {
+            result.check_modifications(act, is_known);
+        }
+        let mut checker = ReferenceChecker::default();
+        act.walk_saes(&mut checker)?;
+        result.errors.extend(checker.errors);
+        result.warnings.extend(checker.warnings);
+        Ok(result)
+    }
+
+    fn check_modifications(&mut self, act: &Act, is_known: impl Fn(ActIdentifier) -> bool) {
+        let ed_set = match EnforcementDateSet::from_act(act) {
+            Ok(ed_set) => ed_set,
+            Err(err) => {
+                self.errors
+                    .push(format!("Could not calculate enforcement dates: {err:#}"));
+                return;
+            }
+        };
+        // The warnings of the enforcement dates are logged by from_act, and
+        // are also shown in the web interface.
+        let mut dates = ed_set.get_all_dates();
+        dates.sort();
+        dates.dedup();
+        for date in dates {
+            let modifications = match extract_modifications_from_act(act, date) {
+                Ok(modifications) => modifications,
+                Err(err) => {
+                    self.errors.push(format!(
+                        "Could not extract the modifications coming into force on {date}: {err:#}"
+                    ));
+                    continue;
+                }
+            };
+            for modification in modifications {
+                match modification.affected_act() {
+                    Ok(act_id) if act_id != act.identifier && !is_known(act_id) => {
+                        self.unknown_targets.insert(act_id);
+                    }
+                    Ok(_) => (),
+                    Err(err) => self.errors.push(format!(
+                        "Could not determine the act modified on {date} by {:?}: {err:#}",
+                        modification.cause
+                    )),
+                }
+            }
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Checks the outgoing references in the semantic info against the texts
+/// they are in, and their positions.
+#[derive(Debug, Default)]
+struct ReferenceChecker {
+    errors: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl SAEVisitor for ReferenceChecker {
+    fn on_enter<IT: IdentifierCommon, CT: ChildrenCommon>(
+        &mut self,
+        position: &Reference,
+        element: &SubArticleElement<IT, CT>,
+    ) -> Result<()> {
+        // Same text as the one the links are rendered into in the web interface
+        let text = match &element.body {
+            SAEBody::Text(text) => text,
+            SAEBody::Children { intro, .. } => intro,
+        };
+        for outgoing_reference in &element.semantic_info.outgoing_references {
+            let (start, end) = (outgoing_reference.start, outgoing_reference.end);
+            if start >= end || text.get(start..end).is_none() {
+                self.errors.push(format!(
+                    "{position}: Invalid outgoing reference position {start}..{end} in text {text:?}"
+                ));
+            }
+            if outgoing_reference.reference.relative_to(position).is_err() {
+                self.warnings.push(format!(
+                    "{position}: Outgoing reference {} cannot be resolved",
+                    outgoing_reference.reference
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hun_law::structure::ActChild;
+
+    use super::*;
+    use crate::testing::{amending_act, pin_fixup_registry, AMENDED};
+
+    #[test]
+    fn test_unknown_targets() {
+        let _lock = pin_fixup_registry(Vec::new());
+        let act = amending_act();
+        let validation = ActValidation::validate(&act, |_| false).unwrap();
+        assert!(validation.is_ok(), "{:?}", validation.errors);
+        // The act itself is not unknown, even though it repeals its own
+        // amending provisions
+        assert_eq!(validation.unknown_targets, [AMENDED].into_iter().collect());
+
+        let validation = ActValidation::validate(&act, |act_id| act_id == AMENDED).unwrap();
+        assert!(validation.is_ok(), "{:?}", validation.errors);
+        assert!(validation.unknown_targets.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_reference_position() {
+        let _lock = pin_fixup_registry(Vec::new());
+        let mut act = amending_act();
+        match &mut act.children[0] {
+            ActChild::Article(article) => {
+                article.children[0].semantic_info.outgoing_references[0].end = 1000
+            }
+            _ => panic!("Unexpected act child"),
+        }
+        let validation = ActValidation::validate(&act, |_| true).unwrap();
+        assert!(!validation.is_ok());
+        assert_eq!(validation.errors.len(), 1, "{:?}", validation.errors);
+        assert!(validation.errors[0].contains("Invalid outgoing reference position"));
+        assert!(validation.unknown_targets.is_empty());
+    }
+}
@@ -27,7 +27,7 @@ use chrono::{Duration, NaiveDate};
 use hun_law::{
     identifier::ActIdentifier,
     reference::to_element::ReferenceToElement,
-    structure::{Act, ActChild, StructuralElement, StructuralElementType},
+    structure::{Act, ActChild, LastChange, StructuralElement, StructuralElementType},
 };
 use maud::{html, Markup, PreEscaped};
 use serde::Deserialize;
@@ -42,15 +42,21 @@ use super::{
     ConvertToParts,
 };
 use crate::{
+    amender::{change_history::ChangeHistory, repeal::get_act_repeal},
+    bitemporal::KnownState,
     database::{ActMetadata, ActSet},
-    enforcement_date_set::EnforcementDateSet,
+    enforcement_date_set::{EnforcementDateSet, EnforcementDateWarning},
     persistence::Persistence,
-    web::util::{logged_http_error, today, OrToday},
+    web::util::{
+        logged_http_error, modified_by_text, today, url_for_act, url_for_act_known_on, OrToday,
+    },
 };
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct RenderActParams {
     date: Option<NaiveDate>,
+    /// Only consider the acts published up to this date
+    known_on: Option<NaiveDate>,
 }
 
 pub async fn render_act(
@@ -60,47 +66,109 @@ pub async fn render_act(
 ) -> Result<Markup, StatusCode> {
     let act_id = act_id_str.parse().map_err(|_| StatusCode::NOT_FOUND)?;
     let date = params.date.or_today();
+    if let Some(known_on) = params.known_on {
+        return render_known_act(act_id, date, known_on, &persistence).await;
+    }
     let state = ActSet::load_async(&persistence, date)
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
     if state.has_act(act_id) {
-        render_existing_act(act_id, date, &persistence).await
+        let act_entry = state.get_act(act_id).map_err(|_| StatusCode::NOT_FOUND)?;
+        let repeal = act_entry.repeal().cloned();
+        let change_history = act_entry
+            .change_history_cached()
+            .await
+            .map_err(logged_http_error)?;
+        // It might seem wasteful to load the state all over again,
+        // but it will be cached at this point anyway
+        let act = load_act(act_id, date, &persistence)
+            .await
+            .map_err(|_| StatusCode::NOT_FOUND)?;
+        let future_changes = if let Ok(future_act) =
+            load_act(act_id, date + Duration::days(365), &persistence).await
+        {
+            FutureActChanges::new(&future_act, date).map_err(|_| StatusCode::NOT_FOUND)?
+        } else {
+            Default::default()
+        };
+        let document_body = if let Some(repeal) = repeal {
+            render_repealed_act_body(&act, &repeal, &change_history)?
+        } else {
+            render_act_body(
+                &act,
+                future_changes,
+                &change_history,
+                act_entry.enforcement_date_warnings(),
+                date,
+            )?
+        };
+        render_existing_act(&act, date, document_body, &persistence).await
     } else {
         render_nonexistent_act(act_id)
     }
 }
 
-async fn render_existing_act(
+/// The act as it could have been known on `known_on`. It is recalculated on
+/// the fly, and the future changes are not shown, as they may have changed since.
+async fn render_known_act(
     act_id: ActIdentifier,
     date: NaiveDate,
+    known_on: NaiveDate,
     persistence: &Persistence,
 ) -> Result<Markup, StatusCode> {
-    // It might seem wasteful to load the state all over again,
-    // but it will be cached at this point anyway
-    let act = load_act(act_id, date, persistence)
-        .await
-        .map_err(|_| StatusCode::NOT_FOUND)?;
-    let future_changes =
-        if let Ok(future_act) = load_act(act_id, date + Duration::days(365), persistence).await {
-            FutureActChanges::new(&future_act, date).map_err(|_| StatusCode::NOT_FOUND)?
-        } else {
-            Default::default()
-        };
-    let act_metadata = ActMetadata::load_async(persistence, act_id)
+    let state = KnownState::load(persistence, date, known_on).map_err(logged_http_error)?;
+    if !state.has_act(act_id) {
+        return render_nonexistent_act(act_id);
+    }
+    let act = state.act(act_id).map_err(logged_http_error)?;
+    let change_history = state.change_history(act_id).map_err(logged_http_error)?;
+    let act_body = if let Some(repeal) = get_act_repeal(&act) {
+        render_repealed_act_body(&act, repeal, &change_history)?
+    } else {
+        let enforcement_dates = EnforcementDateSet::from_act(&act).map_err(logged_http_error)?;
+        render_act_body(
+            &act,
+            Default::default(),
+            &change_history,
+            enforcement_dates.warnings(),
+            date,
+        )?
+    };
+    let document_body = html!(
+        .known_on_notice {
+            "A szöveg a "
+            ( known_on.format("%Y. %m. %d-ig").to_string() )
+            " kihirdetett jogszabályok alapján készült. "
+            a href=( url_for_act(act_id, Some(date)) ) {
+                "A jelenleg ismert állapot megtekintése"
+            }
+        }
+        ( act_body )
+    );
+    render_existing_act(&act, date, document_body, persistence).await
+}
+
+async fn render_existing_act(
+    act: &Act,
+    date: NaiveDate,
+    document_body: Markup,
+    persistence: &Persistence,
+) -> Result<Markup, StatusCode> {
+    let act_metadata = ActMetadata::load_async(persistence, act.identifier)
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
     let modification_dates = act_metadata.modification_dates();
     Ok(document_layout(
         "single_act",
         act.identifier.to_string(),
-        generate_toc(&act),
+        generate_toc(act),
         render_act_menu(
             act.identifier,
             date,
             act.publication_date,
             &modification_dates,
         ),
-        render_act_body(&act, future_changes, date)?,
+        document_body,
     ))
 }
 
@@ -140,12 +208,47 @@ fn render_nonexistent_act(act_id: ActIdentifier) -> Result<Markup, StatusCode> {
     ))
 }
 
+fn render_repealed_act_body(
+    act: &Act,
+    repeal: &LastChange,
+    change_history: &ChangeHistory,
+) -> Result<Markup, StatusCode> {
+    let last_in_force_date = repeal.date.pred();
+    Ok(html!(
+        .act_title {
+            (act.identifier.to_string())
+            br;
+            (act.subject)
+        }
+        .repealed {
+            "A jogszabály már nincs hatályban."
+            br;
+            .modified_by {
+                ( modified_by_text(
+                    repeal.date,
+                    &repeal.cause,
+                    change_history.annulment_of(repeal),
+                    "Hatályon kívül helyezve",
+                )? )
+            }
+            a href=( url_for_act(act.identifier, Some(last_in_force_date)) ) {
+                "Az utolsó hatályos állapot megtekintése"
+            }
+            " ("
+            ( last_in_force_date.format("%Y. %m. %d.").to_string() )
+            ")"
+        }
+    ))
+}
+
 fn render_act_body(
     act: &Act,
     future_changes: FutureActChanges,
+    change_history: &ChangeHistory,
+    enforcement_date_warnings: &[EnforcementDateWarning],
     date: NaiveDate,
 ) -> Result<Markup, StatusCode> {
-    let body_parts = convert_act_to_parts(act, date, future_changes)?;
+    let body_parts = convert_act_to_parts(act, date, future_changes, Some(change_history))?;
     let render_part_params = RenderPartParams {
         date: if date == today() { None } else { Some(date) },
         element_anchors: true,
@@ -161,6 +264,10 @@ fn render_act_body(
             br;
             (act.subject)
         }
+        @if !enforcement_date_warnings.is_empty() {
+            ( render_enforcement_date_warnings(enforcement_date_warnings) )
+        }
+        ( render_retroactive_notice(act.identifier, change_history, date)? )
         .preamble { (act.preamble) }
         @for part in body_parts {
             ( part.render_part(&render_part_params).map_err(logged_http_error)? )
@@ -168,6 +275,82 @@ fn render_act_body(
     ))
 }
 
+/// Shown if the text at `date` was changed by acts published after `date`.
+fn render_retroactive_notice(
+    act_id: ActIdentifier,
+    change_history: &ChangeHistory,
+    date: NaiveDate,
+) -> Result<Markup, StatusCode> {
+    let changes = change_history.retroactive_changes_at(date);
+    let first_published = match changes.iter().filter_map(|e| e.retroactive_since).min() {
+        Some(first_published) => first_published,
+        None => return Ok(html!()),
+    };
+    let mut items = Vec::new();
+    for entry in changes {
+        items.push(modified_by_text(
+            entry.date,
+            &entry.cause,
+            entry.annulment,
+            "Módosítva",
+        )?);
+    }
+    Ok(html!(
+        .retroactive_notice {
+            "Ez az időállapot később kihirdetett jogszabályok által, "
+            "visszamenőleges hatállyal módosult:"
+            ul {
+                @for item in items {
+                    li { (item) }
+                }
+            }
+            a href=( url_for_act_known_on(act_id, date, first_published.pred()) ) {
+                "A módosítások kihirdetése előtt ismert állapot megtekintése"
+            }
+        }
+    ))
+}
+
+fn render_enforcement_date_warnings(warnings: &[EnforcementDateWarning]) -> Markup {
+    html!(
+        .enforcement_date_warnings {
+            "A törvény hatálybalépési rendelkezései nem voltak egyértelműen feldolgozhatók, "
+            "ezért a megjelenített állapot pontatlan lehet:"
+            ul {
+                @for warning in warnings {
+                    li { ( enforcement_date_warning_text(warning) ) }
+                }
+            }
+        }
+    )
+}
+
+fn enforcement_date_warning_text(warning: &EnforcementDateWarning) -> String {
+    match warning {
+        EnforcementDateWarning::NoDefaultDate { assumed } => format!(
+            "Nem található az általános hatálybalépési dátum, helyette {} került felhasználásra.",
+            assumed.format("%Y. %m. %d.")
+        ),
+        EnforcementDateWarning::MultipleDefaultDates { used, .. } => format!(
+            "Több általános hatálybalépési dátum található, a legkorábbi ({}) került felhasználásra.",
+            used.format("%Y. %m. %d.")
+        ),
+        EnforcementDateWarning::DateBeforeDefault { date, positions } => format!(
+            "Egyes rendelkezések ({}) hatálybalépése ({}) megelőzi az általános hatálybalépést.",
+            positions
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            date.format("%Y. %m. %d.")
+        ),
+        EnforcementDateWarning::InvalidEnforcementDate { .. } => {
+            "Egy hatálybalépési rendelkezés nem volt feldolgozható, ezért figyelmen kívül maradt."
+                .to_string()
+        }
+    }
+}
+
 fn update_context_with_act_child(context: &mut ConvertToPartsContext, act_child: &ActChild) {
     match act_child {
         ActChild::StructuralElement(StructuralElement {
@@ -187,14 +370,16 @@ fn update_context_with_act_child(context: &mut ConvertToPartsContext, act_child:
     }
 }
 
-pub fn convert_act_to_parts(
-    act: &Act,
+pub fn convert_act_to_parts<'a>(
+    act: &'a Act,
     date: NaiveDate,
     future_changes: FutureActChanges,
-) -> Result<Vec<DocumentPart>, StatusCode> {
+    change_history: Option<&ChangeHistory>,
+) -> Result<Vec<DocumentPart<'a>>, StatusCode> {
     let mut context = ConvertToPartsContext {
         date,
         future_changes,
+        change_history,
         part_metadata: DocumentPartMetadata {
             reference: act.reference(),
             ..Default::default()
@@ -202,7 +387,7 @@ pub fn convert_act_to_parts(
         ..Default::default()
     };
     let enforcement_dates;
-    if !act.children.is_empty() {
+    if !act.children.is_empty() && get_act_repeal(act).is_none() {
         enforcement_dates = EnforcementDateSet::from_act(act).map_err(logged_http_error)?;
         context.enforcement_dates = Some(&enforcement_dates);
     }
@@ -194,6 +194,7 @@ impl ConvertToParts for Article {
                 child.convert_to_parts(&context, output)?;
                 context.show_article_header = false;
                 context.part_metadata.enforcement_date_marker = None;
+                context.part_metadata.expiry_marker = None;
             }
         }
         Ok(())
@@ -25,6 +25,7 @@ use hun_law::{
 
 use super::{document_part::DocumentPartMetadata, future_changes::FutureActChanges};
 use crate::{
+    amender::change_history::ChangeHistory,
     enforcement_date_set::EnforcementDateSet,
     web::{act::document_part::ChangeMarkerData, util::logged_http_error},
 };
@@ -34,6 +35,7 @@ pub struct ConvertToPartsContext<'a> {
     pub snippet_range: Option<Reference>,
     pub date: NaiveDate,
     pub enforcement_dates: Option<&'a EnforcementDateSet>,
+    pub change_history: Option<&'a ChangeHistory>,
     pub current_book: Option<NumericIdentifier>,
     pub current_chapter: Option<NumericIdentifier>,
     pub show_article_header: bool,
@@ -58,11 +60,28 @@ impl<'a> ConvertToPartsContext<'a> {
     }
 
     pub fn update_change_markers(mut self, last_change: Option<&LastChange>) -> Self {
+        // The history may come from a later state (e.g. in diffs), in which case
+        // it also tells if the currently shown version will ever be in force.
+        if let Some(change_history) = self.change_history {
+            self.part_metadata.never_in_force = change_history
+                .never_in_force_changes(&self.part_metadata.reference)
+                .iter()
+                .any(|entry| entry.date > self.date);
+        }
         if let Some(last_change) = last_change {
+            let history = self.change_history.map_or_else(Vec::new, |change_history| {
+                change_history
+                    .get(&self.part_metadata.reference)
+                    .iter()
+                    .filter(|entry| entry.date <= self.date)
+                    .cloned()
+                    .collect()
+            });
             self.part_metadata.last_change = Some(ChangeMarkerData {
                 changed_ref: self.part_metadata.reference.clone(),
                 change: last_change.clone(),
                 indentation: self.part_metadata.indentation,
+                history,
             })
         }
         if let Some(future_change) = self
@@ -73,6 +92,7 @@ impl<'a> ConvertToPartsContext<'a> {
                 changed_ref: self.part_metadata.reference.clone(),
                 change: future_change.clone(),
                 indentation: self.part_metadata.indentation,
+                history: Vec::new(),
             })
         }
         self
@@ -86,6 +106,8 @@ impl<'a> ConvertToPartsContext<'a> {
                 self.part_metadata.enforcement_date_marker = Some(enforcement_date);
                 self.part_metadata.not_in_force = true;
             }
+            self.part_metadata.expiry_marker =
+                enforcement_dates.upcoming_expiry(&self.part_metadata.reference, self.date);
         }
         self
     }
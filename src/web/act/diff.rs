@@ -38,6 +38,8 @@ use super::{
     DocumentPart, DocumentPartMetadata,
 };
 use crate::{
+    amender::change_history::ChangeHistory,
+    bitemporal::KnownState,
     database::{ActMetadata, ActSet},
     persistence::Persistence,
     web::{
@@ -50,6 +52,8 @@ use crate::{
 pub struct RenderActDiffParams {
     date_left: Option<NaiveDate>,
     date_right: Option<NaiveDate>,
+    /// Only consider the acts published up to this date, on both sides
+    known_on: Option<NaiveDate>,
 }
 
 pub async fn render_act_diff<'a>(
@@ -81,6 +85,8 @@ struct ActDiffData {
     act_right: Arc<Act>,
     date_right: NaiveDate,
     modification_dates: Vec<NaiveDate>,
+    /// The change history at the later date, used for both sides
+    change_history: Arc<ChangeHistory>,
 }
 
 async fn get_act_diff_data(
@@ -89,14 +95,41 @@ async fn get_act_diff_data(
     persistence: &Persistence,
 ) -> anyhow::Result<ActDiffData> {
     let act_id = act_id_str.parse()?;
-
     let date_right = params.date_right.or_today();
-    let state_right = ActSet::load_async(persistence, date_right).await?;
-    let act_right = state_right.get_act(act_id)?.act_cached().await?;
 
-    let date_left = params.date_left.unwrap_or(act_right.publication_date);
-    let state_left = ActSet::load_async(persistence, date_left).await?;
-    let act_left = state_left.get_act(act_id)?.act_cached().await?;
+    let (act_left, date_left, act_right, change_history) = if let Some(known_on) = params.known_on {
+        let state_right = KnownState::load(persistence, date_right, known_on)?;
+        let act_right = state_right.act(act_id)?;
+        let date_left = params.date_left.unwrap_or(act_right.publication_date);
+        let state_left = KnownState::load(persistence, date_left, known_on)?;
+        let change_history = if date_left <= date_right {
+            state_right.change_history(act_id)?
+        } else {
+            state_left.change_history(act_id)?
+        };
+        (
+            Arc::new(state_left.act(act_id)?),
+            date_left,
+            Arc::new(act_right),
+            Arc::new(change_history),
+        )
+    } else {
+        let state_right = ActSet::load_async(persistence, date_right).await?;
+        let entry_right = state_right.get_act(act_id)?;
+        let act_right = entry_right.act_cached().await?;
+
+        let date_left = params.date_left.unwrap_or(act_right.publication_date);
+        let state_left = ActSet::load_async(persistence, date_left).await?;
+        let entry_left = state_left.get_act(act_id)?;
+        let act_left = entry_left.act_cached().await?;
+
+        let change_history = if date_left <= date_right {
+            entry_right.change_history_cached().await?
+        } else {
+            entry_left.change_history_cached().await?
+        };
+        (act_left, date_left, act_right, change_history)
+    };
 
     let act_metadata = ActMetadata::load_async(persistence, act_id).await?;
     let modification_dates = act_metadata.modification_dates();
@@ -107,6 +140,7 @@ async fn get_act_diff_data(
             act_right,
             date_right,
             modification_dates,
+            change_history,
         })
     } else {
         Ok(ActDiffData {
@@ -115,17 +149,23 @@ async fn get_act_diff_data(
             act_left: act_right,
             date_left: date_right,
             modification_dates,
+            change_history,
         })
     }
 }
 
 fn render_act_diff_body(diff_data: &ActDiffData) -> Result<Markup, StatusCode> {
-    let body_parts_left =
-        convert_act_to_parts(&diff_data.act_left, diff_data.date_left, Default::default())?;
+    let body_parts_left = convert_act_to_parts(
+        &diff_data.act_left,
+        diff_data.date_left,
+        Default::default(),
+        Some(&diff_data.change_history),
+    )?;
     let body_parts_right = convert_act_to_parts(
         &diff_data.act_right,
         diff_data.date_right,
         Default::default(),
+        Some(&diff_data.change_history),
     )?;
 
     let render_params_left = RenderPartParams {
@@ -266,6 +306,7 @@ pub fn render_diff_pair(
             .diff_left
             .different[different && left.is_some()]
             .diff_full[different && left.is_some()]
+            .never_in_force[different && left.map_or(false, |l| l.metadata.never_in_force)]
             {
                 @if let Some(left) = left {
                     (left.render_part(left_params).map_err(logged_http_error)?)
@@ -294,7 +335,7 @@ fn render_different_sae_pair(
     let (left_markers, right_markers) = generate_diff_markers(left.text, right.text);
     Ok(html!(
         .diff_container {
-            .diff_left .different{
+            .diff_left .different .never_in_force[left_metadata.never_in_force] {
                 (
                     render_sae_text_part(left_params, left, left_metadata, &left_markers)
                         .map_err(logged_http_error)?
@@ -25,9 +25,13 @@ use hun_law::{
 };
 use maud::{html, Markup, PreEscaped};
 
-use crate::web::{
-    act::markers::render_markers,
-    util::{anchor_string, article_anchor, link_to_reference_end, link_to_reference_start},
+use crate::{
+    amender::change_history::ChangeHistoryEntry,
+    enforcement_date_set::EffectiveDate,
+    web::{
+        act::markers::render_markers,
+        util::{anchor_string, article_anchor, link_to_reference_end, link_to_reference_start},
+    },
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -42,8 +46,12 @@ pub struct DocumentPartMetadata {
     pub indentation: usize,
     pub last_change: Option<ChangeMarkerData>,
     pub future_change: Option<ChangeMarkerData>,
-    pub enforcement_date_marker: Option<NaiveDate>,
+    pub enforcement_date_marker: Option<EffectiveDate>,
+    pub expiry_marker: Option<NaiveDate>,
     pub not_in_force: bool,
+    /// This version of the element is changed or repealed later, before it
+    /// would come into force.
+    pub never_in_force: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -51,6 +59,9 @@ pub struct ChangeMarkerData {
     pub changed_ref: Reference,
     pub change: LastChange,
     pub indentation: usize,
+    /// All past changes of the element, oldest first. May be empty if the
+    /// history is not known.
+    pub history: Vec<ChangeHistoryEntry>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -128,6 +139,7 @@ impl<'a> DocumentPart<'a> {
                     .sae_container
                     .indent_1
                     .not_in_force[self.metadata.not_in_force]
+                    .never_in_force[self.metadata.never_in_force]
                     {
                         .article_header
                         id=[params.element_anchors.then(|| article_anchor(&self.metadata.reference))]
@@ -146,6 +158,7 @@ impl<'a> DocumentPart<'a> {
                     .sae_container
                     .{"indent_" ( (self.metadata.indentation - 1) )}
                     .not_in_force[self.metadata.not_in_force]
+                    .never_in_force[self.metadata.never_in_force]
                     .blockamendment_text
                     {
                         .sae_body { "(" (text) ")" }
@@ -158,6 +171,7 @@ impl<'a> DocumentPart<'a> {
                     .sae_container
                     .{"indent_" (self.metadata.indentation)}
                     .not_in_force[self.metadata.not_in_force]
+                    .never_in_force[self.metadata.never_in_force]
                     {
                         .blockamendment_container {
                             @for part in parts {
@@ -173,6 +187,7 @@ impl<'a> DocumentPart<'a> {
                     .sae_container
                     .{"indent_" (self.metadata.indentation)}
                     .not_in_force[self.metadata.not_in_force]
+                    .never_in_force[self.metadata.never_in_force]
                     {
                         .blockamendment_container {
                             ( render_indented_lines(lines) )
@@ -225,6 +240,7 @@ pub fn render_sae_text_part(
         .sae_container
         .{"indent_" (metadata.indentation)}
         .not_in_force[metadata.not_in_force]
+        .never_in_force[metadata.never_in_force]
         {
             @if part.show_article_header {
                 .article_header
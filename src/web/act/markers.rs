@@ -20,11 +20,15 @@ use hun_law::{reference::Reference, structure::ChangeCause};
 use maud::{html, Markup, PreEscaped};
 
 use super::document_part::{DocumentPartMetadata, RenderPartParams};
-use crate::web::{
-    act::document_part::ChangeMarkerData,
-    util::{
-        anchor_string, modified_by_text, url_for_act, url_for_change_snippet, url_for_diff,
-        url_for_reference, OrToday,
+use crate::{
+    enforcement_date_set::EffectiveDate,
+    web::{
+        act::document_part::ChangeMarkerData,
+        util::{
+            anchor_string, change_history_text, fixup_text, modified_by_text, retroactive_text,
+            url_for_act, url_for_annulment, url_for_change_snippet, url_for_diff,
+            url_for_reference, OrToday,
+        },
     },
 };
 
@@ -59,6 +63,9 @@ pub fn render_markers(params: &RenderPartParams, part_metadata: &DocumentPartMet
         if let Some(ed_marker) = render_enforcement_date_marker(part_metadata) {
             result.push_str(&ed_marker.0);
         }
+        if let Some(expiry_marker) = render_expiry_marker(part_metadata) {
+            result.push_str(&expiry_marker.0);
+        }
     }
     PreEscaped(result)
 }
@@ -78,15 +85,36 @@ fn render_changes_markers(
         changed_ref,
         change,
         indentation,
+        history,
     } = change_data.as_ref()?;
     let (date_left, date_right) = match change_type {
         ChangeType::Past => (change.date.pred(), date),
         ChangeType::Future => (date, change.date),
     };
-    let change_snippet = if changed_ref.article().is_some() {
+    let never_in_force = matches!(change_type, ChangeType::Past)
+        && history.last().map_or(false, |entry| {
+            entry.never_in_force && entry.date == change.date
+        });
+    let by_fixup = matches!(change_type, ChangeType::Past)
+        && history.last().map_or(false, |entry| {
+            entry.fixup.is_some() && entry.date == change.date
+        });
+    let retroactive = matches!(change_type, ChangeType::Past)
+        && history.last().map_or(false, |entry| {
+            entry.retroactive_since.is_some() && entry.date == change.date
+        });
+    let annulment = history
+        .last()
+        .filter(|entry| entry.date == change.date)
+        .and_then(|entry| entry.annulment);
+    let change_snippet = if matches!(change_type, ChangeType::Past)
+        && (history.len() > 1 || never_in_force || by_fixup || retroactive || annulment.is_some())
+    {
+        format!("static:{}", change_history_text(history).ok()?.0)
+    } else if changed_ref.article().is_some() {
         url_for_change_snippet(changed_ref, date_left, date_right, &change.cause)
     } else {
-        let modified_by = modified_by_text(change.date, &change.cause, "Módosítva")
+        let modified_by = modified_by_text(change.date, &change.cause, None, "Módosítva")
             .ok()?
             .0;
         format!("static:{modified_by}")
@@ -101,6 +129,7 @@ fn render_changes_markers(
         ChangeType::Past => "past",
         ChangeType::Future => "future",
     };
+    let annulled = annulment.is_some();
     Some(html!(
         a
         .change_container
@@ -112,6 +141,10 @@ fn render_changes_markers(
             .change_marker
             .near[change_age<365]
             .very_near[change_age<100]
+            .annulled[annulled]
+            .never_in_force[never_in_force]
+            .retroactive[retroactive]
+            .fixup[by_fixup]
             {}
         }
     ))
@@ -121,15 +154,39 @@ fn render_diff_change_marker(
     since_date: NaiveDate,
     part_metadata: &DocumentPartMetadata,
 ) -> Option<Markup> {
-    let last_change = &part_metadata.last_change.as_ref()?.change;
+    let change_data = part_metadata.last_change.as_ref()?;
+    let last_change = &change_data.change;
     if last_change.date < since_date {
         return None;
     }
-    let snippet_text = modified_by_text(last_change.date, &last_change.cause, "Módosítva").ok()?;
+    let last_entry = change_data
+        .history
+        .last()
+        .filter(|entry| entry.date == last_change.date);
+    let never_in_force = last_entry.map_or(false, |entry| entry.never_in_force);
+    let fixup = last_entry.and_then(|entry| entry.fixup.as_ref());
+    let retroactive_since = last_entry.and_then(|entry| entry.retroactive_since);
+    let annulment = last_entry.and_then(|entry| entry.annulment);
+    let verb = if never_in_force {
+        "Hatálybalépése előtt módosítva"
+    } else {
+        "Módosítva"
+    };
+    let snippet_text =
+        modified_by_text(last_change.date, &last_change.cause, annulment, verb).ok()?;
+    let snippet_text = html!(
+        (snippet_text)
+        @if let Some(published) = retroactive_since {
+            " (" (retroactive_text(published)) ")"
+        }
+        @if let Some(fixup) = fixup {
+            " (" (fixup_text(fixup)) ")"
+        }
+    );
     let href = if let ChangeCause::Amendment(change_ref) = &last_change.cause {
         url_for_reference(change_ref, Some(last_change.date), true).ok()
     } else {
-        None
+        annulment.map(url_for_annulment)
     };
     Some(html!(
         a
@@ -139,25 +196,61 @@ fn render_diff_change_marker(
         data-snippet={ "static:" (snippet_text.0) }
         {
             .change_marker
+            .never_in_force[never_in_force]
+            .retroactive[retroactive_since.is_some()]
+            .fixup[fixup.is_some()]
             {}
         }
     ))
 }
 
 fn render_enforcement_date_marker(part_metadata: &DocumentPartMetadata) -> Option<Markup> {
-    let enforcement_date = part_metadata.enforcement_date_marker?;
+    let (enforcement_date, snippet) = match part_metadata.enforcement_date_marker.as_ref()? {
+        EffectiveDate::Date(date) => (
+            Some(*date),
+            date.format("static:%Y. %m. %d-n lép hatályba").to_string(),
+        ),
+        EffectiveDate::Undetermined { event } => (
+            None,
+            format!("static:Hatálybalépésének napja még nem ismert ({event})"),
+        ),
+    };
     let change_url = format!(
         "{}#{}",
-        url_for_act(part_metadata.reference.act()?, Some(enforcement_date)),
+        url_for_act(part_metadata.reference.act()?, enforcement_date),
         anchor_string(&part_metadata.reference)
     );
-    let snippet = enforcement_date
-        .format("static:%Y. %m. %d-n lép hatályba")
-        .to_string();
 
     Some(html!(
-        a .enforcement_date_marker href=(change_url) data-snippet=(snippet) {
+        a
+        .enforcement_date_marker
+        .undetermined[enforcement_date.is_none()]
+        href=(change_url)
+        data-snippet=(snippet)
+        {
             "🕓︎"
         }
     ))
 }
+
+fn render_expiry_marker(part_metadata: &DocumentPartMetadata) -> Option<Markup> {
+    let expiry_date = part_metadata.expiry_marker?;
+    let change_url = format!(
+        "{}#{}",
+        url_for_diff(
+            part_metadata.reference.act()?,
+            expiry_date.pred(),
+            expiry_date
+        ),
+        anchor_string(&part_metadata.reference)
+    );
+    let snippet = expiry_date
+        .format("static:%Y. %m. %d-n hatályát veszti")
+        .to_string();
+
+    Some(html!(
+        a .enforcement_date_marker .expiry href=(change_url) data-snippet=(snippet) {
+            "⌛︎"
+        }
+    ))
+}
@@ -26,6 +26,7 @@ mod layout;
 mod markers;
 mod menu;
 mod sae;
+mod simulation;
 mod toc;
 
 pub use act::render_act;
@@ -35,6 +36,7 @@ pub use diff::{create_diff_pairs, render_act_diff, render_diff_pair};
 pub use document_part::{
     DocumentPart, DocumentPartMetadata, DocumentPartSpecific, RenderPartParams,
 };
+pub use simulation::{render_draft_list, render_simulation};
 
 pub trait ConvertToParts {
     fn convert_to_parts<'a>(
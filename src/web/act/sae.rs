@@ -85,6 +85,7 @@ where
                 });
                 context.show_article_header = false;
                 context.part_metadata.enforcement_date_marker = None;
+                context.part_metadata.expiry_marker = None;
                 children.convert_to_parts(&context.clone().indent(), output)?;
                 if let Some(wrap_up) = wrap_up {
                     output.push(DocumentPart {
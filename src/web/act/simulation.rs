@@ -0,0 +1,201 @@
+// Copyright (c) 2022-2023, Alex Badics
+//
+// This file is part of AJDB
+//
+// AJDB is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// AJDB is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    Extension,
+};
+use chrono::NaiveDate;
+use hun_law::identifier::ActIdentifier;
+use maud::{html, Markup, DOCTYPE};
+use serde::Deserialize;
+
+use super::{
+    act::convert_act_to_parts, create_diff_pairs, layout::document_layout, render_diff_pair,
+    RenderPartParams,
+};
+use crate::{
+    database::ActSet,
+    persistence::Persistence,
+    simulation::{first_enforcement_date, list_drafts, load_named_draft, Simulation},
+    web::util::{logged_http_error, url_for_simulation},
+};
+
+pub async fn render_draft_list() -> Result<Markup, StatusCode> {
+    let drafts = list_drafts().map_err(logged_http_error)?;
+    Ok(html!(
+        (DOCTYPE)
+        html {
+            head {
+                title { "Tervezetek - AJDB" }
+                link rel="stylesheet" href="/static/style_common.css";
+                link rel="stylesheet" href="/static/style_portal.css";
+                link rel="icon" href="/static/favicon.png";
+            }
+            body {
+                .main_container {
+                    .title {
+                        "Tervezetek"
+                    }
+                    .inner_container {
+                        p {
+                            "A tervezetek nem részei az adatbázisnak. "
+                            "Hatásukat az adatbázisban tárolt hatályos szövegen szimuláljuk."
+                        }
+                        ul {
+                            @for draft in &drafts {
+                                li { a href=(url_for_simulation(draft, None)) { (draft) } }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    ))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RenderSimulationParams {
+    date: Option<NaiveDate>,
+}
+
+pub async fn render_simulation(
+    Path(draft_name): Path<String>,
+    params: Query<RenderSimulationParams>,
+    Extension(persistence): Extension<Arc<Persistence>>,
+) -> Result<Markup, StatusCode> {
+    let draft = load_named_draft(&draft_name).map_err(|_| StatusCode::NOT_FOUND)?;
+    let date = match params.date {
+        Some(date) => date,
+        None => first_enforcement_date(&draft).map_err(logged_http_error)?,
+    };
+    let state = ActSet::load_async(&persistence, date)
+        .await
+        .map_err(logged_http_error)?;
+    let simulation = Simulation::run(&state, &draft, date).map_err(logged_http_error)?;
+    Ok(document_layout(
+        "act_diff",
+        format!("{} (tervezet)", draft.identifier),
+        render_simulation_toc(&simulation),
+        render_simulation_menu(draft.identifier, date),
+        render_simulation_body(&simulation)?,
+    ))
+}
+
+fn simulated_act_anchor(act_id: ActIdentifier) -> String {
+    format!("act_{}", act_id.compact_string())
+}
+
+fn render_simulation_toc(simulation: &Simulation) -> Markup {
+    html!(
+        ul {
+            @for act_id in simulation.acts.keys() {
+                li { a href={"#" (simulated_act_anchor(*act_id))} { (act_id.to_string()) } }
+            }
+        }
+    )
+}
+
+fn render_simulation_menu(draft_id: ActIdentifier, date: NaiveDate) -> Markup {
+    html!(
+        .menu_act_title { ( draft_id.to_string() ) " (tervezet)" }
+        .menu_date {
+            .date_flex {
+                .date_current { (date.format("%Y.%m.%d.").to_string()) }
+            }
+        }
+        .menu_change_mode {
+            a href="/simulate" { "Tervezetek" }
+        }
+    )
+}
+
+fn render_simulation_body(simulation: &Simulation) -> Result<Markup, StatusCode> {
+    let render_params_left = RenderPartParams {
+        date: Some(simulation.date),
+        element_anchors: true,
+        convert_links: true,
+        render_past_change_marker: true,
+        ..Default::default()
+    };
+    // The changes in the simulated text are caused by the draft, which is not
+    // in the database, so there is nothing to link the change markers to.
+    let render_params_right = RenderPartParams {
+        date: Some(simulation.date),
+        convert_links: true,
+        ..Default::default()
+    };
+    let mut acts = Vec::new();
+    for (act_id, simulated) in &simulation.acts {
+        let body_parts_left =
+            convert_act_to_parts(&simulated.before, simulation.date, Default::default(), None)?;
+        let body_parts_right =
+            convert_act_to_parts(&simulated.after, simulation.date, Default::default(), None)?;
+        let mut diff = Vec::new();
+        for (left, right) in create_diff_pairs(&body_parts_left, &body_parts_right) {
+            diff.push(render_diff_pair(
+                left,
+                &render_params_left,
+                right,
+                &render_params_right,
+            )?);
+        }
+        acts.push(html!(
+            .act_title id=(simulated_act_anchor(*act_id)) {
+                (act_id.to_string())
+                br;
+                (simulated.before.subject)
+            }
+            @if !simulated.failures.is_empty() {
+                .simulation_failures {
+                    "Nem alkalmazható módosítások:"
+                    ul {
+                        @for (_, error) in &simulated.failures {
+                            li { (error) }
+                        }
+                    }
+                }
+            }
+            @for pair in diff {
+                (pair)
+            }
+        ));
+    }
+    Ok(html!(
+        .act_title {
+            (simulation.draft_id.to_string())
+            " tervezet hatása "
+            (simulation.date.format("%Y. %m. %d-i").to_string())
+            " állapot szerint"
+        }
+        @if !simulation.missing_acts.is_empty() {
+            .simulation_failures {
+                "Az adatbázisban nem szereplő módosított jogszabályok: "
+                @for (i, act_id) in simulation.missing_acts.iter().enumerate() {
+                    @if i > 0 { ", " }
+                    (act_id.to_string())
+                }
+            }
+        }
+        @for act in acts {
+            (act)
+        }
+    ))
+}
@@ -0,0 +1,76 @@
+// Copyright (c) 2022-2023, Alex Badics
+//
+// This file is part of AJDB
+//
+// AJDB is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// AJDB is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
+
+use axum::{extract::Path, http::StatusCode};
+use maud::{html, Markup, DOCTYPE};
+
+use super::util::{date_from_text, link_to_reference, logged_http_error};
+use crate::annulment::{Annulment, DecisionIdentifier};
+
+pub async fn render_annulment(
+    Path((year, number)): Path<(u16, u16)>,
+) -> Result<Markup, StatusCode> {
+    let decision = DecisionIdentifier { year, number };
+    let annulment = Annulment::load(decision)
+        .map_err(logged_http_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    // Link to the last state where the annulled provisions were still in force
+    let in_force_date = annulment.application_date().pred();
+    let position_links = annulment
+        .positions
+        .iter()
+        .map(|position| link_to_reference(position, Some(in_force_date), None, true))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(logged_http_error)?;
+    Ok(html!(
+        (DOCTYPE)
+        html {
+            head {
+                title { (annulment.title) " - AJDB" }
+                link rel="stylesheet" href="/static/style_common.css";
+                link rel="stylesheet" href="/static/style_portal.css";
+                link rel="icon" href="/static/favicon.png";
+            }
+            body {
+                .main_container {
+                    .title {
+                        (annulment.title)
+                    }
+                    .inner_container {
+                        p {
+                            "Az Alkotmánybíróság az alábbi rendelkezéseket "
+                            ( annulment.date.format("%Y. %m. %d-i").to_string() )
+                            " hatállyal megsemmisítette."
+                            @if let Some(retroactive_from) = annulment.retroactive_from {
+                                br;
+                                "A megsemmisítés visszamenőleges hatályú, a rendelkezések "
+                                ( date_from_text(retroactive_from) )
+                                " nem alkalmazhatók."
+                            }
+                        }
+                        h3 { "Megsemmisített rendelkezések:" }
+                        ul {
+                            @for position_link in position_links {
+                                li { (position_link) }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    ))
+}
@@ -19,12 +19,15 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use axum::{http::StatusCode, Extension};
+use chrono::NaiveDate;
 use maud::{html, Markup, DOCTYPE};
 
 use super::util::{logged_http_error, today};
 use crate::{database::ActSet, persistence::Persistence};
 
-async fn get_all_acts(persistence: &Persistence) -> Result<Vec<(String, String)>> {
+async fn get_all_acts(
+    persistence: &Persistence,
+) -> Result<Vec<(String, String, Option<NaiveDate>)>> {
     let state = ActSet::load_async(persistence, today()).await?;
     let acts = state.get_acts()?;
     Ok(acts
@@ -33,6 +36,7 @@ async fn get_all_acts(persistence: &Persistence) -> Result<Vec<(String, String)>
             (
                 format!("{:?}", ae.identifier()),
                 ae.identifier().to_string(),
+                ae.repeal().map(|r| r.date),
             )
         })
         .collect())
@@ -91,8 +95,17 @@ pub async fn render_index(
                         }
                         h3 { "Egyéb törvények:" }
                         ul {
-                            @for (act_id, act_long) in acts {
-                                li { a href={"/act/" (act_id)} { (act_long) } }
+                            @for (act_id, act_long, repeal_date) in acts {
+                                li {
+                                    a href={"/act/" (act_id)} { (act_long) }
+                                    @if let Some(repeal_date) = repeal_date {
+                                        span .repealed_act {
+                                            " (hatályon kívül: "
+                                            ( repeal_date.format("%Y. %m. %d.").to_string() )
+                                            ")"
+                                        }
+                                    }
+                                }
                             }
                         }
                     }
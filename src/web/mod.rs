@@ -16,6 +16,7 @@
 // along with AJDB.  If not, see <http://www.gnu.org/licenses/>.
 
 mod act;
+mod annulment;
 mod index;
 mod snippet;
 mod util;
@@ -23,18 +24,26 @@ mod util;
 use std::{net::SocketAddr, sync::Arc};
 
 use self::{
-    act::{render_act, render_act_diff},
+    act::{render_act, render_act_diff, render_draft_list, render_simulation},
+    annulment::render_annulment,
     index::render_index,
     snippet::{render_diff_snippet, render_snippet},
 };
-use crate::persistence::Persistence;
+use crate::{fixups::FixupRegistry, persistence::Persistence};
 
 pub async fn web_main() {
     let persistence = Persistence::new("db");
+    FixupRegistry::watch_changes();
     let router = axum::Router::new()
         .route("/", axum::routing::get(render_index))
         .route("/act/:act_id", axum::routing::get(render_act))
         .route("/diff/:act_id", axum::routing::get(render_act_diff))
+        .route(
+            "/annulment/:year/:number",
+            axum::routing::get(render_annulment),
+        )
+        .route("/simulate", axum::routing::get(render_draft_list))
+        .route("/simulate/:draft", axum::routing::get(render_simulation))
         .route("/snippet/:snippet_ref", axum::routing::get(render_snippet))
         .route(
             "/diff_snippet/:snippet_ref",
@@ -144,6 +144,8 @@ pub async fn render_diff_snippet(
                     .map_err(|_| StatusCode::NOT_FOUND)?,
             )
         },
+        // Annulments have static snippets, see render_changes_markers
+        None,
         verb,
     )?;
     let render_params_left = RenderPartParams {
@@ -18,7 +18,7 @@
 use std::fmt::Write;
 
 use axum::http::StatusCode;
-use chrono::NaiveDate;
+use chrono::{Datelike, NaiveDate};
 use hun_law::{
     identifier::ActIdentifier, reference::Reference, structure::ChangeCause,
     util::compact_string::CompactString,
@@ -26,6 +26,12 @@ use hun_law::{
 use maud::{html, Markup, PreEscaped};
 use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 
+use crate::{
+    amender::{change_history::ChangeHistoryEntry, text_amendment::text_replace::MatchLevel},
+    annulment::DecisionIdentifier,
+    fixups::FixupProvenance,
+};
+
 pub fn logged_http_error(e: impl std::fmt::Debug) -> StatusCode {
     log::error!("Internal error occured: {:?}", e);
     StatusCode::INTERNAL_SERVER_ERROR
@@ -56,6 +62,13 @@ pub fn url_for_act(act_id: ActIdentifier, date: Option<NaiveDate>) -> String {
     )
 }
 
+pub fn url_for_act_known_on(act_id: ActIdentifier, date: NaiveDate, known_on: NaiveDate) -> String {
+    format!(
+        "/act/{}?date={date}&known_on={known_on}",
+        act_id.compact_string(),
+    )
+}
+
 pub fn url_for_diff(act_id: ActIdentifier, date_left: NaiveDate, date_right: NaiveDate) -> String {
     format!(
         "/diff/{}?date_left={date_left}&date_right={date_right}",
@@ -63,6 +76,18 @@ pub fn url_for_diff(act_id: ActIdentifier, date_left: NaiveDate, date_right: Nai
     )
 }
 
+pub fn url_for_simulation(draft_name: &str, date: Option<NaiveDate>) -> String {
+    if let Some(date) = date {
+        format!("/simulate/{draft_name}?date={date}")
+    } else {
+        format!("/simulate/{draft_name}")
+    }
+}
+
+pub fn url_for_annulment(decision: DecisionIdentifier) -> String {
+    format!("/annulment/{}/{}", decision.year, decision.number)
+}
+
 pub fn url_for_snippet(r: &Reference, date: Option<NaiveDate>) -> String {
     format!(
         "/snippet/{}{}",
@@ -147,11 +172,22 @@ pub fn link_to_reference(
     Ok(PreEscaped(result))
 }
 
+/// `annulment` should be set if the change was an annulment (see
+/// `ChangeHistoryEntry::annulment`), the cause itself does not tell.
 pub fn modified_by_text(
     date: NaiveDate,
     cause_ref: &ChangeCause,
+    annulment: Option<DecisionIdentifier>,
     verb: &'static str,
 ) -> Result<Markup, StatusCode> {
+    if let Some(decision) = annulment {
+        return Ok(html!(
+            "Az Alkotmánybíróság a "
+            a href=(url_for_annulment(decision)) { (decision) "tal" }
+            " megsemmisítette, "
+            ( date.format("%Y. %m. %d-i hatállyal.").to_string() )
+        ));
+    }
     Ok(match cause_ref {
         ChangeCause::Amendment(cause_ref) => {
             let link =
@@ -182,6 +218,96 @@ pub fn modified_by_text(
     })
 }
 
+/// The date with the "-tól/-től" suffix, in the form the day is read out,
+/// e.g. "2022. 03. 01-jétől", "2022. 03. 03-ától" or "2022. 03. 05-étől".
+pub fn date_from_text(date: NaiveDate) -> String {
+    let day = date.day();
+    let suffix = match (day / 10, day % 10) {
+        (0, 1) => "jétől",
+        (1, 0) | (_, 1 | 2 | 4 | 5 | 7 | 9) => "étől",
+        _ => "ától",
+    };
+    format!("{}-{}", date.format("%Y. %m. %d"), suffix)
+}
+
+/// Marks a change that depends on a manual correction (fixup) instead of the
+/// published text of the amendment.
+pub fn fixup_text(fixup: &FixupProvenance) -> Markup {
+    html!(
+        span .fixup_note title={ (fixup.file) " #" (fixup.index) } {
+            "kézi javítással"
+        }
+        @if let Some(note) = &fixup.note {
+            ": " (note)
+        }
+    )
+}
+
+/// Marks a change that was published after it came into force.
+pub fn retroactive_text(published: NaiveDate) -> Markup {
+    html!(
+        span .retroactive_note title={ "Kihirdetve: " (published.format("%Y. %m. %d.").to_string()) } {
+            "visszamenőleges hatállyal módosítva"
+        }
+    )
+}
+
+/// "Módosította: X, Y és Z." text, listing all changes of an element.
+/// Changes made before the element came into force, retroactively, or by
+/// fixups are marked as such.
+pub fn change_history_text(history: &[ChangeHistoryEntry]) -> Result<Markup, StatusCode> {
+    let mut items = Vec::new();
+    for entry in history {
+        let date = if entry.never_in_force {
+            entry
+                .date
+                .format("%Y. %m. %d., hatálybalépése előtt")
+                .to_string()
+        } else {
+            entry.date.format("%Y. %m. %d.").to_string()
+        };
+        let date = html!(
+            (date)
+            @if let Some(published) = entry.retroactive_since {
+                ", " (retroactive_text(published))
+            }
+            @if let Some(fixup) = &entry.fixup {
+                ", " (fixup_text(fixup))
+            }
+            @match entry.match_level {
+                MatchLevel::Exact => {},
+                MatchLevel::Suffix => ", eltérő toldalékkal illesztve",
+                _ => ", eltérő írásmóddal illesztve",
+            }
+        );
+        if let Some(decision) = entry.annulment {
+            items.push(html!(a href=(url_for_annulment(decision)) { (decision) } " (" (date) ")"));
+            continue;
+        }
+        items.push(match &entry.cause {
+            ChangeCause::Amendment(cause_ref) => {
+                let link = link_to_reference(cause_ref, Some(entry.date), None, true)
+                    .map_err(logged_http_error)?;
+                html!((link) " (" (date) ")")
+            }
+            ChangeCause::AutoRepeal => html!("automatikus hatályvesztés (" (date) ")"),
+            ChangeCause::Other(cause_text) => html!((cause_text) " (" (date) ")"),
+        });
+    }
+    Ok(html!(
+        "Módosította: "
+        @for (i, item) in items.iter().enumerate() {
+            @if i + 1 == items.len() && i > 0 {
+                " és "
+            } @else if i > 0 {
+                ", "
+            }
+            (item)
+        }
+        "."
+    ))
+}
+
 pub fn today() -> NaiveDate {
     chrono::Utc::today().naive_utc()
 }
@@ -198,3 +324,25 @@ impl OrToday for Option<NaiveDate> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date_from_text() {
+        let texts = (1..=31)
+            .map(|day| date_from_text(NaiveDate::from_ymd(2022, 3, day)))
+            .collect::<Vec<_>>();
+        assert_eq!(texts[0], "2022. 03. 01-jétől");
+        assert_eq!(texts[1], "2022. 03. 02-ától");
+        assert_eq!(texts[4], "2022. 03. 05-étől");
+        assert_eq!(texts[9], "2022. 03. 10-étől");
+        assert_eq!(texts[10], "2022. 03. 11-étől");
+        assert_eq!(texts[12], "2022. 03. 13-ától");
+        assert_eq!(texts[19], "2022. 03. 20-ától");
+        assert_eq!(texts[26], "2022. 03. 27-étől");
+        assert_eq!(texts[29], "2022. 03. 30-ától");
+        assert_eq!(texts[30], "2022. 03. 31-étől");
+    }
+}
@@ -3,4 +3,6 @@
 // All rights reserved.
 
 pub mod test_apply_modifications;
+pub mod test_bitemporal;
 pub mod test_extract_modifications;
+pub mod test_pending_amendments;
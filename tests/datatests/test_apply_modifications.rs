@@ -23,7 +23,11 @@ declare_test!(dir = "data_apply_modifications", pattern = r"\.yml");
 struct TestData {
     children_original: Vec<ActChild>,
     modifications: Vec<AppliableModificationType>,
+    /// Not checked if the modifications are expected to fail
+    #[serde(default)]
     children_expected: Vec<ActChild>,
+    #[serde(default)]
+    expect_error: bool,
 }
 
 pub fn run_test(path: &Path) -> datatest_stable::Result<()> {
@@ -44,26 +48,35 @@ pub fn run_test(path: &Path) -> datatest_stable::Result<()> {
         .modifications
         .into_iter()
         .enumerate()
-        .map(|(i, modification)| AppliableModification {
-            cause: ChangeCause::Amendment(
-                (
-                    ActIdentifier {
-                        year: 2013,
-                        number: 420,
-                    },
-                    IdentifierRange::from_single(ArticleIdentifier::from((i + 1) as u16)),
-                )
-                    .into(),
-            ),
-            modification,
+        .map(|(i, modification)| {
+            AppliableModification::new(
+                ChangeCause::Amendment(
+                    (
+                        ActIdentifier {
+                            year: 2013,
+                            number: 420,
+                        },
+                        IdentifierRange::from_single(ArticleIdentifier::from((i + 1) as u16)),
+                    )
+                        .into(),
+                ),
+                modification,
+            )
         })
         .collect();
-    AppliableModificationSet::apply_to_act(
+    let result = AppliableModificationSet::apply_to_act(
         &mut act,
         NaiveDate::from_ymd(2013, 4, 20),
         modifications,
         OnError::ReturnErr,
-    )?;
+    );
+    if test_data.expect_error {
+        return match result {
+            Ok(_) => Err("Modifications applied, but an error was expected".into()),
+            Err(_) => Ok(()),
+        };
+    }
+    result?;
     ensure_eq(
         &test_data.children_expected,
         &act.children,
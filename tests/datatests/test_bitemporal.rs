@@ -0,0 +1,125 @@
+// This file is part of AJDB
+// Copyright 2022, Alex Badics
+// All rights reserved.
+
+use std::path::Path;
+
+use ajdb::{
+    annulment::{Annulment, AnnulmentSet, DecisionIdentifier},
+    bitemporal::KnownState,
+    database::ActSet,
+    events::EventRegistry,
+    persistence::Persistence,
+    recalculation::recalculate_stored_date,
+    util::NaiveDateRange,
+};
+use chrono::NaiveDate;
+use hun_law::{
+    identifier::ActIdentifier,
+    structure::{Act, ActChild, SAEBody},
+    util::singleton_yaml,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::declare_test;
+use crate::test_utils::{ensure_eq, read_all};
+
+declare_test!(dir = "data_bitemporal", pattern = r"\.yml");
+
+/// The acts are added to an empty database on their publication dates, and
+/// the database is recalculated until the last queried date. Then the
+/// paragraphs of the queried acts are checked.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestData {
+    acts: Vec<Act>,
+    #[serde(default)]
+    annulments: Vec<TestAnnulment>,
+    queries: Vec<Query>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestAnnulment {
+    decision: DecisionIdentifier,
+    annulment: Annulment,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Query {
+    act: ActIdentifier,
+    date: NaiveDate,
+    known_on: NaiveDate,
+    /// The texts of the paragraphs of the act. Repealed ones are empty.
+    expected: Vec<String>,
+}
+
+pub fn run_test(path: &Path) -> datatest_stable::Result<()> {
+    let test_data: TestData = singleton_yaml::from_slice(&read_all(path)?)?;
+    let tmp_dir = tempfile::tempdir()?;
+    let persistence = Persistence::new(tmp_dir.path().join("db"));
+    let annulments_dir = tmp_dir.path().join("annulments");
+    for TestAnnulment {
+        decision,
+        annulment,
+    } in &test_data.annulments
+    {
+        let year_dir = annulments_dir.join(decision.year.to_string());
+        std::fs::create_dir_all(&year_dir)?;
+        std::fs::write(
+            year_dir.join(format!("{}.yml", decision.number)),
+            singleton_yaml::to_string(annulment)?,
+        )?;
+    }
+    let annulments = AnnulmentSet::load_from(annulments_dir)?;
+    let events = EventRegistry::default();
+
+    let mut first_date = None;
+    for mut act in test_data.acts {
+        act.add_semantic_info()?;
+        let date = act.publication_date;
+        first_date = Some(first_date.map_or(date, |d: NaiveDate| d.min(date)));
+        ActSet::add_new_act(&persistence, act)?;
+    }
+    let first_date = first_date.ok_or("No acts in test")?;
+    let last_date = test_data
+        .queries
+        .iter()
+        .map(|query| query.date.max(query.known_on))
+        .max()
+        .ok_or("No queries in test")?;
+    for date in NaiveDateRange::new(first_date, last_date.succ()) {
+        recalculate_stored_date(&persistence, &annulments, &events, date)?;
+    }
+
+    for query in &test_data.queries {
+        let state = KnownState::load_with_annulments(
+            &persistence,
+            query.date,
+            query.known_on,
+            &annulments,
+        )?;
+        let result = paragraph_texts(&state.act(query.act)?);
+        ensure_eq(
+            &query.expected,
+            &result,
+            &format!(
+                "Wrong text of {} at {} as known on {}",
+                query.act, query.date, query.known_on
+            ),
+        )?;
+    }
+    Ok(())
+}
+
+fn paragraph_texts(act: &Act) -> Vec<String> {
+    let mut result = Vec::new();
+    for child in &act.children {
+        if let ActChild::Article(article) = child {
+            for paragraph in &article.children {
+                if let SAEBody::Text(text) = &paragraph.body {
+                    result.push(text.clone());
+                }
+            }
+        }
+    }
+    result
+}
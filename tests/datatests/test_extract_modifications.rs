@@ -21,10 +21,18 @@ pub fn run_test(path: &Path) -> datatest_stable::Result<()> {
     act.convert_block_amendments()?;
     // Clear remaining quoted blocks to make failing output a bit smaller
     clean_quoted_blocks(&mut act);
+    let result = extract_all_modifications(&act)?;
+    let expected: TestData = singleton_yaml::from_slice(&read_all(path.with_extension("yml"))?)?;
+    ensure_eq(&expected, &result, "Wrong extracted modifiations")?;
+    Ok(())
+}
+
+/// The modifications extracted on each day of the first year of the act
+pub fn extract_all_modifications(act: &Act) -> anyhow::Result<TestData> {
     let mut result: TestData = Default::default();
     for date in act.publication_date.iter_days().take(365) {
         let mut modification_set = AppliableModificationSet::default();
-        modification_set.add(&act, date)?;
+        modification_set.add(act, date)?;
 
         let modifications = modification_set.get_modifications();
         if !modifications.is_empty() {
@@ -35,7 +43,5 @@ pub fn run_test(path: &Path) -> datatest_stable::Result<()> {
             result.insert(date.to_string(), transformed_modifications);
         }
     }
-    let expected: TestData = singleton_yaml::from_slice(&read_all(path.with_extension("yml"))?)?;
-    ensure_eq(&expected, &result, "Wrong extracted modifiations")?;
-    Ok(())
+    Ok(result)
 }
@@ -0,0 +1,71 @@
+// This file is part of AJDB
+// Copyright 2022, Alex Badics
+// All rights reserved.
+
+use std::path::Path;
+
+use ajdb::amender::{
+    AppliableModification, AppliableModificationSet, AppliableModificationType, OnError,
+};
+use hun_law::structure::ChangeCause;
+use hun_law::{structure::Act, util::singleton_yaml};
+use serde::{Deserialize, Serialize};
+
+use super::test_extract_modifications::{extract_all_modifications, TestData as Extracted};
+use crate::declare_test;
+use crate::test_utils::{clean_quoted_blocks, ensure_eq, parse_txt_as_act, read_all};
+
+declare_test!(dir = "data_pending_amendments", pattern = r"\.txt");
+
+/// The txt is the amending act, `pending` are applied to it on its
+/// publication date, before its provisions come into force.
+///
+/// The modifications of the txt act extracted from the `amended_by` acts are
+/// applied too, on the dates they come into force.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TestData {
+    #[serde(default)]
+    pending: Vec<AppliableModificationType>,
+    #[serde(default)]
+    amended_by: Vec<Act>,
+    expected: Extracted,
+}
+
+pub fn run_test(path: &Path) -> datatest_stable::Result<()> {
+    let test_data: TestData = singleton_yaml::from_slice(&read_all(path.with_extension("yml"))?)?;
+    let mut act: Act = parse_txt_as_act(path)?;
+    act.add_semantic_info()?;
+    act.convert_block_amendments()?;
+    let pending = test_data
+        .pending
+        .into_iter()
+        .map(|modification| {
+            AppliableModification::new(ChangeCause::Other("teszt".to_string()), modification)
+        })
+        .collect();
+    let date = act.publication_date;
+    AppliableModificationSet::apply_to_act(&mut act, date, pending, OnError::ReturnErr)?;
+    for mut amending_act in test_data.amended_by {
+        amending_act.add_semantic_info()?;
+        for date in amending_act.publication_date.iter_days().take(365) {
+            let mut modification_set = AppliableModificationSet::default();
+            modification_set.add(&amending_act, date)?;
+            let modifications = modification_set.get_modifications_of(act.identifier);
+            AppliableModificationSet::apply_to_act(
+                &mut act,
+                date,
+                modifications,
+                OnError::ReturnErr,
+            )?;
+        }
+    }
+    // Clear remaining quoted blocks to make failing output a bit smaller
+    clean_quoted_blocks(&mut act);
+    let result = extract_all_modifications(&act)?;
+    ensure_eq(
+        &test_data.expected,
+        &result,
+        "Wrong extracted modifications after the pending amendments",
+    )?;
+    Ok(())
+}
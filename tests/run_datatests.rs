@@ -36,4 +36,9 @@ macro_rules! generate_harness{
     }
 }
 
-generate_harness!(test_extract_modifications, test_apply_modifications,);
+generate_harness!(
+    test_extract_modifications,
+    test_apply_modifications,
+    test_pending_amendments,
+    test_bitemporal,
+);